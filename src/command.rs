@@ -0,0 +1,196 @@
+use rem_utils::check_project;
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+/// Environment variables cargo runs rustc through, e.g. `sccache`
+const RUSTC_WRAPPER_VARS: [&str; 2] = ["RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER"];
+
+/**
+Give the compiles of a repair their own target dir, and the `RUSTFLAGS` they need
+
+Cargo locks the target dir for the whole build, so repairs sharing it with the developer's builds
+(or with each other) wait on every other build, and a different `RUSTFLAGS` invalidates the cached
+artifacts of everyone else using the dir. With a target dir per repair, concurrent repairs neither
+block nor rebuild each other
+
+A rustc wrapper set in the environment is kept (see [`inherit_rustc_wrapper`]), its cache isn't tied
+to the target dir so isolated builds still hit it
+*/
+pub fn isolate_command<'a>(
+    cmd: &'a mut Command,
+    target_dir: impl AsRef<Path>,
+    rustflags: Option<&str>,
+) -> &'a mut Command {
+    cmd.env("CARGO_TARGET_DIR", target_dir.as_ref());
+    if let Some(rustflags) = rustflags {
+        cmd.env("RUSTFLAGS", rustflags);
+    }
+    inherit_rustc_wrapper(cmd)
+}
+
+/// Set the rustc wrapper of the current environment on the command explicitly, so it is passed
+/// through even to a command that doesn't inherit the environment
+pub fn inherit_rustc_wrapper(cmd: &mut Command) -> &mut Command {
+    set_rustc_wrapper(cmd, |var| env::var_os(var))
+}
+
+// sets the wrapper vars `lookup` has a value for on the command
+fn set_rustc_wrapper(cmd: &mut Command, lookup: impl Fn(&str) -> Option<OsString>) -> &mut Command {
+    for var in RUSTC_WRAPPER_VARS {
+        if let Some(value) = lookup(var) {
+            cmd.env(var, value);
+        }
+    }
+    cmd
+}
+
+/// Compile without the rustc wrapper, even when one is set in the environment
+pub fn without_rustc_wrapper(cmd: &mut Command) -> &mut Command {
+    for var in RUSTC_WRAPPER_VARS {
+        cmd.env_remove(var);
+    }
+    cmd
+}
+
+/**
+Compile with the rustup toolchain `toolchain` (`stable`, `nightly-2023-06-01`, ...) whatever the
+default is, like `cargo +toolchain`. The diagnostics the passes parse and the lifetimes rustc
+accepts can differ between toolchains, so a repair should compile with the one that reported the
+errors
+
+Set through `RUSTUP_TOOLCHAIN` rather than a `+toolchain` argument, which has to come first and
+so can't be added to the commands `rem_utils` builds
+*/
+pub fn pin_toolchain<'a>(cmd: &'a mut Command, toolchain: &str) -> &'a mut Command {
+    cmd.env("RUSTUP_TOOLCHAIN", toolchain)
+}
+
+/// The cargo profile and features a project repair compiles, lifetime errors behind a `cfg` only
+/// show up when the build enables it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildProfile {
+    /// `--profile`, `None` checks with cargo's default (dev)
+    pub profile: Option<String>,
+    /// `--features`
+    pub features: Vec<String>,
+    /// `--no-default-features`
+    pub no_default_features: bool,
+}
+
+impl BuildProfile {
+    /// The cargo arguments that select this configuration
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(profile) = &self.profile {
+            args.push("--profile".to_string());
+            args.push(profile.clone());
+        }
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        args
+    }
+}
+
+/// `rem_utils::check_project` for the configuration `profile` describes, to pass to the project
+/// repair loops
+pub fn check_project_with_profile(manifest_path: &str, profile: &BuildProfile) -> Command {
+    let args = profile.cargo_args();
+    check_project(manifest_path, &args.iter().map(String::as_str).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    // the value the command sets `var` to, `Some(None)` when it removes it
+    fn env_of<'a>(cmd: &'a Command, var: &str) -> Option<Option<&'a OsStr>> {
+        cmd.get_envs()
+            .find(|(key, _)| *key == var)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn isolated_command_gets_its_target_dir_and_rustflags() {
+        let mut cmd = Command::new("cargo");
+        isolate_command(&mut cmd, "/tmp/repair-target", Some("-A warnings"));
+        assert_eq!(
+            env_of(&cmd, "CARGO_TARGET_DIR"),
+            Some(Some(OsStr::new("/tmp/repair-target")))
+        );
+        assert_eq!(
+            env_of(&cmd, "RUSTFLAGS"),
+            Some(Some(OsStr::new("-A warnings")))
+        );
+
+        let mut cmd = Command::new("cargo");
+        isolate_command(&mut cmd, "/tmp/repair-target", None);
+        assert_eq!(env_of(&cmd, "RUSTFLAGS"), None);
+    }
+
+    #[test]
+    fn rustc_wrapper_is_passed_through_unless_disabled() {
+        // the environment of the test process is shared with the tests that run cargo
+        let environment = |var: &str| (var == "RUSTC_WRAPPER").then(|| OsString::from("sccache"));
+        let mut cmd = Command::new("cargo");
+        set_rustc_wrapper(&mut cmd, environment);
+        assert_eq!(
+            env_of(&cmd, "RUSTC_WRAPPER"),
+            Some(Some(OsStr::new("sccache")))
+        );
+        assert_eq!(env_of(&cmd, "RUSTC_WORKSPACE_WRAPPER"), None);
+        without_rustc_wrapper(&mut cmd);
+        assert_eq!(env_of(&cmd, "RUSTC_WRAPPER"), Some(None));
+    }
+
+    #[test]
+    fn profile_and_features_are_in_the_check_command() {
+        let profile = BuildProfile {
+            profile: Some("release".to_string()),
+            features: vec!["serde".to_string(), "parallel".to_string()],
+            no_default_features: true,
+        };
+        assert_eq!(
+            profile.cargo_args(),
+            [
+                "--profile",
+                "release",
+                "--features",
+                "serde,parallel",
+                "--no-default-features"
+            ]
+        );
+        assert!(BuildProfile::default().cargo_args().is_empty());
+
+        let cmd = check_project_with_profile("Cargo.toml", &profile);
+        let args: Vec<&OsStr> = cmd.get_args().collect();
+        assert!(
+            args.windows(2)
+                .any(|pair| pair == [OsStr::new("--features"), OsStr::new("serde,parallel")]),
+            "{:?}",
+            args
+        );
+        assert!(
+            args.contains(&OsStr::new("--no-default-features")),
+            "{:?}",
+            args
+        );
+    }
+
+    #[test]
+    fn pinned_toolchain_is_in_the_command() {
+        let mut cmd = check_project("Cargo.toml", &vec![]);
+        pin_toolchain(&mut cmd, "1.70.0");
+        assert_eq!(
+            env_of(&cmd, "RUSTUP_TOOLCHAIN"),
+            Some(Some(OsStr::new("1.70.0")))
+        );
+    }
+}
@@ -1,18 +1,27 @@
-use log::{debug, info};
-use proc_macro2::Span;
+use log::{debug, info, warn};
+use proc_macro2::{Delimiter, Group, LineColumn, Span, TokenStream, TokenTree};
 use quote::ToTokens;
 use regex::Regex;
-use rem_utils::format_source;
+use rem_utils::{check_project, compile_file, format_source};
 use serde::{Deserialize, Serialize};
 use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{BufWriter, Write};
-use std::process::Command;
+use std::io;
+use std::io::{BufRead, BufReader, Lines};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use syn::{
-    visit_mut::VisitMut, ExprCall, ExprMethodCall, FnArg, GenericArgument, GenericParam,
-    ImplItemMethod, ItemFn, Lifetime, PredicateLifetime, ReturnType, Signature, TraitItemMethod,
-    TypeReference, WhereClause, WherePredicate,
+    spanned::Spanned, visit::Visit, visit_mut::VisitMut, AngleBracketedGenericArguments, Attribute,
+    Block, Expr, ExprCall, ExprMethodCall, FnArg, GenericArgument, GenericParam, Generics, Ident,
+    ImplItemMethod, Item, ItemFn, ItemImpl, ItemMod, ItemTrait, Lifetime, LifetimeDef, Lit, Macro,
+    Meta, NestedMeta, ParenthesizedGenericArguments, PathArguments, PredicateLifetime, Receiver,
+    ReturnType, Signature, TraitItemMethod, Type, TypeBareFn, TypeImplTrait, TypeParamBound,
+    TypePath, TypePtr, TypeReference, TypeTraitObject, WhereClause, WherePredicate,
 };
 use std::fmt::{
     self,
@@ -20,6 +29,9 @@ use std::fmt::{
     Formatter
 };
 
+use crate::config::{Edition, ElisionStyle, MethodScope, RepairConfig, WriteDecision};
+use crate::error::RepairError;
+
 pub struct RepairerInput {
     pub input_code: String,
     pub fn_name: String,
@@ -37,788 +49,8808 @@ impl Clone for RepairerInput {
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-////////////////////////////////     REPAIR HELPERS     ////////////////////////////////////////////
+////////////////////////////////     SOURCE PARSING     ////////////////////////////////////////////
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-pub struct RepairResult {
-    pub success: bool,
-    pub repair_count: i32,
-    #[allow(dead_code)]
-    pub has_non_elidible_lifetime: bool,
-    #[allow(dead_code)]
-    pub has_struct_lt: bool,
-}
+// syn 1 can't parse precise capturing (`impl Trait + use<'a>`), so `use<...>` goes through the
+// visitors disguised as a generic bound `__rem_use<...>`. Its lifetimes then count as lifetimes of
+// the output and are never elided, which is right on every edition: 2021 `impl Trait` only captures
+// lifetimes named in its bounds, and an elided lifetime is still in scope for the implicit capture
+// of 2024
+const PRECISE_CAPTURE: &str = "__rem_use";
 
-pub trait RepairSystem: std::fmt::Debug {
-    fn name(&self) -> &str;
-    fn repair_project(&self, src_path: &str, manifest_path: &str, fn_name: &str) -> RepairResult;
-    fn repair_file(&self, file_name: &str, new_file_name: &str) -> RepairResult;
-    fn repair_function(&self, file_name: &str, new_file_name: &str, fn_name: &str) -> RepairResult;
-    fn clone_box(&self) -> Box<dyn RepairSystem>;
+pub fn try_parse_source(source: &str) -> Result<syn::File, RepairError> {
+    let source = disguise_precise_capture(source);
+    syn::parse_str::<syn::File>(&source).map_err(|e| RepairError::parse(None, &e))
 }
 
-impl Clone for Box<dyn RepairSystem> {
-    fn clone(&self) -> Box<dyn RepairSystem> {
-        self.clone_box()
+// `source` with every `use` keyword followed by `<` renamed to `PRECISE_CAPTURE`, leaving alone
+// what only looks like one in comments, string and char literals and raw identifiers (`r#use`).
+// Tolerates source cut off anywhere, `function_name_at_offset` hands it a prefix
+fn disguise_precise_capture(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let ident_char = |c: u8| c == b'_' || c.is_ascii_alphanumeric() || !c.is_ascii();
+    let mut disguised = String::with_capacity(source.len());
+    let (mut copied, mut i) = (0, 0);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                // block comments nest
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            b'"' => i = skip_quoted(bytes, i, b'"'),
+            // a char literal unless it's a lifetime (`'a` not followed by a closing quote)
+            b'\'' => {
+                let c = source[i + 1..].chars().next();
+                let closed = c.is_some_and(|c| bytes.get(i + 1 + c.len_utf8()) == Some(&b'\''));
+                match c {
+                    Some('\\') => i = skip_quoted(bytes, i, b'\''),
+                    Some(_) if closed => i = skip_quoted(bytes, i, b'\''),
+                    _ => i += 1,
+                }
+            }
+            c if ident_char(c) && !c.is_ascii_digit() => {
+                let start = i;
+                while i < bytes.len() && ident_char(bytes[i]) {
+                    i += 1;
+                }
+                let ident = &source[start..i];
+                let hashes = bytes[i..].iter().take_while(|c| **c == b'#').count();
+                match ident {
+                    // raw string, `r#"..."#` and its byte and C string forms
+                    "r" | "br" | "cr" if bytes.get(i + hashes) == Some(&b'"') => {
+                        let closing = format!("\"{}", "#".repeat(hashes));
+                        i += hashes + 1;
+                        i = source[i..]
+                            .find(&closing)
+                            .map_or(bytes.len(), |end| i + end + closing.len());
+                    }
+                    // raw identifier
+                    "r" if hashes == 1 => {
+                        i += 1;
+                        while i < bytes.len() && ident_char(bytes[i]) {
+                            i += 1;
+                        }
+                    }
+                    "use" if source[i..].trim_start().starts_with('<') => {
+                        disguised.push_str(&source[copied..start]);
+                        disguised.push_str(PRECISE_CAPTURE);
+                        copied = i;
+                    }
+                    _ => (),
+                }
+            }
+            _ => i += 1,
+        }
     }
+    disguised.push_str(&source[copied..]);
+    disguised
 }
 
-// Wrapper for RepairSystem trait objects that implements Debug
-pub struct DebugRepairSystem<'a>(pub &'a dyn RepairSystem);
-
-impl<'a> Debug for DebugRepairSystem<'a> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("RepairSystem")
-            .field("name", &self.0.name())
-            .finish()
+// the end of the string or char literal opened by the `quote` at `start`, past escapes
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
     }
+    bytes.len()
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RustcError {
-    pub rendered: String,
-    pub spans: Vec<RustcSpan>,
+// `tokens` with the `PRECISE_CAPTURE` idents back to `use`
+fn restore_precise_capture(tokens: TokenStream) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            TokenTree::Ident(ident) if ident == PRECISE_CAPTURE => {
+                TokenTree::Ident(Ident::new("use", ident.span()))
+            }
+            TokenTree::Group(group) => {
+                let mut restored =
+                    Group::new(group.delimiter(), restore_precise_capture(group.stream()));
+                restored.set_span(group.span());
+                TokenTree::Group(restored)
+            }
+            tt => tt,
+        })
+        .collect()
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RustcSpan {
-    pub file_name: String,
+/// Read and parse `file_name`, errors name the file
+pub fn parse_file(file_name: &str) -> Result<syn::File, RepairError> {
+    let source = read_source(file_name)?;
+    parse_file_source(file_name, &source)
 }
 
-pub fn repair_standard_help(stderr: &str, new_file_name: &str) -> bool {
-    let deserializer = serde_json::Deserializer::from_str(stderr);
-    let stream = deserializer.into_iter::<RustcError>();
-    let mut helped = false;
-    for item in stream {
-        let rendered = match item {
-            Ok(i) => i.rendered,
-            Err(_) => stderr.to_string(),
-        };
-        let re = Regex::new(r"help: consider.+\n.*\n(?P<line_number>\d+) \| (?P<replacement>.+)\n")
-            .unwrap();
-        let help_lines = re.captures_iter(rendered.as_str());
-
-        let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
+// the content of `file_name`, errors name the file
+fn read_source(file_name: &str) -> Result<String, RepairError> {
+    fs::read_to_string(file_name).map_err(|e| RepairError::io(file_name, e))
+}
 
-        let lines = file_content.split("\n");
-        let mut lines_modifiable = Vec::new();
-        for (_, line) in lines.enumerate() {
-            lines_modifiable.push(line);
-        }
+/// The content of `file_name` and its parse, errors name the file and the function `fn_name` being
+/// repaired
+pub fn read_target(file_name: &str, fn_name: &str) -> Result<(String, syn::File), RepairError> {
+    let source = read_source(file_name)?;
+    let file = parse_file_source(file_name, &source).map_err(|e| e.in_function(fn_name))?;
+    Ok((source, file))
+}
 
-        let mut current_line = 0;
+/// Write `content` to `file_name` as is, errors name the file
+pub fn write_file(file_name: &str, content: &str) -> Result<(), RepairError> {
+    fs::write(file_name, content).map_err(|e| RepairError::io(file_name, e))
+}
 
-        let out_file = fs::File::create(&new_file_name).unwrap();
-        let mut writer = BufWriter::new(out_file);
-        for captured in help_lines {
-            /*
-            println!(
-                "line: {:?}, fn: {:?} {}",
-                &captured["line_number"], &captured["replacement"], current_line,
-            );
-             */
+// `try_parse_source` for the content of `file_name`, errors name the file
+fn parse_file_source(file_name: &str, source: &str) -> Result<syn::File, RepairError> {
+    try_parse_source(source).map_err(|e| match e {
+        RepairError::Parse {
+            fn_name,
+            line,
+            column,
+            message,
+            ..
+        } => RepairError::Parse {
+            file: Some(file_name.to_string()),
+            fn_name,
+            line,
+            column,
+            message,
+        },
+        e => e,
+    })
+}
 
-            let line_number = match captured["line_number"].parse::<usize>() {
-                Ok(n) => n,
-                Err(_) => continue,
-            };
-            let replacement = &captured["replacement"];
-            if replacement.contains("&'lifetime") {
-                continue;
-            }
+/**
+Inverse of `try_parse_source` for the file or any item in it, the result still has to go through
+`format_source`
 
-            helped = true;
-            while current_line < line_number - 1 {
-                writeln!(writer, "{}", lines_modifiable[current_line]).unwrap();
-                current_line += 1;
-            }
-            writeln!(writer, "{}", replacement).unwrap();
-            current_line += 1;
-        }
-        while current_line < lines_modifiable.len() {
-            writeln!(writer, "{}", lines_modifiable[current_line]).unwrap();
-            current_line += 1;
-        }
-    }
-    helped
+Attributes and doc comments (`#[doc]` attributes to syn) belong to the items and print with them,
+the visitors only ever rewrite signatures in place so they are never dropped. Plain comments are
+lost, short of marker comments kept by `RepairConfig::preserve_markers`
+*/
+pub fn print_source(tokens: &impl ToTokens) -> String {
+    restore_precise_capture(tokens.to_token_stream()).to_string()
 }
 
-struct FnLifetimeBounder<'a> {
-    fn_name: &'a str,
-    lifetime: &'a str,
-    bound: &'a str,
-    success: bool,
+/**
+`format_source` keeping the final newlines of `original`, which `source` was printed from: rustfmt
+ends every file in exactly one, a repair shouldn't add or drop one
+*/
+pub fn format_source_like(original: &str, source: &str) -> String {
+    let ending = &original[original.trim_end_matches(['\r', '\n']).len()..];
+    let formatted = format_source(source);
+    format!("{}{}", formatted.trim_end_matches(['\r', '\n']), ending)
 }
 
-impl VisitMut for FnLifetimeBounder<'_> {
-    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
-        let id = i.sig.ident.to_string();
-        //println!("caller name: {}, at: {}", self.caller_fn_name, &id);
-        match id == self.fn_name.to_string() {
-            false => (),
-            true => self.fn_lifetime_bounder(&mut i.sig),
-        }
-        syn::visit_mut::visit_impl_item_method_mut(self, i);
-    }
+/**
+The source of `file`, an edited parse of `original`, reprinting only the top-level items the edit
+changed: the rest of `original` is kept byte for byte, so an iteration of a repair loop doesn't
+reformat (and shift the lines of) code it didn't touch. The changed items go through
+`format_source`, the loops format the whole file once they are done
 
-    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
-        let id = i.sig.ident.to_string();
-        match id == self.fn_name.to_string() {
-            false => (),
-            true => self.fn_lifetime_bounder(&mut i.sig),
-        }
+Falls back to formatting all of `file` if the items can't be matched up with `original`
+*/
+pub fn print_edited(original: &str, file: &syn::File) -> String {
+    let reprint = || format_source_like(original, &print_source(file));
+    // spans of the parse refer to `original` exactly, unlike those of `try_parse_source`
+    let parsed = match syn::parse_str::<syn::File>(original) {
+        Ok(parsed) => parsed,
+        Err(_) => return reprint(),
+    };
+    let unchanged = |before: &dyn ToTokens, after: &dyn ToTokens| {
+        before.to_token_stream().to_string() == after.to_token_stream().to_string()
+    };
+    let same_attrs = parsed.attrs.len() == file.attrs.len()
+        && parsed
+            .attrs
+            .iter()
+            .zip(file.attrs.iter())
+            .all(|(before, after)| unchanged(before, after));
+    if parsed.items.len() != file.items.len() || !same_attrs {
+        return reprint();
     }
-
-    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
-        let id = i.sig.ident.to_string();
-        //println!("caller name: {}, at: {}", self.caller_fn_name, &id);
-        match id == self.fn_name.to_string() {
-            false => (),
-            true => self.fn_lifetime_bounder(&mut i.sig),
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(original.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let offset = |position: LineColumn| {
+        let start = line_starts[position.line - 1];
+        let column: usize = original[start..]
+            .chars()
+            .take(position.column)
+            .map(char::len_utf8)
+            .sum();
+        start + column
+    };
+    let mut edited = String::new();
+    let mut copied = 0;
+    for (before, after) in parsed.items.iter().zip(file.items.iter()) {
+        if unchanged(before, after) {
+            continue;
         }
-        syn::visit_mut::visit_trait_item_method_mut(self, i);
+        let span = before.span();
+        let (start, end) = (offset(span.start()), offset(span.end()));
+        if start < copied || end < start {
+            return reprint();
+        }
+        edited.push_str(&original[copied..start]);
+        edited.push_str(format_source(&print_source(after)).trim_end());
+        copied = end;
     }
+    edited.push_str(&original[copied..]);
+    edited
 }
 
-impl FnLifetimeBounder<'_> {
-    fn fn_lifetime_bounder(&mut self, sig: &mut Signature) {
-        let gen = &mut sig.generics;
-        let wc = gen.where_clause.get_or_insert(WhereClause {
-            where_token: Default::default(),
-            predicates: Default::default(),
-        });
-        let mut wp = PredicateLifetime {
-            lifetime: Lifetime::new(self.lifetime, Span::call_site()),
-            colon_token: Default::default(),
-            bounds: Default::default(),
-        };
-        wp.bounds.push(Lifetime::new(self.bound, Span::call_site()));
-        wc.predicates.push(WherePredicate::Lifetime(wp));
-        self.success = true
-    }
+/// A comment marking a function (`// EXTRACTED`), see `RepairConfig::preserve_markers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkerComment {
+    /// Ident of the function the comment is on
+    pub fn_ident: String,
+    /// Which of the functions with that ident, counted in file order from 0
+    pub occurrence: usize,
+    /// The comment as written, `//` included
+    pub comment: String,
+    /// Whether it trails the line of the `fn` rather than standing on a line above it
+    pub trailing: bool,
 }
 
-pub fn repair_bounds_help(stderr: &str, new_file_name: &str, fn_name: &str) -> bool {
-    let deserializer = serde_json::Deserializer::from_str(stderr);
-    let stream = deserializer.into_iter::<RustcError>();
-    let mut helped = false;
-    for item in stream {
-        let rendered = match item {
-            Ok(i) => i.rendered,
-            Err(_) => stderr.to_string(),
+// `fn` and the ident after it, compiled once as every write of a repair keeping markers matches it
+fn fn_ident_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\bfn\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap())
+}
+
+/**
+The line comments containing `marker` in `source` that are on a function: either trailing the line
+of its `fn` or above it, with nothing but attributes and other comments in between
+*/
+pub fn marker_comments(source: &str, marker: &str) -> Vec<MarkerComment> {
+    let fn_re = fn_ident_regex();
+    let lines: Vec<&str> = source.lines().collect();
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    let mut pending = vec![];
+    let mut markers = vec![];
+    for line in lines.iter() {
+        let (code, comment) = match line.find("//") {
+            Some(start) => (&line[..start], Some(line[start..].trim_end())),
+            None => (*line, None),
         };
-        let re = Regex::new(r"= help: consider.+bound: `(?P<constraint_lhs>'[a-z0-9]+): (?P<constraint_rhs>'[a-z0-9]+)`").unwrap();
-        let help_lines = re.captures_iter(rendered.as_str());
-        /*
-            &caps["line_number"],
-            &caps["fn_sig"],
-            &caps["constraint_lhs"],
-            &caps["constraint_rhs"],
-        */
-        for captured in help_lines {
-            // println!("found helps: {}, {}",
-            //          &captured["constraint_lhs"],
-            //          &captured["constraint_rhs"]);
-            let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-            let mut file = syn::parse_str::<syn::File>(file_content.as_str())
-                .map_err(|e| format!("{:?}", e))
-                .unwrap();
-            let mut visit = FnLifetimeBounder {
-                fn_name,
-                lifetime: &captured["constraint_lhs"],
-                bound: &captured["constraint_rhs"],
-                success: false,
-            };
-            visit.visit_file_mut(&mut file);
-            let file = file.into_token_stream().to_string();
-            match visit.success {
-                true => {
-                    fs::write(new_file_name.to_string(), format_source(&file)).unwrap();
-                    helped = true;
+        let comment = comment.filter(|comment| comment.contains(marker));
+        match fn_re.captures(code) {
+            Some(captured) => {
+                let ident = captured.get(1).unwrap().as_str();
+                let occurrence = occurrences.entry(ident).or_insert(0);
+                let above = pending.drain(..).map(|comment: &str| (comment, false));
+                let trailing = comment.map(|comment| (comment, true));
+                for (comment, trailing) in above.chain(trailing) {
+                    markers.push(MarkerComment {
+                        fn_ident: ident.to_string(),
+                        occurrence: *occurrence,
+                        comment: comment.to_string(),
+                        trailing,
+                    });
                 }
-                false => (),
+                *occurrence += 1;
             }
+            None if code.trim().is_empty() => pending.extend(comment),
+            // an attribute keeps the comments above it with the function it belongs to
+            None if code.trim_start().starts_with("#[") => (),
+            None => pending.clear(),
         }
     }
-    helped
+    markers
 }
 
-pub fn repair_iteration(
-    compile_cmd: &mut Command,
-    process_errors: &dyn Fn(&str) -> bool,
-    print_stats: bool,
-    max_iterations: Option<i32>,
-) -> RepairResult {
-    let mut count = 0;
-    let max_iterations = max_iterations.unwrap_or(25);
-    let mut repair_result = RepairResult {
-        success: false,
-        repair_count: 0,
-        has_non_elidible_lifetime: false,
-        has_struct_lt: false,
-    };
-
-    let success = loop {
-        let out = compile_cmd.output().unwrap();
-        let stderr = String::from_utf8_lossy(&out.stderr);
-        if out.status.success() {
-            break true;
-        }
-        count += 1;
-
-        let temp = stderr.to_string();
-        if !process_errors(temp.as_str()) {
-            break false;
+/// Put `markers` back on their functions in `source`, those still there are left as they are
+pub fn reattach_markers(source: &str, markers: &[MarkerComment]) -> String {
+    if markers.is_empty() {
+        return source.to_string();
+    }
+    let fn_re = fn_ident_regex();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut out: Vec<String> = vec![];
+    for line in source.lines() {
+        let code = line.find("//").map_or(line, |start| &line[..start]);
+        let captured = match fn_re.captures(code) {
+            None => {
+                out.push(line.to_string());
+                continue;
+            }
+            Some(captured) => captured,
+        };
+        let ident = captured.get(1).unwrap().as_str();
+        let count = occurrences.entry(ident.to_string()).or_insert(0);
+        let occurrence = *count;
+        *count += 1;
+        let on_fn = markers
+            .iter()
+            .filter(|marker| marker.fn_ident == ident && marker.occurrence == occurrence);
+        let indent = &line[..line.len() - line.trim_start().len()];
+        // above the attributes of the function, where the comment was written
+        let mut above = out.len();
+        while above > 0 && out[above - 1].trim_start().starts_with("#[") {
+            above -= 1;
         }
-        if max_iterations == count {
-            break false;
+        let mut line = line.to_string();
+        for marker in on_fn {
+            match marker.trailing {
+                true if !line.contains(&marker.comment) => {
+                    line = format!("{} {}", line, marker.comment)
+                }
+                false
+                    if !out[..above]
+                        .last()
+                        .is_some_and(|l| l.contains(&marker.comment)) =>
+                {
+                    out.insert(above, format!("{}{}", indent, marker.comment));
+                    above += 1;
+                }
+                _ => (),
+            }
         }
-    };
-
-    if print_stats {
-        info!("repair count: {}", count);
-        info!("status: {}", success);
+        out.push(line);
     }
-
-    repair_result.success = success;
-    repair_result.repair_count = count;
-    repair_result
+    let mut restored = out.join("\n");
+    if source.ends_with('\n') {
+        restored.push('\n');
+    }
+    restored
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-////////////////////////////////    ELIDING LIFETIMES   ////////////////////////////////////////////
+////////////////////////////////    FUNCTION SELECTORS  ////////////////////////////////////////////
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-struct FnLifetimeEliderTypeHelper<'a> {
-    cannot_elide: &'a Vec<String>,
-    lt_count: &'a HashMap<&'a String, i32>,
+/**
+Target of a repair, given either as a bare function name (`bar_extracted`) or qualified by the path
+to it in the file: the inline modules around it and the type of the enclosing impl (`Foo`, or
+`<Foo as Trait>` for a trait impl) or the name of the enclosing trait, as in `a::Foo::bar_extracted`
+
+A bare name matches every function with that ident. A qualified name matches the functions whose
+path ends in the qualifier, or is the end of it: `Foo::bar` selects the method of `impl Foo` in
+any module, and the paths rustc prints in diagnostics work too (`crate::a::Foo::bar_extracted`,
+`Foo<'a>::bar_extracted`). Naming the type alone matches trait impls for it as well, `<Foo as
+Trait>::bar` only the impl of `Trait` and `<Foo>::bar` only the inherent impls. A qualifier
+starting with `self` is anchored at the top of the file, `self::Foo::bar` skips the `Foo` of any
+module
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FnSelector<'a> {
+    /// The path before the name, as written
+    pub qualifier: Option<&'a str>,
+    pub name: &'a str,
 }
 
-impl VisitMut for FnLifetimeEliderTypeHelper<'_> {
-    // don't elide fully just replace with '_
-    // fn visit_angle_bracketed_generic_arguments_mut(
-    //     &mut self,
-    //     i: &mut AngleBracketedGenericArguments,
-    // ) {
-    //     i.args = i
-    //         .args
-    //         .clone()
-    //         .into_iter()
-    //         .filter(|arg| match arg {
-    //             GenericArgument::Lifetime(lt) => {
-    //                 let id = lt.to_string();
-    //                 if !self.lt_count.contains_key(&id) {
-    //                     false
-    //                 } else {
-    //                     let result =
-    //                         self.cannot_elide.contains(&id) || *self.lt_count.get(&id).unwrap() > 1;
-    //                     result
-    //                 }
-    //             }
-    //             _ => true,
-    //         })
-    //         .collect();
-    //     syn::visit_mut::visit_angle_bracketed_generic_arguments_mut(self, i);
-    // }
+impl<'a> FnSelector<'a> {
+    pub fn parse(fn_name: &'a str) -> Self {
+        match fn_name.rsplit_once("::") {
+            Some((qualifier, name)) => FnSelector {
+                qualifier: Some(qualifier),
+                name,
+            },
+            None => FnSelector {
+                qualifier: None,
+                name: fn_name,
+            },
+        }
+    }
 
-    fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
-        match &mut i.lifetime {
-            None => (),
-            Some(lt) => {
-                let id = lt.to_string();
-                if !self.cannot_elide.contains(&id)
-                    && (!self.lt_count.contains_key(&id) || *self.lt_count.get(&id).unwrap() <= 1)
-                {
-                    i.lifetime = None
-                }
+    /// `enclosing` is the scope the function is declared in (see `impl_scope`), `None` for free
+    /// functions at the top of the file or in a function body
+    pub fn matches(&self, ident: &Ident, enclosing: Option<&str>) -> bool {
+        *ident == self.name
+            && match self.qualifier {
+                None => true,
+                Some(qualifier) => qualifier_matches(qualifier, enclosing.unwrap_or("")),
             }
-        };
-        syn::visit_mut::visit_type_reference_mut(self, i);
     }
 }
 
-struct FnLifetimeEliderArgHelper<'a> {
-    cannot_elide: &'a Vec<String>,
-    lt_count: &'a HashMap<&'a String, i32>,
+// one segment of a qualifier or scope: a module, type or trait name, with the trait of a trait impl.
+// `inherent` for a type written `<Foo>`, which only stands for its inherent impls
+#[derive(Debug, PartialEq, Eq)]
+struct PathSegment<'a> {
+    name: &'a str,
+    trait_name: Option<&'a str>,
+    inherent: bool,
 }
 
-impl VisitMut for FnLifetimeEliderArgHelper<'_> {
-    fn visit_fn_arg_mut(&mut self, i: &mut FnArg) {
-        match i {
-            FnArg::Typed(t) => {
-                let mut type_helper = FnLifetimeEliderTypeHelper {
-                    cannot_elide: self.cannot_elide,
-                    lt_count: self.lt_count,
-                };
-                type_helper.visit_type_mut(t.ty.as_mut());
+// the segments of a path split on the `::` outside of angle brackets, `Foo` out of `Foo<'a>`, `&Foo`
+// and `<Foo>`, type and trait out of `<a::Foo as b::Trait>`
+fn path_segments(path: &str) -> Vec<PathSegment<'_>> {
+    let mut segments = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in path.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ':' if depth == 0 && path[i..].starts_with("::") => {
+                segments.push(&path[start..i]);
+                start = i + 2;
             }
-            FnArg::Receiver(_) => (), // cannot elide if there is self
+            _ => (),
         }
     }
+    segments.push(&path[start..]);
+    segments
+        .into_iter()
+        .map(|segment| segment.trim().trim_start_matches('&').trim())
+        .filter(|segment| !segment.is_empty() && !segment.starts_with(':'))
+        .map(|segment| {
+            let qualified = segment.strip_prefix('<').and_then(|q| q.strip_suffix('>'));
+            match qualified.map(|q| q.split_once(" as ").ok_or(q)) {
+                Some(Ok((ty, trait_path))) => PathSegment {
+                    name: last_path_ident(ty),
+                    trait_name: Some(last_path_ident(trait_path)),
+                    inherent: false,
+                },
+                Some(Err(ty)) => PathSegment {
+                    name: last_path_ident(ty),
+                    trait_name: None,
+                    inherent: true,
+                },
+                None => PathSegment {
+                    name: segment.split('<').next().unwrap_or(segment).trim(),
+                    trait_name: None,
+                    inherent: false,
+                },
+            }
+        })
+        .collect()
 }
 
-struct FnLifetimeElider<'a> {
-    fn_name: &'a str,
-    annotations_left: bool,
-    has_struct_lt: bool,
+// `Foo` out of `a::Foo<'a>`
+fn last_path_ident(path: &str) -> &str {
+    let path = path.trim().trim_start_matches('&').trim();
+    let path = path.split('<').next().unwrap_or(path);
+    path.rsplit("::").next().unwrap_or(path).trim()
 }
 
-struct LtGetterElider<'a> {
-    v: &'a mut Vec<String>,
+// whether the scope `enclosing` ends in `qualifier` or is the end of it, see `FnSelector`
+fn qualifier_matches(qualifier: &str, enclosing: &str) -> bool {
+    let mut qualifier = path_segments(qualifier);
+    let scope = path_segments(enclosing);
+    let anchored = qualifier
+        .first()
+        .is_some_and(|segment| segment.name == "self");
+    if anchored {
+        qualifier.remove(0);
+    }
+    let from_root = qualifier
+        .iter()
+        .take_while(|segment| segment.name == "crate" || segment.name == "super")
+        .count();
+    qualifier.drain(..from_root);
+    let overlap = qualifier.len().min(scope.len());
+    let tails_match = qualifier[qualifier.len() - overlap..]
+        .iter()
+        .zip(&scope[scope.len() - overlap..])
+        .all(|(q, s)| {
+            q.name == s.name
+                && match (q.inherent, q.trait_name) {
+                    (true, _) => s.trait_name.is_none(),
+                    (false, None) => true,
+                    (false, trait_name) => trait_name == s.trait_name,
+                }
+        });
+    match anchored {
+        true => qualifier.len() == scope.len() && tails_match,
+        false => overlap > 0 && tails_match,
+    }
 }
-impl VisitMut for LtGetterElider<'_> {
-    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
-        let id = i.to_string();
-        self.v.push(id.to_string());
-        syn::visit_mut::visit_lifetime_mut(self, i)
+
+/// Name of the type an impl is for, stripped of generics (`Foo` for `impl<'a> Foo<'a>`)
+pub fn impl_type_name(self_ty: &Type) -> Option<String> {
+    match self_ty {
+        Type::Path(p) => p.path.segments.last().map(|seg| seg.ident.to_string()),
+        Type::Reference(r) => impl_type_name(r.elem.as_ref()),
+        Type::Paren(p) => impl_type_name(p.elem.as_ref()),
+        Type::Group(g) => impl_type_name(g.elem.as_ref()),
+        _ => None,
     }
 }
 
-struct ChangeLtHelperElider<'a> {
-    map: &'a HashMap<String, String>,
-    has_struct_lt: bool,
+/// Selector of the function `ident` in `enclosing` (`None` for free functions at the top of the
+/// file), the path to it. See `functions_matching` for one that picks out exactly that function
+pub fn selector_of(ident: &Ident, enclosing: Option<&str>) -> String {
+    match enclosing.map(|enclosing| enclosing.trim_end_matches(':')) {
+        Some(enclosing) if !enclosing.is_empty() => format!("{}::{}", enclosing, ident),
+        _ => ident.to_string(),
+    }
 }
 
-impl VisitMut for ChangeLtHelperElider<'_> {
-    fn visit_generic_argument_mut(&mut self, i: &mut GenericArgument) {
-        debug!("generic: {:?}", i);
-        match i {
-            GenericArgument::Lifetime(l) => {
-                let id = l.to_string();
-                debug!("generic lt: {:?}", &id);
-                self.has_struct_lt = true;
-                match self.map.get(&id) {
-                    Some(new_lt) => *l = Lifetime::new(new_lt.as_str(), Span::call_site()),
-                    None => *l = Lifetime::new("'_", Span::call_site()),
-                }
-            }
-            _ => syn::visit_mut::visit_generic_argument_mut(self, i),
-        }
+/*
+The scope the visitors track as `enclosing` is the path a selector qualifies a function by: the
+inline modules around it, each followed by `::` (`a::b::`), then the type of the impl (`a::Foo`,
+`a::<Foo as Trait>`) or the name of the trait the function is in. Functions in a function body are
+in the modules around them only, like free functions
+*/
+
+// the modules part of `enclosing`, `a::` out of `a::Foo`
+fn module_scope(enclosing: Option<&str>) -> &str {
+    let enclosing = enclosing.unwrap_or("");
+    if enclosing.ends_with("::") {
+        return enclosing;
     }
-    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
-        let id = i.to_string();
-        match self.map.get(&id) {
-            Some(new_lt) => *i = Lifetime::new(new_lt.as_str(), Span::call_site()),
-            None => (),
+    let mut depth = 0;
+    let mut end = 0;
+    for (i, c) in enclosing.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ':' if depth == 0 && enclosing[i..].starts_with("::") => end = i + 2,
+            _ => (),
         }
-        syn::visit_mut::visit_lifetime_mut(self, i)
     }
+    &enclosing[..end]
 }
 
-impl VisitMut for FnLifetimeElider<'_> {
-    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
-        let id = i.sig.ident.to_string();
-        //println!("caller name: {}, at: {}", self.caller_fn_name, &id);
-        match id == self.fn_name.to_string() {
-            false => (),
-            true => self.fn_lifetime_elider(&mut i.sig),
-        }
-        syn::visit_mut::visit_impl_item_method_mut(self, i);
-    }
+/// Scope of the items of the inline module `ident` declared in `enclosing`
+pub fn mod_scope(enclosing: Option<&str>, ident: &Ident) -> Option<String> {
+    Some(format!("{}{}::", module_scope(enclosing), ident))
+}
 
-    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
-        let id = i.sig.ident.to_string();
-        match id == self.fn_name.to_string() {
-            false => (),
-            true => self.fn_lifetime_elider(&mut i.sig),
-        }
+/// Scope of the methods of `item` declared in `enclosing`: the impl's type, as `<Foo as Trait>` for
+/// a trait impl. `None` for types `impl_type_name` doesn't name
+pub fn impl_scope(enclosing: Option<&str>, item: &ItemImpl) -> Option<String> {
+    let ty = impl_type_name(&item.self_ty)?;
+    let module = module_scope(enclosing);
+    Some(match &item.trait_ {
+        Some((_, path, _)) => format!(
+            "{}<{} as {}>",
+            module,
+            ty,
+            path.segments
+                .last()
+                .map_or(String::new(), |seg| seg.ident.to_string())
+        ),
+        None => format!("{}{}", module, ty),
+    })
+}
+
+/// Scope of the methods of the trait `ident` declared in `enclosing`
+pub fn trait_scope(enclosing: Option<&str>, ident: &Ident) -> Option<String> {
+    Some(format!("{}{}", module_scope(enclosing), ident))
+}
+
+/// Scope of a free function declared in `enclosing`, the modules around it
+pub fn fn_scope(enclosing: Option<&str>) -> Option<String> {
+    Some(module_scope(enclosing).to_string()).filter(|module| !module.is_empty())
+}
+
+/**
+Whether `sig` is an `extern` or variadic function, logging that its lifetimes are left alone
+
+Such functions rarely carry Rust lifetimes, and a variadic `...` can't take every lifetime construct
+the visitors would introduce
+*/
+pub fn skips_lifetimes(sig: &Signature) -> bool {
+    let foreign = sig.abi.is_some() || sig.variadic.is_some();
+    if foreign {
+        info!("skipping lifetimes of extern/variadic fn {}", sig.ident);
     }
+    foreign
+}
 
-    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
-        let id = i.sig.ident.to_string();
-        //println!("caller name: {}, at: {}", self.caller_fn_name, &id);
-        match id == self.fn_name.to_string() {
-            false => (),
-            true => self.fn_lifetime_elider(&mut i.sig),
+/**
+Whether the signature of a function with these attributes is fixed by what calls it, logging that
+its lifetimes are left alone: the free `fn main` (`enclosing` is `None`) and functions marked
+`#[start]` or `#[no_mangle]`
+
+Rustc rejects `fn main<'lt0>`, and a symbol called from outside Rust can't have the signature it
+was declared with changed under it
+*/
+pub fn has_fixed_signature(attrs: &[Attribute], sig: &Signature, enclosing: Option<&str>) -> bool {
+    let entry_point = enclosing.is_none() && sig.ident == "main";
+    let fixed = entry_point
+        || attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("start") || attr.path.is_ident("no_mangle"));
+    if fixed {
+        info!(
+            "skipping lifetimes of fn {}, its signature is fixed",
+            sig.ident
+        );
+    }
+    fixed
+}
+
+/**
+Whether the `#[cfg]` attributes among `attrs` hold for a compile enabling the cfg options `active`
+(`test`, `unix`, `feature = "x"`, written as in the attribute). An item behind a cfg the compile
+left out never reached rustc, its diagnostics say nothing about it
+
+Without `active` no cfg can be confirmed to hold, only items without one are active
+*/
+pub fn cfg_active(attrs: &[Attribute], active: Option<&[String]>) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("cfg"))
+        .all(|attr| match (active, attr.parse_meta()) {
+            (Some(active), Ok(Meta::List(list))) if list.nested.len() == 1 => {
+                cfg_holds(&list.nested[0], active)
+            }
+            _ => false,
+        })
+}
+
+// evaluates a cfg predicate (`all(unix, not(test))`), options are compared without whitespace
+fn cfg_holds(predicate: &NestedMeta, active: &[String]) -> bool {
+    let enabled = |option: String| {
+        active
+            .iter()
+            .any(|cfg| cfg.split_whitespace().collect::<String>() == option)
+    };
+    match predicate {
+        NestedMeta::Meta(Meta::Path(path)) => enabled(path.to_token_stream().to_string()),
+        NestedMeta::Meta(Meta::NameValue(nv)) => match &nv.lit {
+            Lit::Str(value) => enabled(format!(
+                "{}=\"{}\"",
+                nv.path.to_token_stream(),
+                value.value()
+            )),
+            _ => false,
+        },
+        NestedMeta::Meta(Meta::List(list)) => {
+            let mut nested = list.nested.iter();
+            match list
+                .path
+                .get_ident()
+                .map(|ident| ident.to_string())
+                .as_deref()
+            {
+                Some("all") => nested.all(|predicate| cfg_holds(predicate, active)),
+                Some("any") => nested.any(|predicate| cfg_holds(predicate, active)),
+                Some("not") if list.nested.len() == 1 => !cfg_holds(&list.nested[0], active),
+                _ => false,
+            }
         }
-        syn::visit_mut::visit_trait_item_method_mut(self, i);
+        NestedMeta::Lit(_) => false,
     }
 }
 
-impl FnLifetimeElider<'_> {
-    fn fn_lifetime_elider(&mut self, sig: &mut Signature) {
-        // println!("original : {}", i.sig.clone().into_token_stream().to_string());
-        let gen = &mut sig.generics;
-        let mut cannot_elide = vec![];
-        match &gen.where_clause {
-            None => (),
-            Some(wc) => wc.predicates.iter().for_each(|wp| match wp {
-                WherePredicate::Lifetime(lt) => {
-                    cannot_elide.push(lt.lifetime.to_string());
-                    cannot_elide.push(lt.bounds.first().unwrap().to_string())
-                }
-                _ => (),
-            }),
+// whether a selected function is left out of the compile, by its own cfg or that of the impl,
+// trait or module around it (`inactive`), logging that it is skipped
+fn gated_off(
+    attrs: &[Attribute],
+    sig: &Signature,
+    active: Option<&[String]>,
+    inactive: bool,
+) -> bool {
+    let gated = inactive || !cfg_active(attrs, active);
+    if gated {
+        info!(
+            "skipping lifetimes of fn {}, nothing confirms its cfg is active",
+            sig.ident
+        );
+    }
+    gated
+}
+
+/**
+Where a visitor is in the file: the scope of the items it is among, as a selector qualifies them
+(see `impl_scope`), and whether the cfg of an impl, trait or module around them isn't active. The
+visitors that select functions embed one, `enter` it at each impl, trait, module and free function
+whose items they visit and `leave` it again after
+*/
+pub struct Scope<'a> {
+    enclosing: Option<String>,
+    /// See `RepairConfig::active_cfg`
+    active_cfg: Option<&'a [String]>,
+    /// Inside an impl, trait or module whose cfg isn't active
+    inactive: bool,
+}
+
+/// What `Scope::leave` restores
+pub struct Outer(Option<String>, bool);
+
+impl<'a> Scope<'a> {
+    pub fn new(active_cfg: Option<&'a [String]>) -> Self {
+        Scope {
+            enclosing: None,
+            active_cfg,
+            inactive: false,
         }
-        match sig.output.borrow_mut() {
-            ReturnType::Default => (),
-            ReturnType::Type(_, ty) => {
-                let mut get_lt = LtGetterElider {
-                    v: &mut cannot_elide,
-                };
-                get_lt.visit_type_mut(ty.clone().as_mut());
-            }
-        };
+    }
 
-        let inputs = &mut sig.inputs;
-        let mut has_receiver = false;
-        let mut map = HashMap::new();
-        let mut v = vec![];
-        inputs.iter_mut().for_each(|fn_arg| {
-            match fn_arg {
-                FnArg::Receiver(_) => has_receiver = true,
-                FnArg::Typed(_) => {
-                    let mut get_lt = LtGetterElider { v: &mut v };
-                    get_lt.visit_fn_arg_mut(fn_arg)
-                }
-            };
-        });
-        match has_receiver {
-            true => (),
-            false => {
-                match sig.output.borrow_mut() {
-                    ReturnType::Default => (),
-                    ReturnType::Type(_, ty) => {
-                        let mut get_lt = LtGetterElider { v: &mut v };
-                        get_lt.visit_type_mut(ty.clone().as_mut());
-                    }
-                };
-                gen.params.iter_mut().for_each(|gp| match gp {
-                    GenericParam::Lifetime(_) => (),
-                    gp => {
-                        let mut get_lt = LtGetterElider { v: &mut v };
-                        get_lt.visit_generic_param_mut(gp);
-                    }
-                });
-                v.iter().for_each(|lt| {
-                    match map.contains_key(lt) {
-                        true => map.insert(lt, *map.get(lt).unwrap() + 1),
-                        false => map.insert(lt, 1),
-                    };
-                });
-                let mut fn_arg_helper = FnLifetimeEliderArgHelper {
-                    cannot_elide: &cannot_elide,
-                    lt_count: &map,
-                };
-                inputs
-                    .iter_mut()
-                    .for_each(|fn_arg| fn_arg_helper.visit_fn_arg_mut(fn_arg));
+    /// Scope of the methods here, `None` at the top of the file
+    pub fn enclosing(&self) -> Option<&str> {
+        self.enclosing.as_deref()
+    }
 
-                match sig.output.borrow_mut() {
-                    ReturnType::Default => (),
-                    ReturnType::Type(_, ty) => {
-                        let mut type_helper = FnLifetimeEliderTypeHelper {
-                            cannot_elide: &cannot_elide,
-                            lt_count: &map,
-                        };
-                        type_helper.visit_type_mut(ty.as_mut());
-                    }
-                };
-                gen.params.iter_mut().for_each(|gp| match gp {
-                    GenericParam::Lifetime(_) => (),
-                    gp => {
-                        let mut type_helper = FnLifetimeEliderTypeHelper {
-                            cannot_elide: &cannot_elide,
-                            lt_count: &map,
-                        };
-                        type_helper.visit_generic_param_mut(gp);
-                    }
-                });
-                gen.params = gen
-                    .params
-                    .iter()
-                    .cloned()
-                    .filter(|g| match g {
-                        GenericParam::Lifetime(lt) => {
-                            let id = lt.lifetime.to_string();
-                            if !map.contains_key(&id) {
-                                false
-                            } else {
-                                let result =
-                                    *map.get(&id).unwrap() > 1 || cannot_elide.contains(&id);
-                                debug!("lt: {}, result: {}", id, result);
-                                result
-                            }
-                        }
-                        _ => true,
-                    })
-                    .collect();
+    /// Scope of a free function declared here, see `fn_scope`
+    pub fn free_fn(&self) -> Option<String> {
+        fn_scope(self.enclosing())
+    }
 
-                let mut lt_count = 0;
-                let mut new_lts = HashMap::new();
-                gen.params.iter_mut().for_each(|gp| match gp {
-                    GenericParam::Lifetime(lt) => {
-                        let id = lt.lifetime.to_string();
-                        self.annotations_left = true;
-                        new_lts.insert(id, format!("'lt{}", lt_count));
-                        lt.lifetime =
-                            Lifetime::new(format!("'lt{}", lt_count).as_str(), Span::call_site());
-                        lt_count += 1
-                    }
-                    _ => (),
-                });
-                gen.params.iter_mut().for_each(|gp| match gp {
-                    GenericParam::Lifetime(_) => (),
-                    gp => {
-                        let mut change_lt = ChangeLtHelperElider {
-                            map: &new_lts,
-                            has_struct_lt: false,
-                        };
-                        if change_lt.has_struct_lt {
-                            self.has_struct_lt = true;
-                        }
-                        change_lt.visit_generic_param_mut(gp);
+    pub fn enter_impl(&mut self, i: &ItemImpl) -> Outer {
+        let scope = impl_scope(self.enclosing(), i);
+        self.enter(scope, &i.attrs)
+    }
+
+    pub fn enter_trait(&mut self, i: &ItemTrait) -> Outer {
+        let scope = trait_scope(self.enclosing(), &i.ident);
+        self.enter(scope, &i.attrs)
+    }
+
+    pub fn enter_mod(&mut self, i: &ItemMod) -> Outer {
+        let scope = mod_scope(self.enclosing(), &i.ident);
+        self.enter(scope, &i.attrs)
+    }
+
+    /// Enter `scope`, inactive from here on if `attrs` hold a cfg that isn't active
+    pub fn enter(&mut self, scope: Option<String>, attrs: &[Attribute]) -> Outer {
+        let outer = Outer(std::mem::replace(&mut self.enclosing, scope), self.inactive);
+        self.inactive |= !cfg_active(attrs, self.active_cfg);
+        outer
+    }
+
+    pub fn leave(&mut self, outer: Outer) {
+        self.enclosing = outer.0;
+        self.inactive = outer.1;
+    }
+
+    /// Whether the lifetimes of a function declared in `scope` may be edited: it isn't `extern`, its
+    /// signature isn't fixed and its cfg and the ones around it are active, logging why not
+    pub fn editable(&self, attrs: &[Attribute], sig: &Signature, scope: Option<&str>) -> bool {
+        !skips_lifetimes(sig)
+            && !has_fixed_signature(attrs, sig, scope)
+            && !gated_off(attrs, sig, self.active_cfg, self.inactive)
+    }
+
+    /// Whether `fn_name` selects a function declared in `scope` whose lifetimes may be edited
+    pub fn edits(
+        &self,
+        fn_name: &str,
+        attrs: &[Attribute],
+        sig: &Signature,
+        scope: Option<&str>,
+    ) -> bool {
+        FnSelector::parse(fn_name).matches(&sig.ident, scope) && self.editable(attrs, sig, scope)
+    }
+}
+
+/// Outcome of looking up the target function of a repair in a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FnLookup {
+    Found,
+    NotFound,
+    /// Only defined inside a macro invocation, whose tokens syn leaves unparsed and the visitors
+    /// therefore can't rewrite
+    InsideMacro,
+    /// The file is empty or only whitespace, most likely a read that raced a write. It parses fine
+    /// and would compile, so repairing it would report a success for nothing
+    EmptySource,
+}
+
+struct FnFinder<'a> {
+    fn_name: &'a str,
+    scope: Scope<'a>,
+    /// Reprinted source of every matching function, in file order
+    found: Vec<String>,
+    /// Start of the ident of every matching function, in file order
+    locations: Vec<LineColumn>,
+    /// Selector and signature of every matching function, in file order
+    signatures: Vec<(String, Signature)>,
+    in_macro: bool,
+}
+
+impl<'a> FnFinder<'a> {
+    fn new(fn_name: &'a str) -> Self {
+        FnFinder {
+            fn_name,
+            scope: Scope::new(None),
+            found: vec![],
+            locations: vec![],
+            signatures: vec![],
+            in_macro: false,
+        }
+    }
+
+    // looks for `fn <name>` anywhere in the (unparsed) tokens of a macro invocation
+    fn scan_macro_tokens(&mut self, tokens: TokenStream) {
+        let mut after_fn = false;
+        for tt in tokens {
+            match tt {
+                TokenTree::Ident(ident) => {
+                    if after_fn && ident == FnSelector::parse(self.fn_name).name {
+                        self.in_macro = true;
                     }
-                });
-                match &mut gen.where_clause {
-                    None => (),
-                    Some(wc) => wc.predicates.iter_mut().for_each(|wp| match wp {
-                        WherePredicate::Lifetime(lt) => {
-                            let id = lt.lifetime.to_string();
-                            match new_lts.get(&id) {
-                                Some(new_lt) => {
-                                    lt.lifetime = Lifetime::new(new_lt.as_str(), Span::call_site())
-                                }
-                                None => (),
-                            };
-                            lt.bounds.iter_mut().for_each(|bound| {
-                                let id = bound.to_string();
-                                match new_lts.get(&id) {
-                                    Some(new_lt) => {
-                                        *bound = Lifetime::new(new_lt.as_str(), Span::call_site())
-                                    }
-                                    None => (),
-                                }
-                            })
-                        }
-                        _ => (),
-                    }),
+                    after_fn = ident == "fn";
                 }
-                inputs.iter_mut().for_each(|fn_arg| match fn_arg {
-                    FnArg::Receiver(_) => (),
-                    FnArg::Typed(t) => {
-                        let mut change_lt = ChangeLtHelperElider {
-                            map: &new_lts,
-                            has_struct_lt: false,
-                        };
-                        if change_lt.has_struct_lt {
-                            self.has_struct_lt = true;
-                        }
-                        debug!("debugging input: {:?}", t);
-                        change_lt.visit_pat_type_mut(t);
-                    }
-                });
-                match sig.output.borrow_mut() {
-                    ReturnType::Default => (),
-                    ReturnType::Type(_, ty) => {
-                        let mut change_lt = ChangeLtHelperElider {
-                            map: &new_lts,
-                            has_struct_lt: false,
-                        };
-                        if change_lt.has_struct_lt {
-                            self.has_struct_lt = true;
-                        }
-                        change_lt.visit_type_mut(ty.as_mut());
-                    }
+                TokenTree::Group(group) => {
+                    self.scan_macro_tokens(group.stream());
+                    after_fn = false;
                 }
+                _ => after_fn = false,
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for FnFinder<'_> {
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit::visit_item_impl(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit::visit_item_trait(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit::visit_item_mod(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        if FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing()) {
+            self.found.push(print_source(i));
+            self.locations.push(i.sig.ident.span().start());
+            let selector = selector_of(&i.sig.ident, self.scope.enclosing());
+            self.signatures.push((selector, i.sig.clone()));
+        }
+        syn::visit::visit_impl_item_method(self, i);
+    }
+
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        let scope = self.scope.free_fn();
+        if FnSelector::parse(self.fn_name).matches(&i.sig.ident, scope.as_deref()) {
+            self.found.push(print_source(i));
+            self.locations.push(i.sig.ident.span().start());
+            let selector = selector_of(&i.sig.ident, scope.as_deref());
+            self.signatures.push((selector, i.sig.clone()));
+        }
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit::visit_item_fn(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        if FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing()) {
+            self.found.push(print_source(i));
+            self.locations.push(i.sig.ident.span().start());
+            let selector = selector_of(&i.sig.ident, self.scope.enclosing());
+            self.signatures.push((selector, i.sig.clone()));
+        }
+        syn::visit::visit_trait_item_method(self, i);
+    }
+
+    fn visit_macro(&mut self, i: &'ast Macro) {
+        self.scan_macro_tokens(i.tokens.clone());
+        syn::visit::visit_macro(self, i);
+    }
+}
+
+/// Look up `fn_name` in the file, to catch misspelled targets before repairing
+pub fn lookup_function(file_name: &str, fn_name: &str) -> Result<FnLookup, RepairError> {
+    let file_content = read_source(file_name)?;
+    if file_content.trim().is_empty() {
+        return Ok(FnLookup::EmptySource);
+    }
+    let file = parse_file_source(file_name, &file_content)?;
+    let mut visit = FnFinder::new(fn_name);
+    visit.visit_file(&file);
+    Ok(match (visit.found.is_empty(), visit.in_macro) {
+        (true, true) => FnLookup::InsideMacro,
+        (true, false) => FnLookup::NotFound,
+        _ => FnLookup::Found,
+    })
+}
+
+/// Whether `fn_name` selects at least one function in the file
+pub fn function_exists(file_name: &str, fn_name: &str) -> Result<bool, RepairError> {
+    Ok(lookup_function(file_name, fn_name)? == FnLookup::Found)
+}
+
+/**
+Check `fn_name` selects at most one function in the file. The passes rewrite every function the
+selector matches, so a bound one of them needs would over-constrain the others sharing its name.
+Qualify the name (`Type::name`) to pick one, or set `RepairConfig::apply_to_all` to repair them all
+*/
+pub fn check_unambiguous(
+    file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<(), RepairError> {
+    if config.apply_to_all {
+        return Ok(());
+    }
+    let file = parse_file(file_name)?;
+    let candidates = candidates(file_name, &file, fn_name);
+    if candidates.len() > 1 {
+        return Err(RepairError::AmbiguousFunction {
+            fn_name: fn_name.to_string(),
+            candidates,
+        });
+    }
+    Ok(())
+}
+
+// `file:line:column` of every function `fn_name` selects in `file`
+fn candidates(file_name: &str, file: &syn::File, fn_name: &str) -> Vec<String> {
+    let mut visit = FnFinder::new(fn_name);
+    visit.visit_file(file);
+    visit
+        .locations
+        .iter()
+        .map(|start| format!("{}:{}:{}", file_name, start.line, start.column + 1))
+        .collect()
+}
+
+struct PatternFnFinder<'a> {
+    pattern: &'a Regex,
+    scope: Scope<'a>,
+    /// Selector of every function whose ident matches, in file order, and for the methods of an
+    /// inherent impl the one naming the impl as `<Foo>`
+    found: Vec<(String, Option<String>)>,
+}
+
+impl PatternFnFinder<'_> {
+    fn check_ident(&mut self, ident: &Ident, enclosing: Option<String>, inherent: bool) {
+        if !self.pattern.is_match(&ident.to_string()) {
+            return;
+        }
+        let selector = selector_of(ident, enclosing.as_deref());
+        let inherent = enclosing.filter(|_| inherent).map(|enclosing| {
+            let module = module_scope(Some(&enclosing));
+            format!("{}<{}>::{}", module, &enclosing[module.len()..], ident)
+        });
+        if !self.found.iter().any(|(found, _)| *found == selector) {
+            self.found.push((selector, inherent));
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for PatternFnFinder<'_> {
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit::visit_item_impl(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit::visit_item_trait(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit::visit_item_mod(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        let inherent = !self.scope.enclosing().unwrap_or("").ends_with('>');
+        self.check_ident(
+            &i.sig.ident,
+            self.scope.enclosing().map(String::from),
+            inherent,
+        );
+        syn::visit::visit_impl_item_method(self, i);
+    }
+
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        let scope = self.scope.free_fn();
+        self.check_ident(&i.sig.ident, scope.clone(), false);
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit::visit_item_fn(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        self.check_ident(
+            &i.sig.ident,
+            self.scope.enclosing().map(String::from),
+            false,
+        );
+        syn::visit::visit_trait_item_method(self, i);
+    }
+}
+
+/**
+Selectors of the functions whose ident matches `pattern`, in file order: the path to each (`bar`,
+`a::Foo::bar`, `a::<Foo as Trait>::bar`), anchored with `self::` when it's also the end of a longer
+path, and naming an inherent impl as `<Foo>` when a trait impl for `Foo` has a method with the same
+name. Each selects exactly its function, two functions with the same path (methods of two inherent
+impls for the same type) make a `RepairError::AmbiguousFunction`
+*/
+pub fn functions_matching(file_name: &str, pattern: &Regex) -> Result<Vec<String>, RepairError> {
+    let file = parse_file(file_name)?;
+    let mut visit = PatternFnFinder {
+        pattern,
+        scope: Scope::new(None),
+        found: vec![],
+    };
+    visit.visit_file(&file);
+    visit
+        .found
+        .into_iter()
+        .map(|(selector, inherent)| {
+            let anchored = format!("self::{}", selector);
+            let mut tried = vec![selector.clone(), anchored];
+            if let Some(inherent) = inherent {
+                tried.push(format!("self::{}", inherent));
+            }
+            let mut candidates_of = vec![];
+            for qualified in tried {
+                candidates_of = candidates(file_name, &file, &qualified);
+                if candidates_of.len() == 1 {
+                    return Ok(qualified);
+                }
+            }
+            Err(RepairError::AmbiguousFunction {
+                fn_name: selector,
+                candidates: candidates_of,
+            })
+        })
+        .collect()
+}
+
+struct FnAtPosition {
+    position: LineColumn,
+    scope: Scope<'static>,
+    /// Selector of the innermost function containing `position` so far
+    found: Option<String>,
+}
+
+impl FnAtPosition {
+    fn check_span(&mut self, span: Span, ident: &Ident, enclosing: Option<&str>) {
+        if span.start() <= self.position && self.position < span.end() {
+            self.found = Some(selector_of(ident, enclosing));
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for FnAtPosition {
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit::visit_item_impl(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit::visit_item_trait(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit::visit_item_mod(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        let enclosing = self.scope.enclosing().map(String::from);
+        self.check_span(i.span(), &i.sig.ident, enclosing.as_deref());
+        syn::visit::visit_impl_item_method(self, i);
+    }
+
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        let scope = self.scope.free_fn();
+        self.check_span(i.span(), &i.sig.ident, scope.as_deref());
+        // functions nested in the body are free functions again
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit::visit_item_fn(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        let enclosing = self.scope.enclosing().map(String::from);
+        self.check_span(i.span(), &i.sig.ident, enclosing.as_deref());
+        syn::visit::visit_trait_item_method(self, i);
+    }
+}
+
+/**
+Selector (`bar` or `Foo::bar`) of the innermost function whose span, attributes included, contains
+the byte `offset` into `source`, for editor integrations that only know the cursor position
+
+`None` if the offset is outside every function or not on a char boundary, an error if `source`
+doesn't parse (as editor buffers often don't mid-edit)
+*/
+pub fn function_name_at_offset(source: &str, offset: usize) -> Result<Option<String>, RepairError> {
+    // spans are in lines and chars of the source as `try_parse_source` rewrote it
+    let prefix = match source.get(..offset) {
+        Some(prefix) => disguise_precise_capture(prefix),
+        None => return Ok(None),
+    };
+    let line_start = prefix.rfind('\n').map_or(0, |i| i + 1);
+    let position = LineColumn {
+        line: prefix.matches('\n').count() + 1,
+        column: prefix[line_start..].chars().count(),
+    };
+    let file = try_parse_source(source)?;
+    let mut visit = FnAtPosition {
+        position,
+        scope: Scope::new(None),
+        found: None,
+    };
+    visit.visit_file(&file);
+    Ok(visit.found)
+}
+
+struct SignatureLtFinder<'a> {
+    fn_name: &'a str,
+    scope: Scope<'a>,
+    found: bool,
+    has_lifetimes: bool,
+}
+
+impl SignatureLtFinder<'_> {
+    fn check_signature(&mut self, sig: &Signature) {
+        self.found = true;
+        let mut finder = RefLtFinder { found: false };
+        finder.visit_signature(sig);
+        self.has_lifetimes |= finder.found;
+    }
+}
+
+impl<'ast> Visit<'ast> for SignatureLtFinder<'_> {
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit::visit_item_impl(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit::visit_item_trait(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit::visit_item_mod(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        if FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing()) {
+            self.check_signature(&i.sig);
+        }
+        syn::visit::visit_impl_item_method(self, i);
+    }
+
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        let scope = self.scope.free_fn();
+        if FnSelector::parse(self.fn_name).matches(&i.sig.ident, scope.as_deref()) {
+            self.check_signature(&i.sig);
+        }
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit::visit_item_fn(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        if FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing()) {
+            self.check_signature(&i.sig);
+        }
+        syn::visit::visit_trait_item_method(self, i);
+    }
+}
+
+struct RefLtFinder {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for RefLtFinder {
+    fn visit_receiver(&mut self, i: &'ast Receiver) {
+        if i.reference.is_some() {
+            self.found = true;
+        }
+    }
+
+    fn visit_type_reference(&mut self, _i: &'ast TypeReference) {
+        self.found = true;
+    }
+
+    fn visit_lifetime(&mut self, _i: &'ast Lifetime) {
+        self.found = true;
+    }
+}
+
+/**
+Cheap check before compiling: whether a signature `fn_name` selects has references or lifetimes
+that a lifetime repair could be about
+
+Types with hidden lifetime parameters (`Foo` for `struct Foo<'a>`) can't be seen without resolving
+them, and a function that can't be found counts as needing repair so the repair still reports it
+*/
+pub fn may_need_lifetime_repair(file_name: &str, fn_name: &str) -> Result<bool, RepairError> {
+    let file = parse_file(file_name)?;
+    let mut visit = SignatureLtFinder {
+        fn_name,
+        scope: Scope::new(None),
+        found: false,
+        has_lifetimes: false,
+    };
+    visit.visit_file(&file);
+    Ok(!visit.found || visit.has_lifetimes)
+}
+
+/// Formatted source of the first function `fn_name` selects, without the rest of the file
+pub fn extract_function_source(
+    file_name: &str,
+    fn_name: &str,
+) -> Result<Option<String>, RepairError> {
+    let file = parse_file(file_name)?;
+    let mut visit = FnFinder::new(fn_name);
+    visit.visit_file(&file);
+    Ok(visit
+        .found
+        .into_iter()
+        .next()
+        .map(|source| format_source(&source)))
+}
+
+/// Source of the target function before (`file_name`) and after (`new_file_name`) its repair
+pub fn extract_repaired_function(
+    file_name: &str,
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<Option<(String, String)>, RepairError> {
+    let before = extract_function_source(file_name, fn_name)?;
+    let after = extract_function_source(new_file_name, fn_name)?;
+    Ok(before.zip(after))
+}
+
+/**
+The signature of every function `fn_name` selects in the file, by selector, formatted without the
+body (`fn foo(x: &T) -> &T`) to report what a repair turned it into
+*/
+pub fn final_signatures(
+    file_name: &str,
+    fn_name: &str,
+) -> Result<HashMap<String, String>, RepairError> {
+    let file = parse_file(file_name)?;
+    let mut visit = FnFinder::new(fn_name);
+    visit.visit_file(&file);
+    Ok(visit
+        .signatures
+        .into_iter()
+        .map(|(selector, sig)| {
+            // the empty body goes on a line of its own after a where clause, `{\n}`
+            let formatted = format_source(&format!("{} {{}}", print_source(&sig)));
+            let body = formatted.trim_end().strip_suffix('}').map(str::trim_end);
+            let sig = body
+                .and_then(|sig| sig.strip_suffix('{'))
+                .unwrap_or(&formatted);
+            let sig = sig.trim_end();
+            (selector, sig.to_string())
+        })
+        .collect())
+}
+
+/// A signature taken apart for showing its lifetimes, each list in the order they are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureView {
+    /// The declared lifetime parameters
+    pub lifetimes: Vec<String>,
+    pub inputs: Vec<InputView>,
+    /// Lifetimes named in the return type, empty for `()`
+    pub output_lifetimes: Vec<String>,
+}
+
+/// One input of a [`SignatureView`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputView {
+    /// `self` for the receiver, otherwise the pattern (`x`, `mut buf`)
+    pub name: String,
+    /// The type as written (`&'a mut self` for the receiver)
+    pub ty: String,
+    /// Lifetimes named in the type
+    pub lifetimes: Vec<String>,
+}
+
+impl SignatureView {
+    pub fn of(sig: &Signature) -> Self {
+        let named_lifetimes = |ty: &Type| {
+            let mut v = vec![];
+            let mut get_lt = LtGetterElider { v: &mut v };
+            get_lt.visit_type_mut(&mut ty.clone());
+            v
+        };
+        let inputs = sig
+            .inputs
+            .iter()
+            .map(|fn_arg| match fn_arg {
+                FnArg::Receiver(receiver) => {
+                    let reference = match &receiver.reference {
+                        None => String::new(),
+                        Some((_, None)) => "&".to_string(),
+                        Some((_, Some(lt))) => format!("&{} ", lt),
+                    };
+                    let mutability = if receiver.mutability.is_some() {
+                        "mut "
+                    } else {
+                        ""
+                    };
+                    InputView {
+                        name: "self".to_string(),
+                        ty: format!("{}{}self", reference, mutability),
+                        lifetimes: receiver
+                            .lifetime()
+                            .map(|lt| lt.to_string())
+                            .into_iter()
+                            .collect(),
+                    }
+                }
+                FnArg::Typed(pt) => InputView {
+                    name: print_source(&pt.pat),
+                    ty: format_type(&pt.ty),
+                    lifetimes: named_lifetimes(&pt.ty),
+                },
+            })
+            .collect();
+        SignatureView {
+            lifetimes: sig
+                .generics
+                .lifetimes()
+                .map(|def| def.lifetime.to_string())
+                .collect(),
+            inputs,
+            output_lifetimes: match &sig.output {
+                ReturnType::Default => vec![],
+                ReturnType::Type(_, ty) => named_lifetimes(ty),
+            },
+        }
+    }
+}
+
+// the type formatted the way it would be written, `&'a T` rather than the tokens' `& 'a T`
+fn format_type(ty: &Type) -> String {
+    let formatted = format_source(&format!("type T = {};", print_source(ty)));
+    formatted
+        .trim()
+        .strip_prefix("type T = ")
+        .and_then(|ty| ty.strip_suffix(';'))
+        .map_or_else(
+            || print_source(ty),
+            |ty| ty.split_whitespace().collect::<Vec<_>>().join(" "),
+        )
+}
+
+/// The [`SignatureView`] of every function `fn_name` selects in the file, by selector
+pub fn signature_views(
+    file_name: &str,
+    fn_name: &str,
+) -> Result<HashMap<String, SignatureView>, RepairError> {
+    let file = parse_file(file_name)?;
+    let mut visit = FnFinder::new(fn_name);
+    visit.visit_file(&file);
+    Ok(visit
+        .signatures
+        .into_iter()
+        .map(|(selector, sig)| (selector, SignatureView::of(&sig)))
+        .collect())
+}
+
+/// The [`SignatureView`] of every function `fn_name` selects before (`file_name`) and after
+/// (`new_file_name`) its repair, by selector. Functions only one of the files has are left out
+pub fn repaired_signature_views(
+    file_name: &str,
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<HashMap<String, (SignatureView, SignatureView)>, RepairError> {
+    let mut before = signature_views(file_name, fn_name)?;
+    Ok(signature_views(new_file_name, fn_name)?
+        .into_iter()
+        .filter_map(|(selector, after)| {
+            let before = before.remove(&selector)?;
+            Some((selector, (before, after)))
+        })
+        .collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+////////////////////////////////     REPAIR HELPERS     ////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////////////////////////
+pub struct RepairResult {
+    pub success: bool,
+    pub repair_count: i32,
+    #[allow(dead_code)]
+    pub has_non_elidible_lifetime: bool,
+    #[allow(dead_code)]
+    pub has_struct_lt: bool,
+    /// Time spent waiting on the compiler, summed over all iterations
+    pub compile_time: Duration,
+    /// The project already failed to compile on errors outside the target file alone, so nothing
+    /// was repaired. Also the case when the target isn't the file the command compiles
+    pub unrelated_error: bool,
+    /// Signature of each function the repair targeted as it ended up, by selector. Filled by the
+    /// repairs that succeed on a function, see `final_signatures`
+    pub final_signatures: HashMap<String, String>,
+}
+
+impl RepairResult {
+    /// The result of a repair that gave up before or without getting the code to compile
+    pub fn failed() -> Self {
+        RepairResult {
+            success: false,
+            repair_count: 0,
+            has_non_elidible_lifetime: false,
+            has_struct_lt: false,
+            compile_time: Duration::ZERO,
+            unrelated_error: false,
+            final_signatures: HashMap::new(),
+        }
+    }
+}
+
+pub trait RepairSystem: std::fmt::Debug {
+    fn name(&self) -> &str;
+    /// The config `repair_project` and `repair_function` run under, logging under `label`
+    fn default_config(&self, label: &str) -> RepairConfig {
+        RepairConfig::builder().label(label).build()
+    }
+    fn repair_project(&self, src_path: &str, manifest_path: &str, fn_name: &str) -> RepairResult {
+        let config = self.default_config(fn_name);
+        self.repair_project_with_config(src_path, manifest_path, fn_name, &config)
+    }
+    /// `repair_project` under the caller's `config` instead of `default_config`
+    fn repair_project_with_config(
+        &self,
+        src_path: &str,
+        manifest_path: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult;
+    fn repair_file(&self, file_name: &str, new_file_name: &str) -> RepairResult;
+    fn repair_function(&self, file_name: &str, new_file_name: &str, fn_name: &str) -> RepairResult {
+        let label = match fn_name.is_empty() {
+            true => new_file_name,
+            false => fn_name,
+        };
+        let config = self.default_config(label);
+        self.repair_function_with_config(file_name, new_file_name, fn_name, &config)
+    }
+    /// `repair_function` under the caller's `config` instead of `default_config`
+    fn repair_function_with_config(
+        &self,
+        file_name: &str,
+        new_file_name: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult;
+    fn clone_box(&self) -> Box<dyn RepairSystem>;
+}
+
+impl Clone for Box<dyn RepairSystem> {
+    fn clone(&self) -> Box<dyn RepairSystem> {
+        self.clone_box()
+    }
+}
+
+// Wrapper for RepairSystem trait objects that implements Debug
+pub struct DebugRepairSystem<'a>(pub &'a dyn RepairSystem);
+
+impl<'a> Debug for DebugRepairSystem<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RepairSystem")
+            .field("name", &self.0.name())
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RustcError {
+    pub rendered: String,
+    pub spans: Vec<RustcSpan>,
+    /// `error`, `warning`, ...
+    #[serde(default)]
+    pub level: String,
+    /// Error code (`E0106`) or lint name (`clippy::needless_lifetimes`), if the diagnostic has one
+    pub code: Option<RustcCode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RustcCode {
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RustcSpan {
+    pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+}
+
+/**
+Reads the diagnostics out of a compiler's output, so the iteration loops work with compilers (rustc
+forks, wrappers) whose json differs from rustc's. Set one with `RepairConfig::diagnostic_parser`,
+loops without one use `RustcJsonParser`
+
+`repair_iteration` hands the raw output to its `process_errors` instead, so only the passes given
+to `repair_iteration_prioritized` and the project loops go through the parser
+*/
+pub trait DiagnosticParser: Send + Sync {
+    /// Diagnostics in the stderr of a single file compile (`rustc --error-format=json`)
+    fn parse_rustc(&self, stderr: &str) -> Vec<RustcError>;
+    /// Diagnostics in the stdout of a project build (`cargo check --message-format=json`)
+    fn parse_cargo(&self, stdout: &str) -> Vec<RustcError>;
+}
+
+/// The json of rustc and cargo, lines that don't parse are skipped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustcJsonParser;
+
+impl DiagnosticParser for RustcJsonParser {
+    fn parse_rustc(&self, stderr: &str) -> Vec<RustcError> {
+        serde_json::Deserializer::from_str(stderr)
+            .into_iter::<RustcError>()
+            .filter_map(|item| item.ok())
+            .collect()
+    }
+
+    fn parse_cargo(&self, stdout: &str) -> Vec<RustcError> {
+        let deserializer = serde_json::Deserializer::from_str(stdout);
+        let mut errors = vec![];
+        for item in deserializer.into_iter::<CargoError>() {
+            match item {
+                Ok(CargoError {
+                    message: Some(message),
+                }) => errors.push(message),
+                Ok(_) => (),
+                Err(e) => debug!("error parsing cargo error:\n{}", e),
+            }
+        }
+        errors
+    }
+}
+
+fn diagnostic_parser(config: &RepairConfig) -> &dyn DiagnosticParser {
+    match &config.diagnostic_parser {
+        Some(parser) => parser.as_ref(),
+        None => &RustcJsonParser,
+    }
+}
+
+/// Replace the bytes `span` covers in `file` with `replacement`, the span has to lie on char
+/// boundaries of the current file content
+pub fn apply_span_replacement(
+    file: &str,
+    span: &RustcSpan,
+    replacement: &str,
+) -> Result<(), RepairError> {
+    apply_span_replacement_with_config(file, span, replacement, &RepairConfig::default())
+}
+
+/// Like `apply_span_replacement`, writing the file through `write_source`
+pub fn apply_span_replacement_with_config(
+    file: &str,
+    span: &RustcSpan,
+    replacement: &str,
+    config: &RepairConfig,
+) -> Result<(), RepairError> {
+    let mut content = fs::read_to_string(file).map_err(|e| RepairError::io(file, e))?;
+    let in_bounds = span.byte_start <= span.byte_end
+        && content.is_char_boundary(span.byte_start)
+        && content.is_char_boundary(span.byte_end);
+    if !in_bounds {
+        let source = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "span {}..{} is not a valid range of {} bytes",
+                span.byte_start,
+                span.byte_end,
+                content.len()
+            ),
+        );
+        return Err(RepairError::io(file, source));
+    }
+    content.replace_range(span.byte_start..span.byte_end, replacement);
+    write_source(file, &content, config)
+}
+
+// a suggestion is one or more numbered source lines (`~` marks a changed line when it spans
+// several), each possibly followed by a `|  ++++` marker line
+const STANDARD_HELP: &str =
+    r"help: consider.+\n.*\n(?P<block>(?:[ ]*\d+ [|~] .+\n(?:[ ]*\|[ +~^-]*\n)?)+)";
+
+const BOUNDS_HELP: &str =
+    r"= help: consider.+bound: `(?P<constraint_lhs>'[a-z0-9]+): (?P<constraint_rhs>'[a-z0-9]+)`";
+
+// the labels of a lifetime error naming the function's lifetimes and which has to outlive which
+const NOTE_DEFINED: &str = r"lifetime `(?P<lifetime>'[a-z0-9]+)` defined here";
+const NOTE_OUTLIVES: &str =
+    r"requires that `(?P<constraint_lhs>'[a-z0-9]+)` must outlive `(?P<constraint_rhs>'[a-z0-9]+)`";
+const NOTE_RETURNS: &str = r"supposed to return data with lifetime `(?P<constraint_rhs>'[a-z0-9]+)` but it is returning data with lifetime `(?P<constraint_lhs>'[a-z0-9]+)`";
+
+// the help patterns compiled once, they are matched against every diagnostic of every iteration
+fn standard_help_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(STANDARD_HELP).unwrap())
+}
+
+fn bounds_help_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(BOUNDS_HELP).unwrap())
+}
+
+// `NOTE_DEFINED`, `NOTE_OUTLIVES` and `NOTE_RETURNS`
+fn note_regexes() -> &'static [Regex; 3] {
+    static RE: OnceLock<[Regex; 3]> = OnceLock::new();
+    RE.get_or_init(|| {
+        [NOTE_DEFINED, NOTE_OUTLIVES, NOTE_RETURNS].map(|note| Regex::new(note).unwrap())
+    })
+}
+
+pub fn repair_standard_help(stderr: &str, new_file_name: &str) -> Result<bool, RepairError> {
+    repair_standard_help_with_config(stderr, new_file_name, &RepairConfig::default())
+}
+
+/// Like `repair_standard_help`, skipping suggestions that change a function body when
+/// `config.signatures_only` is set
+pub fn repair_standard_help_with_config(
+    stderr: &str,
+    new_file_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    let diagnostics = rendered_diagnostics(stderr);
+    standard_help_of(
+        diagnostics.iter().map(String::as_str),
+        new_file_name,
+        config,
+    )
+}
+
+/// `repair_standard_help_with_config` for diagnostics the caller already has as [`RustcError`]s,
+/// without a round trip through json
+pub fn repair_standard_help_from_errors(
+    errors: &[RustcError],
+    new_file_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    standard_help_of(
+        errors.iter().map(|error| error.rendered.as_str()),
+        new_file_name,
+        config,
+    )
+}
+
+fn standard_help_of<'a>(
+    diagnostics: impl Iterator<Item = &'a str>,
+    new_file_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    let mut helped = false;
+    for rendered in diagnostics {
+        let file_content = read_source(new_file_name)?;
+        let (new_content, applied) = apply_standard_help(rendered, &file_content, config);
+        write_source(new_file_name, &new_content, config)?;
+        helped |= applied;
+    }
+    Ok(helped)
+}
+
+/// Rendered text of each json diagnostic in `stderr`, all of it if it isn't json
+fn rendered_diagnostics(stderr: &str) -> Vec<String> {
+    let deserializer = serde_json::Deserializer::from_str(stderr);
+    deserializer
+        .into_iter::<RustcError>()
+        .map(|item| match item {
+            Ok(i) => i.rendered,
+            Err(_) => stderr.to_string(),
+        })
+        .collect()
+}
+
+// the suggestions of one rendered diagnostic applied to `file_content`, and whether there were any
+fn apply_standard_help(
+    rendered: &str,
+    file_content: &str,
+    config: &RepairConfig,
+) -> (String, bool) {
+    let re = standard_help_regex();
+    let help_lines = re.captures_iter(rendered);
+
+    let lines = file_content.split("\n");
+    let mut lines_modifiable = Vec::new();
+    for line in lines {
+        lines_modifiable.push(line);
+    }
+
+    let mut helped = false;
+    let mut current_line = 0;
+    // joined with the newlines `split` took out, a final newline stays a final newline
+    let mut out = vec![];
+    // the lifetimes filled in for placeholders, declared once all suggestions are in
+    let mut filled = vec![];
+    for captured in help_lines {
+        let replacements = suggestion_lines(captured.name("block").unwrap().as_str());
+        let placeholder = replacements
+            .iter()
+            .any(|(_, replacement)| replacement.contains("&'lifetime"));
+        if replacements.is_empty() || (placeholder && !config.fill_placeholders) {
+            continue;
+        }
+        if replacements.iter().any(|(line_number, replacement)| {
+            drops_const(lines_modifiable.get(line_number - 1), replacement)
+        }) {
+            warn!("skipped a suggestion that would turn a const fn into a plain fn");
+            continue;
+        }
+        if config.signatures_only && changes_fn_body(&lines_modifiable, &replacements) {
+            debug!("skipped a suggestion that would change a function body");
+            continue;
+        }
+
+        let lifetime = match placeholder {
+            true => {
+                let lifetime = fresh_lifetime(file_content, &filled);
+                debug!("filling the `'lifetime` placeholder with `{}`", lifetime);
+                filled.push(lifetime.clone());
+                Some(lifetime)
+            }
+            false => None,
+        };
+        helped = true;
+        for (line_number, replacement) in replacements {
+            while current_line < line_number - 1 {
+                out.push(lines_modifiable[current_line].to_string());
+                current_line += 1;
+            }
+            out.push(match &lifetime {
+                Some(lifetime) => replacement.replace("'lifetime", lifetime),
+                None => replacement.to_string(),
+            });
+            current_line += 1;
+        }
+    }
+    while current_line < lines_modifiable.len() {
+        out.push(lines_modifiable[current_line].to_string());
+        current_line += 1;
+    }
+    match filled.is_empty() {
+        true => (out.join("\n"), helped),
+        false => (declare_lifetimes(&out.join("\n"), &filled), helped),
+    }
+}
+
+// `'lt{n}` for the first n that neither `source` nor `taken` uses
+fn fresh_lifetime(source: &str, taken: &[String]) -> String {
+    static LIFETIME: OnceLock<Regex> = OnceLock::new();
+    let lifetime = LIFETIME.get_or_init(|| Regex::new(r"'[A-Za-z_][A-Za-z0-9_]*").unwrap());
+    let used: HashSet<&str> = lifetime.find_iter(source).map(|m| m.as_str()).collect();
+    (0..)
+        .map(|n| format!("'lt{}", n))
+        .find(|lifetime| !taken.contains(lifetime) && !used.contains(lifetime.as_str()))
+        .unwrap()
+}
+
+// declares each of `lifetimes` on the functions whose signature uses it, they are fresh so
+// nothing declares them yet
+struct LifetimeDeclarer<'a> {
+    lifetimes: &'a [String],
+    declared: Vec<String>,
+}
+
+impl VisitMut for LifetimeDeclarer<'_> {
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        self.declare(&mut i.sig);
+        syn::visit_mut::visit_item_fn_mut(self, i);
+    }
+
+    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+        self.declare(&mut i.sig);
+        syn::visit_mut::visit_impl_item_method_mut(self, i);
+    }
+
+    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+        self.declare(&mut i.sig);
+        syn::visit_mut::visit_trait_item_method_mut(self, i);
+    }
+}
+
+impl LifetimeDeclarer<'_> {
+    fn declare(&mut self, sig: &mut Signature) {
+        let mut used = vec![];
+        LtGetterElider { v: &mut used }.visit_signature_mut(&mut sig.clone());
+        for lifetime in self.lifetimes.iter().rev() {
+            if used.contains(lifetime) {
+                // lifetimes go before the type and const parameters
+                sig.generics.params.insert(
+                    0,
+                    GenericParam::Lifetime(LifetimeDef::new(Lifetime::new(
+                        lifetime,
+                        Span::call_site(),
+                    ))),
+                );
+                self.declared.push(lifetime.clone());
+            }
+        }
+    }
+}
+
+/**
+`source` with `lifetimes` declared on the functions whose signatures use them. A lifetime only a
+function body uses is left undeclared, the next compile points out where it belongs
+*/
+fn declare_lifetimes(source: &str, lifetimes: &[String]) -> String {
+    let mut file = match try_parse_source(source) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("cannot declare {:?}: {}", lifetimes, e);
+            return source.to_string();
+        }
+    };
+    let mut visit = LifetimeDeclarer {
+        lifetimes,
+        declared: vec![],
+    };
+    visit.visit_file_mut(&mut file);
+    match visit.declared.is_empty() {
+        true => source.to_string(),
+        false => print_edited(source, &file),
+    }
+}
+
+/**
+`repair_standard_help` and `repair_bounds_help` in one go: the diagnostics are parsed once, the
+suggested lines replaced first and the bounds added after, with the file written once at the end.
+The line numbers of the suggestions refer to the file as it is, so they are applied from the bottom
+of the file up. `config` is applied as in `repair_standard_help_with_config` and
+`repair_bounds_help_with_config`
+*/
+pub fn repair_all_help(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    let diagnostics = rendered_diagnostics(stderr);
+    let mut file_content = read_source(new_file_name)?;
+    let mut helped = false;
+    // an edit only moves the lines below it, a stable sort keeps suggestions for a line in order
+    let mut bottom_up: Vec<&String> = diagnostics.iter().collect();
+    bottom_up.sort_by_key(|rendered| Reverse(first_suggested_line(rendered)));
+    for rendered in bottom_up {
+        let (new_content, applied) = apply_standard_help(rendered, &file_content, config);
+        file_content = new_content;
+        helped |= applied;
+    }
+
+    let re = bounds_help_regex();
+    let bounds: Vec<(&str, &str)> = diagnostics
+        .iter()
+        .flat_map(|rendered| re.captures_iter(rendered))
+        .map(|captured| {
+            (
+                captured.name("constraint_lhs").unwrap().as_str(),
+                captured.name("constraint_rhs").unwrap().as_str(),
+            )
+        })
+        .collect();
+    let mut bounded = false;
+    if !bounds.is_empty() {
+        // the suggested lines can leave the file unparsable, their repair is still written
+        match try_parse_source(&file_content) {
+            Ok(mut file) => {
+                for (lifetime, bound) in bounds {
+                    debug!("[{}] adding bound {}: {}", config.label, lifetime, bound);
+                    let modified = add_lifetime_bound_in_ast_with_config(
+                        &mut file, fn_name, lifetime, bound, config,
+                    );
+                    bounded |= !modified.is_empty();
+                }
+                if bounded {
+                    file_content = print_edited(&file_content, &file);
+                }
+            }
+            Err(e) => warn!(
+                "[{}] cannot add the suggested bounds to {}: {}",
+                config.label, new_file_name, e
+            ),
+        }
+    }
+
+    if helped || bounded {
+        write_source(new_file_name, &file_content, config)?;
+    }
+    Ok(helped || bounded)
+}
+
+// the first line of the file the suggestions of `rendered` replace, `None` without suggestions
+fn first_suggested_line(rendered: &str) -> Option<usize> {
+    standard_help_regex()
+        .captures_iter(rendered)
+        .flat_map(|captured| suggestion_lines(captured.name("block").unwrap().as_str()))
+        .map(|(line_number, _)| line_number)
+        .min()
+}
+
+/// A single edit: replace bytes `byte_start..byte_end` of `file_name` with `replacement`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RepairEdit {
+    pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub replacement: String,
+    /// Lines of the original source before the edited ones, see [`RepairEdit::with_context`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_before: Option<String>,
+    /// Lines of the original source after the edited ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_after: Option<String>,
+}
+
+impl RepairEdit {
+    /**
+    The edit with up to `lines` lines of `source` (the content of `file_name` it applies to) on
+    either side of the lines it touches, so a review tool can show it without reading the file.
+    Fewer lines at the start or end of the file, an edit on the first line gets an empty
+    `context_before`
+    */
+    pub fn with_context(self, source: &str, lines: usize) -> Self {
+        let line_start = source[..self.byte_start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[self.byte_end..]
+            .find('\n')
+            .map_or(source.len(), |i| self.byte_end + i + 1);
+        let mut before: Vec<&str> = source[..line_start].lines().rev().take(lines).collect();
+        before.reverse();
+        let after: Vec<&str> = source[line_end..].lines().take(lines).collect();
+        RepairEdit {
+            context_before: Some(before.join("\n")),
+            context_after: Some(after.join("\n")),
+            ..self
+        }
+    }
+
+    /// The edit as a rustfix replacement, `source` is the content of `file_name` it applies to
+    pub fn to_rustfix(&self, source: &str) -> rustfix::Replacement {
+        let position = |byte: usize| {
+            let line_start = source[..byte].rfind('\n').map_or(0, |i| i + 1);
+            rustfix::LinePosition {
+                line: source[..byte].matches('\n').count() + 1,
+                column: source[line_start..byte].chars().count() + 1,
+            }
+        };
+        let line_start = source[..self.byte_start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[self.byte_end..]
+            .find('\n')
+            .map_or(source.len(), |i| self.byte_end + i);
+        rustfix::Replacement {
+            snippet: rustfix::Snippet {
+                file_name: self.file_name.clone(),
+                line_range: rustfix::LineRange {
+                    start: position(self.byte_start),
+                    end: position(self.byte_end),
+                },
+                range: self.byte_start..self.byte_end,
+                text: (
+                    source[line_start..self.byte_start].to_string(),
+                    source[self.byte_start..self.byte_end].to_string(),
+                    source[self.byte_end..line_end].to_string(),
+                ),
+            },
+            replacement: self.replacement.clone(),
+        }
+    }
+}
+
+/**
+`edits` of `file_name` (whose content is `source`) as `{ file, replacements }`, each replacement
+laid out like rustfix's `Replacement` so apply tools and review UIs built on rustfix can read it,
+with the edit's context lines next to it when it has them
+*/
+pub fn rustfix_json(file_name: &str, source: &str, edits: &[RepairEdit]) -> serde_json::Value {
+    let replacements: Vec<serde_json::Value> = edits
+        .iter()
+        .map(|edit| {
+            let replacement = edit.to_rustfix(source);
+            let snippet = replacement.snippet;
+            let position = |p: rustfix::LinePosition| {
+                serde_json::json!({ "line": p.line, "column": p.column })
+            };
+            let mut json = serde_json::json!({
+                "snippet": {
+                    "file_name": snippet.file_name,
+                    "line_range": {
+                        "start": position(snippet.line_range.start),
+                        "end": position(snippet.line_range.end),
+                    },
+                    "range": { "start": snippet.range.start, "end": snippet.range.end },
+                    "text": [snippet.text.0, snippet.text.1, snippet.text.2],
+                },
+                "replacement": replacement.replacement,
+            });
+            if let Some(context) = &edit.context_before {
+                json["context_before"] = serde_json::json!(context);
+            }
+            if let Some(context) = &edit.context_after {
+                json["context_after"] = serde_json::json!(context);
+            }
+            json
+        })
+        .collect();
+    serde_json::json!({ "file": file_name, "replacements": replacements })
+}
+
+// the repairs only ever add lifetime parameters and bounds, which const fns allow, and leave
+// `sig.constness` as it is; a suggested line is the one place `const` could get lost
+fn drops_const(original: Option<&&str>, replacement: &str) -> bool {
+    static CONST_FN: OnceLock<Regex> = OnceLock::new();
+    let re = CONST_FN.get_or_init(|| Regex::new(r"\bconst\b[^;{}]*\bfn\b").unwrap());
+    original.is_some_and(|line| re.is_match(line)) && !re.is_match(replacement)
+}
+
+// whether replacing `lines` with `replacements` changes the tokens of any function body, a source
+// that no longer parses counts as changed
+fn changes_fn_body(lines: &[&str], replacements: &[(usize, &str)]) -> bool {
+    let mut replaced: Vec<&str> = lines.to_vec();
+    for &(line_number, replacement) in replacements {
+        match replaced.get_mut(line_number - 1) {
+            Some(line) => *line = replacement,
+            None => return true,
+        }
+    }
+    match (
+        try_parse_source(&lines.join("\n")),
+        try_parse_source(&replaced.join("\n")),
+    ) {
+        (Ok(before), Ok(after)) => fn_bodies(&before) != fn_bodies(&after),
+        _ => true,
+    }
+}
+
+// printed body of every function (and default trait method) in `file`, in file order
+fn fn_bodies(file: &syn::File) -> Vec<String> {
+    struct FnBodies(Vec<String>);
+
+    impl<'ast> Visit<'ast> for FnBodies {
+        fn visit_block(&mut self, i: &'ast Block) {
+            // nested functions are part of the body
+            self.0.push(print_source(i));
+        }
+    }
+
+    let mut visit = FnBodies(vec![]);
+    visit.visit_file(file);
+    visit.0
+}
+
+/// `(line number, replacement)` of each changed line of a rendered suggestion, up to the first line
+/// that doesn't directly follow the previous one
+fn suggestion_lines(block: &str) -> Vec<(usize, &str)> {
+    static LINE: OnceLock<Regex> = OnceLock::new();
+    static MARKER: OnceLock<Regex> = OnceLock::new();
+    let re = LINE.get_or_init(|| {
+        Regex::new(r"^[ ]*(?P<line_number>\d+) (?P<separator>[|~]) (?P<replacement>.+)$").unwrap()
+    });
+    let marker = MARKER.get_or_init(|| Regex::new(r"^[ ]*\|[ ]*[+~-][ +~-]*$").unwrap());
+    let block_lines: Vec<&str> = block.lines().collect();
+    // (line number, replacement, whether rustc marked the line as changed)
+    let mut lines: Vec<(usize, &str, bool)> = vec![];
+    for (i, line) in block_lines.iter().enumerate() {
+        let captured = match re.captures(line) {
+            Some(captured) => captured,
+            None => continue,
+        };
+        let line_number = match captured["line_number"].parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => break,
+        };
+        if lines
+            .last()
+            .is_some_and(|(last, _, _)| *last + 1 != line_number)
+        {
+            break;
+        }
+        let changed = &captured["separator"] == "~"
+            || block_lines
+                .get(i + 1)
+                .is_some_and(|next| marker.is_match(next));
+        lines.push((
+            line_number,
+            captured.name("replacement").unwrap().as_str(),
+            changed,
+        ));
+    }
+    // rustc shows unchanged (and possibly shortened) context lines around the changed ones without
+    // a mark, only a suggestion that marks nothing is taken as a whole
+    let any_changed = lines.iter().any(|(_, _, changed)| *changed);
+    lines
+        .into_iter()
+        .filter(|(_, _, changed)| *changed || !any_changed)
+        .map(|(line_number, replacement, _)| (line_number, replacement))
+        .collect()
+}
+
+struct FnLifetimeBounder<'a> {
+    fn_name: &'a str,
+    scope: Scope<'a>,
+    lifetime: &'a str,
+    bound: &'a str,
+    success: bool,
+    /// Whether the bound was the reverse of an existing one and the lifetimes got unified instead
+    unified: bool,
+    /// Whether a selected function already had the bound, which is left as it is
+    present: bool,
+    /// Selector of every function the bound was added to
+    modified: Vec<String>,
+}
+
+impl VisitMut for FnLifetimeBounder<'_> {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit_mut::visit_item_impl_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit_mut::visit_item_trait_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+        match self
+            .scope
+            .edits(self.fn_name, &i.attrs, &i.sig, self.scope.enclosing())
+        {
+            false => (),
+            true => {
+                if self.fn_lifetime_bounder(&mut i.sig, Some(&mut i.block)) {
+                    let selector = selector_of(&i.sig.ident, self.scope.enclosing());
+                    self.modified.push(selector);
+                }
+            }
+        }
+        syn::visit_mut::visit_impl_item_method_mut(self, i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        let scope = self.scope.free_fn();
+        match self
+            .scope
+            .edits(self.fn_name, &i.attrs, &i.sig, scope.as_deref())
+        {
+            false => (),
+            true => {
+                if self.fn_lifetime_bounder(&mut i.sig, Some(i.block.as_mut())) {
+                    self.modified
+                        .push(selector_of(&i.sig.ident, scope.as_deref()));
+                }
+            }
+        }
+        // functions and modules can be declared inside a function body, as free items again
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit_mut::visit_item_fn_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+        match self
+            .scope
+            .edits(self.fn_name, &i.attrs, &i.sig, self.scope.enclosing())
+        {
+            false => (),
+            true => {
+                if self.fn_lifetime_bounder(&mut i.sig, i.default.as_mut()) {
+                    let selector = selector_of(&i.sig.ident, self.scope.enclosing());
+                    self.modified.push(selector);
+                }
+            }
+        }
+        syn::visit_mut::visit_trait_item_method_mut(self, i);
+    }
+}
+
+impl FnLifetimeBounder<'_> {
+    // returns whether `sig` changed, a bound it already has is no progress for the repair loop
+    fn fn_lifetime_bounder(&mut self, sig: &mut Signature, block: Option<&mut Block>) -> bool {
+        // an earlier iteration added the reverse bound: together they force both lifetimes to be
+        // the same, and stacking them lets rustc ask for each in turn forever
+        let declared = |lt: &str| {
+            sig.generics
+                .lifetimes()
+                .any(|def| def.lifetime.to_string() == lt)
+        };
+        if declared(self.lifetime)
+            && declared(self.bound)
+            && has_lifetime_bound(&sig.generics, self.bound, self.lifetime)
+        {
+            debug!("unifying {} into {}", self.bound, self.lifetime);
+            unify_lifetimes(sig, block, self.bound, self.lifetime);
+            self.unified = true;
+            self.success = true;
+            return true;
+        }
+        if has_lifetime_bound(&sig.generics, self.lifetime, self.bound) {
+            self.success = true;
+            self.present = true;
+            return false;
+        }
+        let gen = &mut sig.generics;
+        let wc = gen.where_clause.get_or_insert(WhereClause {
+            where_token: Default::default(),
+            predicates: Default::default(),
+        });
+        insert_lifetime_bound(wc, self.lifetime, self.bound);
+        sort_lifetime_predicates(wc);
+        self.success = true;
+        true
+    }
+}
+
+// adds `lifetime: bound` to the predicate of `lifetime` (`'a: 'c + 'b`) or as a new one after the
+// other lifetime predicates, so they stay together ahead of the type predicates of a mixed clause.
+// The predicates are re-punctuated, whatever trailing comma the clause had
+fn insert_lifetime_bound(wc: &mut WhereClause, lifetime: &str, bound: &str) {
+    let bound = Lifetime::new(bound, Span::call_site());
+    let mut predicates: Vec<WherePredicate> = wc.predicates.iter().cloned().collect();
+    let existing = predicates.iter_mut().find_map(|wp| match wp {
+        WherePredicate::Lifetime(lt) if lt.lifetime.to_string() == lifetime => Some(lt),
+        _ => None,
+    });
+    match existing {
+        Some(lt) => {
+            let mut bounds: Vec<Lifetime> = lt.bounds.iter().cloned().collect();
+            bounds.push(bound);
+            bounds.sort_by_key(|b| b.to_string());
+            lt.bounds = bounds.into_iter().collect();
+        }
+        None => {
+            let mut wp = PredicateLifetime {
+                lifetime: Lifetime::new(lifetime, Span::call_site()),
+                colon_token: Default::default(),
+                bounds: Default::default(),
+            };
+            wp.bounds.push(bound);
+            let after_lifetimes = predicates
+                .iter()
+                .rposition(|wp| matches!(wp, WherePredicate::Lifetime(_)))
+                .map_or(0, |i| i + 1);
+            predicates.insert(after_lifetimes, WherePredicate::Lifetime(wp));
+        }
+    }
+    wc.predicates = predicates.into_iter().collect();
+}
+
+// sorts the lifetime predicates by lhs then rhs, in the slots lifetime predicates already take, so
+// the output doesn't depend on the order rustc suggested the bounds in
+fn sort_lifetime_predicates(wc: &mut WhereClause) {
+    let key = |lt: &PredicateLifetime| {
+        let bounds: Vec<String> = lt.bounds.iter().map(|b| b.to_string()).collect();
+        (lt.lifetime.to_string(), bounds)
+    };
+    let mut sorted: Vec<PredicateLifetime> = wc
+        .predicates
+        .iter()
+        .filter_map(|wp| match wp {
+            WherePredicate::Lifetime(lt) => Some(lt.clone()),
+            _ => None,
+        })
+        .collect();
+    sorted.sort_by_key(key);
+    let mut sorted = sorted.into_iter();
+    for wp in wc.predicates.iter_mut() {
+        if let WherePredicate::Lifetime(lt) = wp {
+            *lt = sorted.next().unwrap();
+        }
+    }
+}
+
+/// Whether `lifetime: bound` is declared, either on the generic parameter or in the where clause
+fn has_lifetime_bound(gen: &Generics, lifetime: &str, bound: &str) -> bool {
+    let on_param = gen.lifetimes().any(|def| {
+        def.lifetime.to_string() == lifetime && def.bounds.iter().any(|b| b.to_string() == bound)
+    });
+    let in_where_clause = gen.where_clause.as_ref().is_some_and(|wc| {
+        wc.predicates.iter().any(|wp| match wp {
+            WherePredicate::Lifetime(lt) => {
+                lt.lifetime.to_string() == lifetime
+                    && lt.bounds.iter().any(|b| b.to_string() == bound)
+            }
+            _ => false,
+        })
+    });
+    on_param || in_where_clause
+}
+
+// renames a lifetime of the function, nested items declare their own and are left alone
+struct LifetimeUnifier<'a> {
+    from: &'a str,
+    to: &'a str,
+}
+
+impl VisitMut for LifetimeUnifier<'_> {
+    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+        if i.to_string() == self.from {
+            *i = Lifetime::new(self.to, Span::call_site())
+        }
+    }
+
+    fn visit_item_mut(&mut self, _i: &mut Item) {}
+}
+
+/// Replace lifetime `from` with `to` in the signature and body, `from`'s bounds move over to `to`
+/// and the `to: to` bounds left behind are dropped
+fn unify_lifetimes(sig: &mut Signature, block: Option<&mut Block>, from: &str, to: &str) {
+    let gen = &mut sig.generics;
+    let from_bounds: Vec<Lifetime> = gen
+        .lifetimes()
+        .filter(|def| def.lifetime.to_string() == from)
+        .flat_map(|def| def.bounds.iter().cloned())
+        .collect();
+    gen.params = gen
+        .params
+        .iter()
+        .filter(|gp| !matches!(gp, GenericParam::Lifetime(def) if def.lifetime.to_string() == from))
+        .cloned()
+        .collect();
+    if let Some(def) = gen
+        .lifetimes_mut()
+        .find(|def| def.lifetime.to_string() == to)
+    {
+        def.bounds.extend(from_bounds);
+    }
+
+    let mut unifier = LifetimeUnifier { from, to };
+    unifier.visit_signature_mut(sig);
+    if let Some(block) = block {
+        unifier.visit_block_mut(block);
+    }
+
+    let gen = &mut sig.generics;
+    let not_self_bound = |b: &&Lifetime| b.to_string() != to;
+    for def in gen.lifetimes_mut() {
+        if def.lifetime.to_string() == to {
+            def.bounds = def.bounds.iter().filter(not_self_bound).cloned().collect();
+        }
+    }
+    if let Some(wc) = &mut gen.where_clause {
+        wc.predicates = wc
+            .predicates
+            .iter()
+            .cloned()
+            .filter_map(|wp| match wp {
+                WherePredicate::Lifetime(mut lt) if lt.lifetime.to_string() == to => {
+                    lt.bounds = lt.bounds.iter().filter(not_self_bound).cloned().collect();
+                    (!lt.bounds.is_empty()).then_some(WherePredicate::Lifetime(lt))
+                }
+                wp => Some(wp),
+            })
+            .collect();
+        if wc.predicates.is_empty() {
+            gen.where_clause = None;
+        }
+    }
+}
+
+pub fn repair_bounds_help(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<bool, RepairError> {
+    repair_bounds_help_tracked(stderr, new_file_name, fn_name, &mut vec![])
+}
+
+/// Like `repair_bounds_help`, skipping the functions `config.active_cfg` leaves out of the compile
+/// and logging under `config.label`
+pub fn repair_bounds_help_with_config(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    let diagnostics = rendered_diagnostics(stderr);
+    bounds_help_of(
+        diagnostics.iter().map(String::as_str),
+        new_file_name,
+        fn_name,
+        config,
+        &mut vec![],
+        &mut vec![],
+    )
+}
+
+/// Like `repair_bounds_help`, also pushing the selector of each function it added a bound to onto
+/// `modified`, see `elide_modified_functions`
+pub fn repair_bounds_help_tracked(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    modified: &mut Vec<String>,
+) -> Result<bool, RepairError> {
+    repair_bounds_help_with_unapplied(stderr, new_file_name, fn_name, modified, &mut vec![])
+}
+
+/// A bound rustc suggested that `repair_bounds_help` matched but found no function `fn_name`
+/// selects to add it to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnappliedBound {
+    pub lifetime: String,
+    pub bound: String,
+    /// The diagnostic suggesting it
+    pub rendered: String,
+}
+
+/// Like `repair_bounds_help_tracked`, also pushing every suggested bound it couldn't apply onto
+/// `unapplied`
+pub fn repair_bounds_help_with_unapplied(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    modified: &mut Vec<String>,
+    unapplied: &mut Vec<UnappliedBound>,
+) -> Result<bool, RepairError> {
+    let diagnostics = rendered_diagnostics(stderr);
+    bounds_help_of(
+        diagnostics.iter().map(String::as_str),
+        new_file_name,
+        fn_name,
+        &RepairConfig::default(),
+        modified,
+        unapplied,
+    )
+}
+
+/// `repair_bounds_help_with_config` for diagnostics the caller already has as [`RustcError`]s,
+/// without a round trip through json
+pub fn repair_bounds_help_from_errors(
+    errors: &[RustcError],
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    bounds_help_of(
+        errors.iter().map(|error| error.rendered.as_str()),
+        new_file_name,
+        fn_name,
+        config,
+        &mut vec![],
+        &mut vec![],
+    )
+}
+
+fn bounds_help_of<'a>(
+    diagnostics: impl Iterator<Item = &'a str>,
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+    modified: &mut Vec<String>,
+    unapplied: &mut Vec<UnappliedBound>,
+) -> Result<bool, RepairError> {
+    let mut helped = false;
+    for rendered in diagnostics {
+        let help_lines = bounds_help_regex().captures_iter(rendered);
+        for captured in help_lines {
+            let (file_content, mut file) = read_target(new_file_name, fn_name)?;
+            let bounder = bound_in_ast(
+                &mut file,
+                fn_name,
+                &captured["constraint_lhs"],
+                &captured["constraint_rhs"],
+                config,
+            );
+            match (bounder.modified.is_empty(), bounder.present) {
+                (false, _) => {
+                    write_source(new_file_name, &print_edited(&file_content, &file), config)?;
+                    helped = true;
+                    for selector in bounder.modified {
+                        if !modified.contains(&selector) {
+                            modified.push(selector);
+                        }
+                    }
+                }
+                (true, true) => debug!(
+                    "[{}] bound `{}: {}` suggested but `{}` already has it",
+                    config.label, &captured["constraint_lhs"], &captured["constraint_rhs"], fn_name
+                ),
+                (true, false) => {
+                    info!(
+                        "[{}] bound `{}: {}` suggested but no function `{}` selects to add it to",
+                        config.label,
+                        &captured["constraint_lhs"],
+                        &captured["constraint_rhs"],
+                        fn_name
+                    );
+                    unapplied.push(UnappliedBound {
+                        lifetime: captured["constraint_lhs"].to_string(),
+                        bound: captured["constraint_rhs"].to_string(),
+                        rendered: rendered.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(helped)
+}
+
+/**
+Add the bounds lifetime errors without a suggested bound need, like `repair_bounds_help` does for
+the suggested ones. The bound is read off the labels: both lifetimes `defined here` and one
+`requires that 'b must outlive 'a` (or `supposed to return data with lifetime 'a but it is
+returning data with lifetime 'b`) give `'b: 'a`. Diagnostics suggesting a bound are left to
+`repair_bounds_help`
+*/
+pub fn repair_note_bounds(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<bool, RepairError> {
+    repair_note_bounds_with_config(stderr, new_file_name, fn_name, &RepairConfig::default())
+}
+
+/// Like `repair_note_bounds`, skipping the functions `config.active_cfg` leaves out of the compile
+/// and logging under `config.label`
+pub fn repair_note_bounds_with_config(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    let help = bounds_help_regex();
+    let [defined, outlives, returns] = note_regexes();
+    let mut helped = false;
+    for rendered in rendered_diagnostics(stderr) {
+        if help.is_match(&rendered) {
+            continue;
+        }
+        let captured = match outlives
+            .captures(&rendered)
+            .or_else(|| returns.captures(&rendered))
+        {
+            Some(captured) => captured,
+            None => continue,
+        };
+        let (lifetime, bound) = (&captured["constraint_lhs"], &captured["constraint_rhs"]);
+        // lifetimes not defined by the function (an impl's, `'static`) can't be bounded on it
+        let labelled: Vec<&str> = defined
+            .captures_iter(&rendered)
+            .map(|defined| defined.name("lifetime").unwrap().as_str())
+            .collect();
+        if !labelled.contains(&lifetime) || !labelled.contains(&bound) {
+            continue;
+        }
+        debug!(
+            "[{}] inferred bound {}: {} from:\n{}",
+            config.label, lifetime, bound, rendered
+        );
+        let (file_content, mut file) = read_target(new_file_name, fn_name)?;
+        let bounded =
+            add_lifetime_bound_in_ast_with_config(&mut file, fn_name, lifetime, bound, config);
+        if !bounded.is_empty() {
+            write_source(new_file_name, &print_edited(&file_content, &file), config)?;
+            helped = true;
+        }
+    }
+    Ok(helped)
+}
+
+/**
+Add the bound `lifetime: bound` to the functions `fn_name` selects in an already parsed `file`, as
+`repair_bounds_help` does for each bound rustc suggests. Returns the selector of every function it
+changed
+*/
+pub fn add_lifetime_bound_in_ast(
+    file: &mut syn::File,
+    fn_name: &str,
+    lifetime: &str,
+    bound: &str,
+) -> Vec<String> {
+    add_lifetime_bound_in_ast_with_config(file, fn_name, lifetime, bound, &RepairConfig::default())
+}
+
+/// `add_lifetime_bound_in_ast` skipping the functions `config.active_cfg` leaves out of the compile
+pub fn add_lifetime_bound_in_ast_with_config(
+    file: &mut syn::File,
+    fn_name: &str,
+    lifetime: &str,
+    bound: &str,
+    config: &RepairConfig,
+) -> Vec<String> {
+    bound_in_ast(file, fn_name, lifetime, bound, config).modified
+}
+
+// `add_lifetime_bound_in_ast_with_config`, returning the visitor to tell a bound already there
+// from a function that isn't
+fn bound_in_ast<'a>(
+    file: &mut syn::File,
+    fn_name: &'a str,
+    lifetime: &'a str,
+    bound: &'a str,
+    config: &'a RepairConfig,
+) -> FnLifetimeBounder<'a> {
+    let mut visit = FnLifetimeBounder {
+        fn_name,
+        scope: Scope::new(config.active_cfg.as_deref()),
+        lifetime,
+        bound,
+        success: false,
+        unified: false,
+        present: false,
+        modified: vec![],
+    };
+    visit.visit_file_mut(file);
+    visit
+}
+
+struct HiddenCaptureBounder<'a> {
+    fn_name: &'a str,
+    scope: Scope<'a>,
+    edition: Edition,
+    lifetime: &'a str,
+    success: bool,
+}
+
+impl VisitMut for HiddenCaptureBounder<'_> {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit_mut::visit_item_impl_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit_mut::visit_item_trait_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+        match self
+            .scope
+            .edits(self.fn_name, &i.attrs, &i.sig, self.scope.enclosing())
+        {
+            false => (),
+            true => self.bound_hidden_capture(&mut i.sig),
+        }
+        syn::visit_mut::visit_impl_item_method_mut(self, i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        let scope = self.scope.free_fn();
+        match self
+            .scope
+            .edits(self.fn_name, &i.attrs, &i.sig, scope.as_deref())
+        {
+            false => (),
+            true => self.bound_hidden_capture(&mut i.sig),
+        }
+        // functions and modules can be declared inside a function body, as free items again
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit_mut::visit_item_fn_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+        match self
+            .scope
+            .edits(self.fn_name, &i.attrs, &i.sig, self.scope.enclosing())
+        {
+            false => (),
+            true => self.bound_hidden_capture(&mut i.sig),
+        }
+        syn::visit_mut::visit_trait_item_method_mut(self, i);
+    }
+}
+
+impl HiddenCaptureBounder<'_> {
+    // `-> impl Trait + use<'b>` becomes `-> impl Trait + use<'a, 'b>`, the clause lists all the
+    // hidden type captures. Without one `-> impl Trait` becomes `-> impl Trait + 'a` before 2024,
+    // from 2024 on it captures every lifetime in scope already and is left as it is
+    fn bound_hidden_capture(&mut self, sig: &mut Signature) {
+        if let ReturnType::Type(_, ty) = &mut sig.output {
+            if let Type::ImplTrait(impl_trait) = ty.as_mut() {
+                if let Some(captures) = precise_captures(impl_trait) {
+                    let captures = &mut captures.args;
+                    let listed = captures.iter().any(|arg| match arg {
+                        GenericArgument::Lifetime(lt) => lt.to_string() == self.lifetime,
+                        _ => false,
+                    });
+                    if !listed {
+                        // lifetimes come first in the clause
+                        let at = captures
+                            .iter()
+                            .take_while(|arg| matches!(arg, GenericArgument::Lifetime(_)))
+                            .count();
+                        let lifetime = Lifetime::new(self.lifetime, Span::call_site());
+                        captures.insert(at, GenericArgument::Lifetime(lifetime));
+                        self.success = true
+                    }
+                    return;
+                }
+                if self.edition >= Edition::E2024 {
+                    debug!(
+                        "`impl Trait` captures {} implicitly on {}, not bounding it",
+                        self.lifetime,
+                        self.edition.as_str()
+                    );
+                    return;
+                }
+                let bound_present = impl_trait.bounds.iter().any(|bound| match bound {
+                    TypeParamBound::Lifetime(lt) => lt.to_string() == self.lifetime,
+                    _ => false,
+                });
+                if !bound_present {
+                    let lifetime = Lifetime::new(self.lifetime, Span::call_site());
+                    impl_trait.bounds.push(TypeParamBound::Lifetime(lifetime));
+                    self.success = true
+                }
+            }
+        }
+    }
+}
+
+// the precise capturing clause (`use<'a, T>`) of `impl_trait`, if it has one
+fn precise_captures(impl_trait: &mut TypeImplTrait) -> Option<&mut AngleBracketedGenericArguments> {
+    impl_trait.bounds.iter_mut().find_map(|bound| match bound {
+        TypeParamBound::Trait(t) if t.path.segments.len() == 1 => {
+            let segment = t.path.segments.first_mut()?;
+            match &mut segment.arguments {
+                PathArguments::AngleBracketed(captures) if segment.ident == PRECISE_CAPTURE => {
+                    Some(captures)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/**
+Repair E0700, an `impl Trait` return type whose hidden type captures a lifetime its bounds don't
+mention, by adding that lifetime as a bound (`impl Trait + 'a`), or to its precise capturing clause
+(`impl Trait + use<'a>`) when it has one. From edition 2024 on (`RepairConfig::edition`) an
+`impl Trait` without a clause captures every lifetime in scope, so it's left as it is
+
+Captured lifetimes that are elided (the "anonymous lifetime") have no name to add and are skipped
+*/
+pub fn repair_hidden_lifetime_capture(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<bool, RepairError> {
+    repair_hidden_lifetime_capture_with_config(
+        stderr,
+        new_file_name,
+        fn_name,
+        &RepairConfig::default(),
+    )
+}
+
+/// Like `repair_hidden_lifetime_capture`, skipping the functions `config.active_cfg` leaves out of
+/// the compile
+pub fn repair_hidden_lifetime_capture_with_config(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, RepairError> {
+    let deserializer = serde_json::Deserializer::from_str(stderr);
+    let stream = deserializer.into_iter::<RustcError>();
+    static CAPTURED: OnceLock<Regex> = OnceLock::new();
+    let re = CAPTURED.get_or_init(|| {
+        Regex::new(r"captures the lifetime `(?P<lifetime>'[a-zA-Z0-9_]+)`").unwrap()
+    });
+    let mut helped = false;
+    for item in stream {
+        let rendered = match item {
+            Ok(i) => i.rendered,
+            Err(_) => stderr.to_string(),
+        };
+        if !rendered.contains("error[E0700]") {
+            continue;
+        }
+        for captured in re.captures_iter(rendered.as_str()) {
+            let (file_content, mut file) = read_target(new_file_name, fn_name)?;
+            let mut visit = HiddenCaptureBounder {
+                fn_name,
+                scope: Scope::new(config.active_cfg.as_deref()),
+                edition: config.edition,
+                lifetime: &captured["lifetime"],
+                success: false,
+            };
+            visit.visit_file_mut(&mut file);
+            if visit.success {
+                write_source(new_file_name, &print_edited(&file_content, &file), config)?;
+                helped = true;
+            }
+        }
+    }
+    Ok(helped)
+}
+
+pub fn repair_iteration(
+    compile_cmd: &mut Command,
+    process_errors: &dyn Fn(&str) -> bool,
+    config: &RepairConfig,
+) -> RepairResult {
+    let mut count = 0;
+    let label = config.label.as_str();
+    let mut repair_result = RepairResult::failed();
+
+    let success = loop {
+        let started = Instant::now();
+        let out = match run_compile(compile_cmd) {
+            Ok(out) => out,
+            Err(e) => {
+                warn!("[{}] {}", label, e);
+                break false;
+            }
+        };
+        repair_result.compile_time += started.elapsed();
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        if out.status.success() {
+            break true;
+        }
+        count += 1;
+
+        let temp = stderr.to_string();
+        if !process_errors(temp.as_str()) {
+            break false;
+        }
+        if config.max_iterations == count {
+            break false;
+        }
+    };
+
+    if config.print_stats {
+        info!("[{}] repair count: {}", label, count);
+        info!("[{}] status: {}", label, success);
+    }
+
+    repair_result.success = success;
+    repair_result.repair_count = count;
+    repair_result
+}
+
+/// One kind of repair: the diagnostics it applies to and how to fix them
+pub struct RepairPass<'a> {
+    pub name: &'a str,
+    pub matches: &'a dyn Fn(&RustcError) -> bool,
+    /// Whether the diagnostic was repaired. An error is logged and repairs nothing
+    pub repair: &'a dyn Fn(&RustcError) -> Result<bool, RepairError>,
+}
+
+// applies the first pass that repairs any of `errors`, returns whether one did
+fn apply_passes(errors: &[RustcError], passes: &[RepairPass], config: &RepairConfig) -> bool {
+    apply_passes_counting(errors, passes, config, &mut HashSet::new())
+}
+
+// `apply_passes` adding the codes of the errors it hands to a pass to `attempted`, those of the
+// lower priority passes it doesn't get to aren't attempts
+fn apply_passes_counting(
+    errors: &[RustcError],
+    passes: &[RepairPass],
+    config: &RepairConfig,
+    attempted: &mut HashSet<String>,
+) -> bool {
+    passes.iter().any(|pass| {
+        let mut help = false;
+        for error in errors.iter().filter(|error| (pass.matches)(error)) {
+            attempted.extend(error.code.as_ref().map(|code| code.code.clone()));
+            debug!(
+                "[{}] {} processing error: {}",
+                config.label, pass.name, &error.rendered
+            );
+            match (pass.repair)(error) {
+                Ok(repaired) => help |= repaired,
+                Err(e) => warn!("[{}] {} failed: {}", config.label, pass.name, e),
+            }
+        }
+        help
+    })
+}
+
+/// `repair_iteration` for a compile with `--error-format=json`, applying `passes` in priority
+/// order like `repair_iteration_project_prioritized`
+pub fn repair_iteration_prioritized(
+    compile_cmd: &mut Command,
+    passes: &[RepairPass],
+    config: &RepairConfig,
+) -> RepairResult {
+    let process_errors = |stderr: &str| {
+        let errors = diagnostic_parser(config).parse_rustc(stderr);
+        apply_passes(&errors, passes, config)
+    };
+    repair_iteration(compile_cmd, &process_errors, config)
+}
+
+/**
+What a repair system does to the repaired file around the passes of `default_repair_function` and
+`default_repair_project`
+*/
+pub struct RepairSteps<'a> {
+    /// Run before the first compile, e.g. annotating the lifetimes the passes then bound. Returning
+    /// false or an error gives up without compiling
+    pub prepare: &'a dyn Fn(&str) -> Result<bool, RepairError>,
+    /// Run once the file compiles, with the command that compiled it, e.g. eliding the annotations
+    /// the repair didn't need. An error fails the repair
+    pub finish: &'a dyn Fn(&str, &mut Command, &mut RepairResult) -> Result<(), RepairError>,
+}
+
+impl RepairSteps<'_> {
+    /// Nothing around the passes
+    pub fn none() -> RepairSteps<'static> {
+        RepairSteps {
+            prepare: &|_| Ok(true),
+            finish: &|_, _, _| Ok(()),
+        }
+    }
+
+    // runs `prepare`, logging an error
+    fn prepared(&self, file_name: &str, config: &RepairConfig) -> bool {
+        (self.prepare)(file_name).unwrap_or_else(|e| {
+            warn!("[{}] cannot prepare the repair: {}", config.label, e);
+            false
+        })
+    }
+
+    // runs `finish` on a successful `result`, failing it on an error
+    fn finished(
+        &self,
+        file_name: &str,
+        compile_cmd: &mut Command,
+        result: &mut RepairResult,
+        config: &RepairConfig,
+    ) {
+        if let Err(e) = (self.finish)(file_name, compile_cmd, result) {
+            warn!("[{}] cannot finish the repair: {}", config.label, e);
+            result.success = false;
+        }
+    }
+}
+
+// whether `fn_name` selects the one function of `file_name` to repair, logging why not. An empty
+// `fn_name` targets the whole file when `whole_file` is set
+fn check_target(file_name: &str, fn_name: &str, whole_file: bool, config: &RepairConfig) -> bool {
+    let lookup = match lookup_function(file_name, fn_name) {
+        Ok(lookup) => lookup,
+        Err(e) => {
+            warn!("[{}] cannot repair: {}", config.label, e);
+            return false;
+        }
+    };
+    let whole_file = whole_file && fn_name.is_empty();
+    if lookup == FnLookup::EmptySource || (!whole_file && lookup != FnLookup::Found) {
+        warn!(
+            "[{}] cannot repair `{}` in {}: {:?}",
+            config.label, fn_name, file_name, lookup
+        );
+        return false;
+    }
+    if let Err(e) = check_unambiguous(file_name, fn_name, config) {
+        warn!("[{}] cannot repair: {}", config.label, e);
+        return false;
+    }
+    true
+}
+
+// `final_signatures` of a repaired file, none if it can't be read back
+fn signatures_or_warn(
+    file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> HashMap<String, String> {
+    final_signatures(file_name, fn_name).unwrap_or_else(|e| {
+        warn!("[{}] cannot report the signatures: {}", config.label, e);
+        HashMap::new()
+    })
+}
+
+/**
+The usual `RepairSystem::repair_function`: check `fn_name` exists, repair a copy of `file_name` at
+`new_file_name` with `passes` until it compiles. Repair systems that annotate before or elide after
+the loop do so with `default_repair_function_with_steps`
+*/
+pub fn default_repair_function(
+    file_name: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    passes: &[RepairPass],
+    config: &RepairConfig,
+) -> RepairResult {
+    default_repair_function_with_steps(
+        file_name,
+        new_file_name,
+        fn_name,
+        passes,
+        &RepairSteps::none(),
+        config,
+    )
+}
+
+/// `default_repair_function` running `steps` on the copy around the passes
+pub fn default_repair_function_with_steps(
+    file_name: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    passes: &[RepairPass],
+    steps: &RepairSteps,
+    config: &RepairConfig,
+) -> RepairResult {
+    let process_errors = |stderr: &str| {
+        let errors = diagnostic_parser(config).parse_rustc(stderr);
+        apply_passes(&errors, passes, config)
+    };
+    default_repair_function_with_processor(
+        file_name,
+        new_file_name,
+        fn_name,
+        &process_errors,
+        steps,
+        config,
+    )
+}
+
+/// `default_repair_function_with_steps` handing the whole json output of each compile to
+/// `process_errors`, as `repair_iteration` does, for repairs that aren't a set of passes
+pub fn default_repair_function_with_processor(
+    file_name: &str,
+    new_file_name: &str,
+    fn_name: &str,
+    process_errors: &dyn Fn(&str) -> bool,
+    steps: &RepairSteps,
+    config: &RepairConfig,
+) -> RepairResult {
+    if !check_target(file_name, fn_name, true, config) {
+        return RepairResult::failed();
+    }
+    if let Err(e) = copy_source(file_name, new_file_name, config) {
+        warn!("[{}] cannot repair: {}", config.label, e);
+        return RepairResult::failed();
+    }
+    if !steps.prepared(new_file_name, config) {
+        return RepairResult::failed();
+    }
+    let mut compile_cmd = compile_file(new_file_name, &vec!["--error-format=json"]);
+    let mut result = repair_iteration(&mut compile_cmd, process_errors, config);
+    if result.success {
+        steps.finished(new_file_name, &mut compile_cmd, &mut result, config);
+    }
+    let tests_failed = result.success && !passes_tests(config);
+    result.success &= !tests_failed;
+    if result.success && !fn_name.is_empty() {
+        result.final_signatures = signatures_or_warn(new_file_name, fn_name, config);
+    }
+    let cleaned_up = if tests_failed {
+        warn!(
+            "[{}] tests failed after the repair, reverting {}",
+            config.label, new_file_name
+        );
+        copy_source(file_name, new_file_name, config)
+    } else if !result.success && config.revert_if_not_converged {
+        warn!(
+            "[{}] did not converge, reverting {}",
+            config.label, new_file_name
+        );
+        copy_source(file_name, new_file_name, config)
+    } else {
+        read_source(file_name).and_then(|original| {
+            format_repaired(new_file_name, &original, config)?;
+            restore_markers(new_file_name, &original, config)
+        })
+    };
+    if let Err(e) = cleaned_up {
+        warn!("[{}] {}", config.label, e);
+    }
+    result
+}
+
+// copies `file_name` over `new_file_name`, errors name the file that failed. The copy is fresh, so
+// the marker comments of whatever `new_file_name` held before aren't carried over
+fn copy_source(
+    file_name: &str,
+    new_file_name: &str,
+    config: &RepairConfig,
+) -> Result<(), RepairError> {
+    let content = read_source(file_name)?;
+    write_through_hook(new_file_name, &content, config)
+}
+
+/**
+The usual `RepairSystem::repair_project`: check `fn_name` exists in `src_path`, then repair it in
+place with `passes` until `cargo check` on `manifest_path` succeeds, running `steps` around them
+*/
+pub fn default_repair_project(
+    src_path: &str,
+    manifest_path: &str,
+    fn_name: &str,
+    passes: &[RepairPass],
+    steps: &RepairSteps,
+    config: &RepairConfig,
+) -> RepairResult {
+    if !check_target(src_path, fn_name, false, config) || !steps.prepared(src_path, config) {
+        return RepairResult::failed();
+    }
+    let mut compile_cmd = check_project(manifest_path, &vec![]);
+    let mut result =
+        repair_iteration_project_prioritized(&mut compile_cmd, src_path, passes, config);
+    if result.success {
+        steps.finished(src_path, &mut compile_cmd, &mut result, config);
+    }
+    if result.success {
+        result.final_signatures = signatures_or_warn(src_path, fn_name, config);
+    }
+    result
+}
+
+/**
+Repair every function of `file_name` whose ident matches `pattern` (say `_extracted$`) into
+`new_file_name`, one after the other so each repair starts from the result of the previous one.
+Returns the selector and result of each, in file order
+
+The repair systems copy their input over `new_file_name`, so the output of each successful repair
+is moved aside to `{new_file_name}.prev` as the input of the next one, and back at the end. A
+failed repair leaves no trace: the next one starts from the last success, and `new_file_name` ends
+up with every successful repair, or a copy of `file_name` if none succeeded. Only the matching functions are targeted, but passes that apply
+rustc's suggestions wherever they point (`repair_standard_help`) can still touch others
+*/
+pub fn repair_functions_matching(
+    file_name: &str,
+    new_file_name: &str,
+    pattern: &Regex,
+    repair_system: &dyn RepairSystem,
+    config: &RepairConfig,
+) -> Result<Vec<(String, RepairResult)>, RepairError> {
+    let fn_names = functions_matching(file_name, pattern)?;
+    info!(
+        "[{}] {} functions match `{}`: {:?}",
+        config.label,
+        fn_names.len(),
+        pattern,
+        fn_names
+    );
+    let prev_file_name = format!("{}.prev", new_file_name);
+    let mut results = vec![];
+    // whether `prev_file_name` holds the output of a successful repair
+    let mut repaired = false;
+    for fn_name in fn_names {
+        let input = match repaired {
+            true => prev_file_name.as_str(),
+            false => file_name,
+        };
+        let result =
+            repair_system.repair_function_with_config(input, new_file_name, &fn_name, config);
+        debug!(
+            "[{}] {}: success {}, repair count {}",
+            config.label, fn_name, result.success, result.repair_count
+        );
+        if result.success && Path::new(new_file_name).exists() {
+            if let Err(e) = fs::rename(new_file_name, &prev_file_name) {
+                // `new_file_name` still has the last success, the one moved aside before it is older
+                let _ = fs::remove_file(&prev_file_name);
+                return Err(RepairError::io(new_file_name, e));
+            }
+            repaired = true;
+        }
+        results.push((fn_name, result));
+    }
+    match repaired {
+        true => fs::rename(&prev_file_name, new_file_name)
+            .map_err(|e| RepairError::io(&prev_file_name, e))?,
+        // the last failed attempt is still in `new_file_name`
+        false if !results.is_empty() => copy_source(file_name, new_file_name, config)?,
+        false => (),
+    }
+    Ok(results)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+////////////////////////////////    ELIDING LIFETIMES   ////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////////////////////////
+struct FnLifetimeEliderTypeHelper<'a> {
+    cannot_elide: &'a Vec<String>,
+    force_elide: &'a [String],
+    style: ElisionStyle,
+    lt_count: &'a HashMap<&'a String, i32>,
+}
+
+impl FnLifetimeEliderTypeHelper<'_> {
+    fn is_elidible(&self, lt: &Lifetime) -> bool {
+        let id = lt.to_string();
+        id != "'static"
+            && id != "'_"
+            && !self.cannot_elide.contains(&id)
+            && (self.force_elide.contains(&id)
+                || self.lt_count.get(&id).is_none_or(|count| *count <= 1))
+    }
+}
+
+// only the places a lifetime can be written are overridden, the default recursion reaches them
+// inside tuples (`(&'a i32, &'b str)`), arrays and slices (`[&'a i32; 2]`) and pointers alike
+impl VisitMut for FnLifetimeEliderTypeHelper<'_> {
+    // lifetime generic arguments (`Foo<'a>`) cannot be removed, only replaced with '_
+    fn visit_angle_bracketed_generic_arguments_mut(
+        &mut self,
+        i: &mut AngleBracketedGenericArguments,
+    ) {
+        for arg in i.args.iter_mut() {
+            if let GenericArgument::Lifetime(lt) = arg {
+                if self.is_elidible(lt) {
+                    *lt = Lifetime::new("'_", Span::call_site())
+                }
+            }
+        }
+        syn::visit_mut::visit_angle_bracketed_generic_arguments_mut(self, i);
+    }
+
+    fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
+        if let Some(lt) = &i.lifetime {
+            if self.is_elidible(lt) {
+                i.lifetime = match self.style {
+                    ElisionStyle::Remove => None,
+                    ElisionStyle::Anonymize => Some(Lifetime::new("'_", Span::call_site())),
+                }
+            }
+        }
+        syn::visit_mut::visit_type_reference_mut(self, i);
+    }
+
+    // `dyn Trait + 'a` has no elided form other than '_, dropping the bound would default it to
+    // 'static. Lifetimes of a trait object in the output are in cannot_elide and stay as they are
+    fn visit_type_trait_object_mut(&mut self, i: &mut TypeTraitObject) {
+        for bound in i.bounds.iter_mut() {
+            if let TypeParamBound::Lifetime(lt) = bound {
+                if self.is_elidible(lt) {
+                    *lt = Lifetime::new("'_", Span::call_site())
+                }
+            }
+        }
+        syn::visit_mut::visit_type_trait_object_mut(self, i);
+    }
+
+    // const expressions (array lengths like `[&'a i32; N]`, const generic arguments) are left
+    // exactly as written, eliding a lifetime in expression position would change what it refers to
+    fn visit_expr_mut(&mut self, _i: &mut Expr) {}
+}
+
+struct FnLifetimeEliderArgHelper<'a> {
+    cannot_elide: &'a Vec<String>,
+    force_elide: &'a [String],
+    style: ElisionStyle,
+    lt_count: &'a HashMap<&'a String, i32>,
+}
+
+impl VisitMut for FnLifetimeEliderArgHelper<'_> {
+    fn visit_fn_arg_mut(&mut self, i: &mut FnArg) {
+        match i {
+            FnArg::Typed(t) => {
+                let mut type_helper = FnLifetimeEliderTypeHelper {
+                    cannot_elide: self.cannot_elide,
+                    force_elide: self.force_elide,
+                    style: self.style,
+                    lt_count: self.lt_count,
+                };
+                type_helper.visit_type_mut(t.ty.as_mut());
+            }
+            FnArg::Receiver(_) => (), // cannot elide if there is self
+        }
+    }
+}
+
+struct FnLifetimeElider<'a> {
+    fn_name: &'a str,
+    force_elide: &'a [String],
+    style: ElisionStyle,
+    /// Names the lifetimes that survive elision by their index
+    name_policy: &'a dyn Fn(usize) -> String,
+    method_scope: MethodScope,
+    /// See `RepairConfig::max_elision_depth`
+    max_depth: Option<usize>,
+    scope: Scope<'a>,
+    /// Trait implemented by the surrounding impl, if any
+    impl_trait: Option<String>,
+    /// Lifetimes declared by the surrounding impl or trait, never elided from its methods
+    outer_lifetimes: Vec<String>,
+    /// Traits with an impl whose method was elided
+    elided_impl_traits: Vec<String>,
+    found: bool,
+    /// Lifetimes declared by the selected functions before elision
+    declared: Vec<String>,
+    annotations_left: bool,
+    has_struct_lt: bool,
+    lifetime_map: HashMap<String, String>,
+}
+
+// collects every occurrence, nested references included: in `&'a mut &'b T` both `'a` and `'b` are
+// counted (and later elided) independently of each other
+struct LtGetterElider<'a> {
+    v: &'a mut Vec<String>,
+}
+impl VisitMut for LtGetterElider<'_> {
+    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+        let id = i.to_string();
+        self.v.push(id.to_string());
+        syn::visit_mut::visit_lifetime_mut(self, i)
+    }
+}
+
+// lifetimes in `Fn(&'a T) -> &'a U` sugar and `fn(&'a T) -> &'a U` pointers can't be elided:
+// there an elided lifetime is bound by the closure's own inputs (or is an error without any)
+// instead of the function's
+struct FnSugarLtGetter<'a> {
+    v: &'a mut Vec<String>,
+}
+
+impl VisitMut for FnSugarLtGetter<'_> {
+    fn visit_parenthesized_generic_arguments_mut(&mut self, i: &mut ParenthesizedGenericArguments) {
+        let mut get_lt = LtGetterElider { v: self.v };
+        get_lt.visit_parenthesized_generic_arguments_mut(i);
+    }
+
+    fn visit_type_bare_fn_mut(&mut self, i: &mut TypeBareFn) {
+        let mut get_lt = LtGetterElider { v: self.v };
+        get_lt.visit_type_bare_fn_mut(i);
+    }
+}
+
+// lifetime bounds of trait objects (`dyn Trait + 'a`), written out in the output they say what the
+// returned object captures
+struct TraitObjectLtGetter<'a> {
+    v: &'a mut Vec<String>,
+}
+
+impl VisitMut for TraitObjectLtGetter<'_> {
+    fn visit_type_trait_object_mut(&mut self, i: &mut TypeTraitObject) {
+        for bound in i.bounds.iter() {
+            if let TypeParamBound::Lifetime(lt) = bound {
+                self.v.push(lt.to_string());
+            }
+        }
+        syn::visit_mut::visit_type_trait_object_mut(self, i);
+    }
+}
+
+// lifetimes in associated type projections (`<T as Trait<'a>>::Assoc`, `I::Assoc<'a>`) are kept,
+// the projected type may depend on them in ways the signature alone doesn't show
+struct ProjectionLtGetter<'a> {
+    type_params: Vec<String>,
+    v: &'a mut Vec<String>,
+}
+
+impl VisitMut for ProjectionLtGetter<'_> {
+    fn visit_type_path_mut(&mut self, i: &mut TypePath) {
+        let projects_type_param = i.path.segments.len() > 1
+            && self
+                .type_params
+                .iter()
+                .any(|param| i.path.segments[0].ident == param);
+        if i.qself.is_some() || projects_type_param {
+            let mut get_lt = LtGetterElider { v: self.v };
+            get_lt.visit_type_path_mut(i);
+        } else {
+            syn::visit_mut::visit_type_path_mut(self, i);
+        }
+    }
+}
+
+// types whose lifetime arguments are invariant, matched on the last path segment
+const INVARIANT_WRAPPERS: [&str; 5] = ["Cell", "RefCell", "UnsafeCell", "Mutex", "RwLock"];
+
+// lifetimes nested deeper than `max_depth` types into a type of the signature, see
+// `RepairConfig::max_elision_depth`
+struct DeepLtGetter<'a> {
+    max_depth: usize,
+    // types entered so far, a lifetime written on the outermost one is at depth 0
+    types: usize,
+    v: &'a mut Vec<String>,
+}
+
+impl VisitMut for DeepLtGetter<'_> {
+    fn visit_type_mut(&mut self, i: &mut Type) {
+        self.types += 1;
+        syn::visit_mut::visit_type_mut(self, i);
+        self.types -= 1;
+    }
+
+    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+        if self.types > self.max_depth + 1 {
+            self.v.push(i.to_string());
+        }
+    }
+
+    // the type helper leaves const expressions alone at any depth
+    fn visit_expr_mut(&mut self, _i: &mut Expr) {}
+}
+
+// lifetimes in invariant positions (`&'a mut T` and everything in `T`, `*mut T`, `Cell<&'a T>`, ...)
+// are kept unless forced: eliding one only looks safe from the usage count, callers relying on the
+// exact lifetime can break even though the function itself still compiles
+struct InvariantLtGetter<'a> {
+    v: &'a mut Vec<String>,
+}
+
+impl VisitMut for InvariantLtGetter<'_> {
+    fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
+        match i.mutability {
+            Some(_) => {
+                let mut get_lt = LtGetterElider { v: self.v };
+                get_lt.visit_type_reference_mut(i);
+            }
+            None => syn::visit_mut::visit_type_reference_mut(self, i),
+        }
+    }
+
+    fn visit_type_ptr_mut(&mut self, i: &mut TypePtr) {
+        match i.mutability {
+            Some(_) => {
+                let mut get_lt = LtGetterElider { v: self.v };
+                get_lt.visit_type_ptr_mut(i);
+            }
+            None => syn::visit_mut::visit_type_ptr_mut(self, i),
+        }
+    }
+
+    fn visit_type_path_mut(&mut self, i: &mut TypePath) {
+        let invariant = i
+            .path
+            .segments
+            .last()
+            .is_some_and(|seg| INVARIANT_WRAPPERS.iter().any(|w| seg.ident == w));
+        match invariant {
+            true => {
+                let mut get_lt = LtGetterElider { v: self.v };
+                get_lt.visit_type_path_mut(i);
+            }
+            false => syn::visit_mut::visit_type_path_mut(self, i),
+        }
+    }
+}
+
+// lifetimes anywhere in `impl Trait` arguments (`impl Iterator<Item = i32> + 'a`) stay named, an
+// anonymous lifetime in there is unstable and rule 2 wouldn't hand it to the output anyway
+struct ImplTraitLtGetter<'a> {
+    v: &'a mut Vec<String>,
+}
+
+impl VisitMut for ImplTraitLtGetter<'_> {
+    fn visit_type_impl_trait_mut(&mut self, i: &mut TypeImplTrait) {
+        let mut get_lt = LtGetterElider { v: self.v };
+        get_lt.visit_type_impl_trait_mut(i);
+    }
+}
+
+// the lifetime of the only reference in the inputs when the output names no other: elided inputs
+// get a lifetime each (rule 1) and with exactly one the elided output gets it too (rule 2), so
+// `fn f<'a, T>(x: &'a T) -> &'a T` is `fn f<T>(x: &T) -> &T`. `'static` in the output (a borrowed
+// const) stays as written and is no other lifetime. Neither is a trait object bound the output
+// names it in (`Box<dyn Trait + 'a>`), that stays as written too
+fn sole_input_lifetime(
+    sig: &Signature,
+    output_lts: &[String],
+    cannot_elide: &[String],
+) -> Option<String> {
+    let mut input_lts = vec![];
+    let mut elided_refs = ElidedRefCounter { count: 0 };
+    for fn_arg in sig.inputs.iter() {
+        match fn_arg {
+            FnArg::Receiver(_) => return None,
+            FnArg::Typed(_) => {
+                let mut get_lt = LtGetterElider { v: &mut input_lts };
+                get_lt.visit_fn_arg_mut(&mut fn_arg.clone());
+                elided_refs.visit_fn_arg(fn_arg);
+            }
+        }
+    }
+    // an input reference already elided gets a lifetime of its own, the output can't elide to one
+    if elided_refs.count > 0 {
+        return None;
+    }
+    // lifetimes the output's fn sugar, projections or trait objects name keep their meaning only
+    // when written
+    let mut fixed = vec![];
+    if let ReturnType::Type(_, ty) = &sig.output {
+        let mut get_fn_sugar_lt = FnSugarLtGetter { v: &mut fixed };
+        get_fn_sugar_lt.visit_type_mut(ty.clone().as_mut());
+        let mut get_projection_lt = ProjectionLtGetter {
+            type_params: sig
+                .generics
+                .type_params()
+                .map(|tp| tp.ident.to_string())
+                .collect(),
+            v: &mut fixed,
+        };
+        get_projection_lt.visit_type_mut(ty.clone().as_mut());
+        let mut get_trait_object_lt = TraitObjectLtGetter { v: &mut fixed };
+        get_trait_object_lt.visit_type_mut(ty.clone().as_mut());
+    }
+    match input_lts.as_slice() {
+        [lt] if lt != "'static"
+            && lt != "'_"
+            && output_lts.contains(lt)
+            && output_lts.iter().all(|out| out == lt || out == "'static")
+            && !cannot_elide.contains(lt)
+            && !fixed.contains(lt) =>
+        {
+            Some(lt.clone())
+        }
+        _ => None,
+    }
+}
+
+// references without a lifetime, those in fn sugar and fn pointers are elided against their own
+// inputs and don't count
+struct ElidedRefCounter {
+    count: usize,
+}
+
+impl<'ast> Visit<'ast> for ElidedRefCounter {
+    fn visit_type_reference(&mut self, i: &'ast TypeReference) {
+        if i.lifetime.is_none() {
+            self.count += 1;
+        }
+        syn::visit::visit_type_reference(self, i);
+    }
+
+    fn visit_parenthesized_generic_arguments(&mut self, _: &'ast ParenthesizedGenericArguments) {}
+
+    fn visit_type_bare_fn(&mut self, _: &'ast TypeBareFn) {}
+}
+
+struct ChangeLtHelperElider<'a> {
+    map: &'a HashMap<String, String>,
+    has_struct_lt: bool,
+}
+
+// renames the lifetimes in `map` wherever they occur and leaves every other one as it is, unlike
+// `ChangeLtHelperElider` which turns unknown lifetime arguments into '_
+struct LtRenamer<'a> {
+    map: &'a HashMap<String, String>,
+}
+
+impl VisitMut for LtRenamer<'_> {
+    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+        if let Some(new_lt) = self.map.get(&i.to_string()) {
+            *i = Lifetime::new(new_lt.as_str(), Span::call_site());
+        }
+    }
+}
+
+impl VisitMut for ChangeLtHelperElider<'_> {
+    fn visit_generic_argument_mut(&mut self, i: &mut GenericArgument) {
+        debug!("generic: {:?}", i);
+        match i {
+            GenericArgument::Lifetime(l) => {
+                let id = l.to_string();
+                debug!("generic lt: {:?}", &id);
+                self.has_struct_lt = true;
+                match self.map.get(&id) {
+                    Some(new_lt) => *l = Lifetime::new(new_lt.as_str(), Span::call_site()),
+                    // `Foo<'static>` is no elided lifetime, `'_` would make it one
+                    None if id == "'static" => (),
+                    None => *l = Lifetime::new("'_", Span::call_site()),
+                }
+            }
+            _ => syn::visit_mut::visit_generic_argument_mut(self, i),
+        }
+    }
+    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+        let id = i.to_string();
+        match self.map.get(&id) {
+            Some(new_lt) => *i = Lifetime::new(new_lt.as_str(), Span::call_site()),
+            None => (),
+        }
+        syn::visit_mut::visit_lifetime_mut(self, i)
+    }
+}
+
+impl VisitMut for FnLifetimeElider<'_> {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        let impl_trait = std::mem::replace(
+            &mut self.impl_trait,
+            i.trait_
+                .as_ref()
+                .and_then(|(_, path, _)| path.segments.last())
+                .map(|seg| seg.ident.to_string()),
+        );
+        let outer_lifetimes =
+            std::mem::replace(&mut self.outer_lifetimes, declared_lifetimes(&i.generics));
+        syn::visit_mut::visit_item_impl_mut(self, i);
+        self.scope.leave(outer);
+        self.impl_trait = impl_trait;
+        self.outer_lifetimes = outer_lifetimes;
+    }
+
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        let outer_lifetimes =
+            std::mem::replace(&mut self.outer_lifetimes, declared_lifetimes(&i.generics));
+        syn::visit_mut::visit_item_trait_mut(self, i);
+        self.scope.leave(outer);
+        self.outer_lifetimes = outer_lifetimes;
+    }
+
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+        let selected = self.method_scope != MethodScope::TraitDecls
+            && FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing());
+        if selected {
+            let enclosing = self.scope.enclosing().map(String::from);
+            self.fn_lifetime_elider(&i.attrs, &mut i.sig, enclosing.as_deref());
+            if let Some(impl_trait) = &self.impl_trait {
+                self.elided_impl_traits.push(impl_trait.clone());
+            }
+        }
+        syn::visit_mut::visit_impl_item_method_mut(self, i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        // a function nested in a method body sees neither the impl's type nor its lifetimes
+        let scope = self.scope.free_fn();
+        let outer = self.scope.enter(scope.clone(), &[]);
+        let impl_trait = self.impl_trait.take();
+        let outer_lifetimes = std::mem::take(&mut self.outer_lifetimes);
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, scope.as_deref()) {
+            false => (),
+            true => self.fn_lifetime_elider(&i.attrs, &mut i.sig, scope.as_deref()),
+        }
+        syn::visit_mut::visit_item_fn_mut(self, i);
+        self.scope.leave(outer);
+        self.impl_trait = impl_trait;
+        self.outer_lifetimes = outer_lifetimes;
+    }
+
+    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+        let selected = self.method_scope != MethodScope::Impls
+            && FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing());
+        if selected {
+            let enclosing = self.scope.enclosing().map(String::from);
+            self.fn_lifetime_elider(&i.attrs, &mut i.sig, enclosing.as_deref());
+        }
+        syn::visit_mut::visit_trait_item_method_mut(self, i);
+    }
+}
+
+impl FnLifetimeElider<'_> {
+    // the name `name_policy` gives the n-th surviving lifetime, `'lt{n}` (or the next free
+    // `'lt{k}`) if that's no name a lifetime can be declared with or it is `taken` already: by an
+    // earlier lifetime of the function, which would merge the two, or by the impl, which it would
+    // shadow
+    fn lifetime_name(&self, n: usize, taken: &[String]) -> String {
+        static LIFETIME: OnceLock<Regex> = OnceLock::new();
+        let name = (self.name_policy)(n);
+        let re = LIFETIME.get_or_init(|| Regex::new(r"^'[A-Za-z_][A-Za-z0-9_]*$").unwrap());
+        let free = |name: &String| !taken.contains(name) && !self.outer_lifetimes.contains(name);
+        if re.is_match(&name) && name != "'_" && name != "'static" && free(&name) {
+            return name;
+        }
+        let fallback = (n..).map(|k| format!("'lt{}", k)).find(free).unwrap();
+        warn!(
+            "cannot declare lifetime `{}`, naming it {} instead",
+            name, fallback
+        );
+        fallback
+    }
+
+    fn fn_lifetime_elider(
+        &mut self,
+        attrs: &[Attribute],
+        sig: &mut Signature,
+        enclosing: Option<&str>,
+    ) {
+        self.found = true;
+        if !self.scope.editable(attrs, sig, enclosing) {
+            return;
+        }
+        self.declared
+            .extend(sig.generics.lifetimes().map(|def| def.lifetime.to_string()));
+        // println!("original : {}", i.sig.clone().into_token_stream().to_string());
+        // `&'a T` in `impl<'a> Foo<'a>` is the impl's `'a`, eliding it would make it a new lifetime
+        let mut cannot_elide = self.outer_lifetimes.clone();
+        match &sig.generics.where_clause {
+            None => (),
+            Some(wc) => wc.predicates.iter().for_each(|wp| match wp {
+                WherePredicate::Lifetime(lt) => {
+                    cannot_elide.push(lt.lifetime.to_string());
+                    cannot_elide.push(lt.bounds.first().unwrap().to_string())
+                }
+                // `I: Iterator<Item = &'a u8>` pins `'a` just as much
+                WherePredicate::Type(pt) => {
+                    let mut get_lt = LtGetterElider {
+                        v: &mut cannot_elide,
+                    };
+                    get_lt.visit_predicate_type_mut(&mut pt.clone());
+                }
+                _ => (),
+            }),
+        }
+        // bounds declared inline (`'a: 'static`) constrain the lifetime just like a where clause
+        for def in sig.generics.lifetimes() {
+            if !def.bounds.is_empty() {
+                cannot_elide.push(def.lifetime.to_string());
+                cannot_elide.extend(def.bounds.iter().map(|bound| bound.to_string()));
+            }
+        }
+        // a default (`T = &'a i32`) is no place for an elided lifetime, it needs `'a` declared
+        for tp in sig.generics.type_params() {
+            if let Some(default) = &tp.default {
+                let mut get_lt = LtGetterElider {
+                    v: &mut cannot_elide,
+                };
+                get_lt.visit_type_mut(&mut default.clone());
+            }
+        }
+        let mut output_lts = vec![];
+        match sig.output.borrow_mut() {
+            ReturnType::Default => (),
+            ReturnType::Type(_, ty) => {
+                let mut get_lt = LtGetterElider { v: &mut output_lts };
+                get_lt.visit_type_mut(ty.clone().as_mut());
+            }
+        };
+        let mut get_fn_sugar_lt = FnSugarLtGetter {
+            v: &mut cannot_elide,
+        };
+        for fn_arg in sig.inputs.iter() {
+            get_fn_sugar_lt.visit_fn_arg_mut(&mut fn_arg.clone());
+        }
+        for gp in sig.generics.params.iter() {
+            get_fn_sugar_lt.visit_generic_param_mut(&mut gp.clone());
+        }
+        let mut get_impl_trait_lt = ImplTraitLtGetter {
+            v: &mut cannot_elide,
+        };
+        for fn_arg in sig.inputs.iter() {
+            get_impl_trait_lt.visit_fn_arg_mut(&mut fn_arg.clone());
+        }
+        let mut get_projection_lt = ProjectionLtGetter {
+            type_params: sig
+                .generics
+                .type_params()
+                .map(|tp| tp.ident.to_string())
+                .collect(),
+            v: &mut cannot_elide,
+        };
+        for fn_arg in sig.inputs.iter() {
+            get_projection_lt.visit_fn_arg_mut(&mut fn_arg.clone());
+        }
+        for gp in sig.generics.params.iter() {
+            get_projection_lt.visit_generic_param_mut(&mut gp.clone());
+        }
+        let mut invariant = vec![];
+        let mut get_invariant_lt = InvariantLtGetter { v: &mut invariant };
+        for fn_arg in sig.inputs.iter() {
+            get_invariant_lt.visit_fn_arg_mut(&mut fn_arg.clone());
+        }
+        for gp in sig.generics.params.iter() {
+            get_invariant_lt.visit_generic_param_mut(&mut gp.clone());
+        }
+        cannot_elide.extend(
+            invariant
+                .into_iter()
+                .filter(|lt| !self.force_elide.contains(lt)),
+        );
+        // pinning a deep lifetime keeps its shallower uses named too, one lifetime can't be both
+        if let Some(max_depth) = self.max_depth {
+            let mut get_deep_lt = DeepLtGetter {
+                max_depth,
+                types: 0,
+                v: &mut cannot_elide,
+            };
+            get_deep_lt.visit_signature_mut(&mut sig.clone());
+        }
+        let sole_input_lt = sole_input_lifetime(sig, &output_lts, &cannot_elide);
+        let pinned = cannot_elide.clone();
+        cannot_elide.extend(
+            output_lts
+                .iter()
+                .filter(|lt| Some(*lt) != sole_input_lt.as_ref())
+                .cloned(),
+        );
+
+        let gen = &mut sig.generics;
+        let inputs = &mut sig.inputs;
+        let mut has_receiver = false;
+        let mut map = HashMap::new();
+        let mut v = vec![];
+        inputs.iter_mut().for_each(|fn_arg| {
+            match fn_arg {
+                FnArg::Receiver(_) => has_receiver = true,
+                FnArg::Typed(_) => {
+                    let mut get_lt = LtGetterElider { v: &mut v };
+                    get_lt.visit_fn_arg_mut(fn_arg)
+                }
+            };
+        });
+        match has_receiver {
+            // only the lifetime of `&'a self` may go, whatever else is declared stays as it is
+            true => {
+                self.elide_receiver_lifetime(sig, &pinned, &v);
+                sig.generics.lifetimes().for_each(|lt| {
+                    let id = lt.lifetime.to_string();
+                    self.annotations_left = true;
+                    self.lifetime_map.insert(id.clone(), id);
+                })
+            }
+            false => {
+                // the output doesn't count as a second use of the sole input lifetime, rule 2
+                // gives its elided form that lifetime back
+                v.extend(
+                    output_lts
+                        .iter()
+                        .filter(|lt| Some(*lt) != sole_input_lt.as_ref())
+                        .cloned(),
+                );
+                gen.params.iter_mut().for_each(|gp| match gp {
+                    GenericParam::Lifetime(_) => (),
+                    gp => {
+                        let mut get_lt = LtGetterElider { v: &mut v };
+                        get_lt.visit_generic_param_mut(gp);
+                    }
+                });
+                v.iter().for_each(|lt| {
+                    match map.contains_key(lt) {
+                        true => map.insert(lt, *map.get(lt).unwrap() + 1),
+                        false => map.insert(lt, 1),
+                    };
+                });
+                let mut fn_arg_helper = FnLifetimeEliderArgHelper {
+                    cannot_elide: &cannot_elide,
+                    force_elide: self.force_elide,
+                    style: self.style,
+                    lt_count: &map,
+                };
+                inputs
+                    .iter_mut()
+                    .for_each(|fn_arg| fn_arg_helper.visit_fn_arg_mut(fn_arg));
+
+                match sig.output.borrow_mut() {
+                    ReturnType::Default => (),
+                    ReturnType::Type(_, ty) => {
+                        let mut type_helper = FnLifetimeEliderTypeHelper {
+                            cannot_elide: &cannot_elide,
+                            force_elide: self.force_elide,
+                            style: self.style,
+                            lt_count: &map,
+                        };
+                        type_helper.visit_type_mut(ty.as_mut());
+                    }
+                };
+                gen.params.iter_mut().for_each(|gp| match gp {
+                    GenericParam::Lifetime(_) => (),
+                    gp => {
+                        let mut type_helper = FnLifetimeEliderTypeHelper {
+                            cannot_elide: &cannot_elide,
+                            force_elide: self.force_elide,
+                            style: self.style,
+                            lt_count: &map,
+                        };
+                        type_helper.visit_generic_param_mut(gp);
+                    }
+                });
+                gen.params = gen
+                    .params
+                    .iter()
+                    .cloned()
+                    .filter(|g| match g {
+                        GenericParam::Lifetime(lt) => {
+                            let id = lt.lifetime.to_string();
+                            // a lifetime only the where clause uses is still declared by the fn
+                            if !map.contains_key(&id) {
+                                cannot_elide.contains(&id)
+                            } else {
+                                let result = (*map.get(&id).unwrap() > 1
+                                    && !self.force_elide.contains(&id))
+                                    || cannot_elide.contains(&id);
+                                debug!("lt: {}, result: {}", id, result);
+                                result
+                            }
+                        }
+                        _ => true,
+                    })
+                    .collect();
+                clear_empty_generics(gen);
+
+                let mut lt_count = 0;
+                let mut new_lts = HashMap::new();
+                gen.params.iter_mut().for_each(|gp| match gp {
+                    GenericParam::Lifetime(lt) => {
+                        let id = lt.lifetime.to_string();
+                        self.annotations_left = true;
+                        let chosen: Vec<String> = new_lts.values().cloned().collect();
+                        let name = self.lifetime_name(lt_count, &chosen);
+                        lt.lifetime = Lifetime::new(name.as_str(), Span::call_site());
+                        new_lts.insert(id, name);
+                        lt_count += 1
+                    }
+                    _ => (),
+                });
+                gen.params.iter_mut().for_each(|gp| match gp {
+                    GenericParam::Lifetime(_) => (),
+                    gp => {
+                        let mut change_lt = ChangeLtHelperElider {
+                            map: &new_lts,
+                            has_struct_lt: false,
+                        };
+                        change_lt.visit_generic_param_mut(gp);
+                        if change_lt.has_struct_lt {
+                            self.has_struct_lt = true;
+                        }
+                    }
+                });
+                match &mut gen.where_clause {
+                    None => (),
+                    Some(wc) => wc.predicates.iter_mut().for_each(|wp| match wp {
+                        WherePredicate::Lifetime(lt) => {
+                            let id = lt.lifetime.to_string();
+                            match new_lts.get(&id) {
+                                Some(new_lt) => {
+                                    lt.lifetime = Lifetime::new(new_lt.as_str(), Span::call_site())
+                                }
+                                None => (),
+                            };
+                            lt.bounds.iter_mut().for_each(|bound| {
+                                let id = bound.to_string();
+                                match new_lts.get(&id) {
+                                    Some(new_lt) => {
+                                        *bound = Lifetime::new(new_lt.as_str(), Span::call_site())
+                                    }
+                                    None => (),
+                                }
+                            })
+                        }
+                        WherePredicate::Type(pt) => {
+                            let mut rename_lt = LtRenamer { map: &new_lts };
+                            rename_lt.visit_predicate_type_mut(pt);
+                        }
+                        _ => (),
+                    }),
+                }
+                inputs.iter_mut().for_each(|fn_arg| match fn_arg {
+                    FnArg::Receiver(_) => (),
+                    FnArg::Typed(t) => {
+                        let mut change_lt = ChangeLtHelperElider {
+                            map: &new_lts,
+                            has_struct_lt: false,
+                        };
+                        debug!("debugging input: {:?}", t);
+                        change_lt.visit_pat_type_mut(t);
+                        if change_lt.has_struct_lt {
+                            self.has_struct_lt = true;
+                        }
+                    }
+                });
+                match sig.output.borrow_mut() {
+                    ReturnType::Default => (),
+                    ReturnType::Type(_, ty) => {
+                        let mut change_lt = ChangeLtHelperElider {
+                            map: &new_lts,
+                            has_struct_lt: false,
+                        };
+                        change_lt.visit_type_mut(ty.as_mut());
+                        if change_lt.has_struct_lt {
+                            self.has_struct_lt = true;
+                        }
+                    }
+                }
+                self.report_name_collisions(sig, new_lts.values());
+                self.lifetime_map.extend(new_lts);
+            }
+        }
+    }
+
+    // the renamed lifetimes are legal and distinct, but one named like a type or const parameter
+    // (`'t` next to `T`) reads as that parameter
+    fn report_name_collisions<'n>(&self, sig: &Signature, names: impl Iterator<Item = &'n String>) {
+        let params: Vec<String> = sig
+            .generics
+            .params
+            .iter()
+            .filter_map(|gp| match gp {
+                GenericParam::Type(tp) => Some(tp.ident.to_string()),
+                GenericParam::Const(cp) => Some(cp.ident.to_string()),
+                GenericParam::Lifetime(_) => None,
+            })
+            .collect();
+        for name in names {
+            let bare = name.trim_start_matches('\'');
+            if let Some(param) = params.iter().find(|param| param.eq_ignore_ascii_case(bare)) {
+                info!(
+                    "{} of {} is named like its parameter {}",
+                    name, sig.ident, param
+                );
+            }
+        }
+    }
+
+    // `&'a self` -> `&self` when nothing but the output names `'a`: by rule 3 the elided output
+    // lifetimes are the receiver's, so `-> &'a T` becomes `-> &T`. `pinned` are the lifetimes that
+    // can't be elided for reasons other than the output, `typed_lts` those of the other inputs
+    fn elide_receiver_lifetime(
+        &self,
+        sig: &mut Signature,
+        pinned: &[String],
+        typed_lts: &[String],
+    ) {
+        let lifetime = match sig.receiver() {
+            Some(FnArg::Receiver(Receiver {
+                reference: Some((_, Some(lt))),
+                ..
+            })) => lt.to_string(),
+            _ => return,
+        };
+        let mut generic_lts = vec![];
+        for gp in sig.generics.params.iter() {
+            if !matches!(gp, GenericParam::Lifetime(_)) {
+                let mut get_lt = LtGetterElider {
+                    v: &mut generic_lts,
+                };
+                get_lt.visit_generic_param_mut(&mut gp.clone());
+            }
+        }
+        // the output's fn sugar and projections don't take the receiver's lifetime when elided
+        let mut output_fixed = vec![];
+        if let ReturnType::Type(_, ty) = &sig.output {
+            let mut get_fn_sugar_lt = FnSugarLtGetter {
+                v: &mut output_fixed,
+            };
+            get_fn_sugar_lt.visit_type_mut(ty.clone().as_mut());
+            let mut get_projection_lt = ProjectionLtGetter {
+                type_params: sig
+                    .generics
+                    .type_params()
+                    .map(|tp| tp.ident.to_string())
+                    .collect(),
+                v: &mut output_fixed,
+            };
+            get_projection_lt.visit_type_mut(ty.clone().as_mut());
+        }
+        let declared = sig
+            .generics
+            .lifetimes()
+            .any(|def| def.lifetime.to_string() == lifetime);
+        let named_elsewhere = [pinned, typed_lts, &generic_lts, &output_fixed]
+            .iter()
+            .any(|lts| lts.contains(&lifetime));
+        if !declared || named_elsewhere {
+            return;
+        }
+
+        if let Some(FnArg::Receiver(Receiver {
+            reference: Some((_, lt)),
+            ..
+        })) = sig.inputs.first_mut()
+        {
+            *lt = match self.style {
+                ElisionStyle::Remove => None,
+                ElisionStyle::Anonymize => Some(Lifetime::new("'_", Span::call_site())),
+            };
+        }
+        if let ReturnType::Type(_, ty) = &mut sig.output {
+            let mut others = vec![];
+            let mut get_lt = LtGetterElider { v: &mut others };
+            get_lt.visit_type_mut(ty.clone().as_mut());
+            others.retain(|lt| *lt != lifetime);
+            let mut type_helper = FnLifetimeEliderTypeHelper {
+                cannot_elide: &others,
+                force_elide: &[],
+                style: self.style,
+                lt_count: &HashMap::new(),
+            };
+            type_helper.visit_type_mut(ty.as_mut());
+        }
+        sig.generics.params = sig
+            .generics
+            .params
+            .iter()
+            .filter(|gp| match gp {
+                GenericParam::Lifetime(def) => def.lifetime.to_string() != lifetime,
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        clear_empty_generics(&mut sig.generics);
+    }
+}
+
+/// Drop the `<>` tokens once the last generic parameter has been removed
+fn clear_empty_generics(gen: &mut Generics) {
+    if gen.params.is_empty() {
+        gen.lt_token = None;
+        gen.gt_token = None;
+    }
+}
+
+/// Names of the lifetime parameters `gen` declares, in declaration order
+fn declared_lifetimes(gen: &Generics) -> Vec<String> {
+    gen.lifetimes()
+        .map(|def| def.lifetime.to_string())
+        .collect()
+}
+
+pub struct ElideLifetimeResult {
+    #[allow(dead_code)]
+    pub success: bool,
+    /// Whether `fn_name` matched any function, nothing is elided otherwise
+    pub found: bool,
+    pub annotations_left: bool,
+    pub has_struct_lt: bool,
+    /// Original name of every lifetime that survived elision mapped to its new name (`'lt{n}`
+    /// unless `RepairConfig::name_policy` says otherwise), elided lifetimes have no entry
+    pub lifetime_map: HashMap<String, String>,
+}
+
+impl ElideLifetimeResult {
+    /// Lifetimes still declared after elision, by their new names
+    pub fn remaining_lifetimes(&self) -> Vec<&str> {
+        let mut remaining: Vec<&str> = self.lifetime_map.values().map(|lt| lt.as_str()).collect();
+        remaining.sort();
+        remaining
+    }
+
+    /// Log the lifetimes a successful repair could not elide
+    pub fn report_annotations_left(&self, label: &str) {
+        if self.annotations_left {
+            info!(
+                "[{}] compiles, but {} could not be elided",
+                label,
+                self.remaining_lifetimes().join(", ")
+            );
+        }
+    }
+}
+
+/**
+Elide lifetimes that are only used once in the inputs and not used in output(s)/bound(s), or
+that are the one lifetime written in the inputs and the only one in the output (`fn f<'a, T>(x: &'a
+T) -> &'a T`)
+
+When receiver (self) is in the input only the lifetime of `&'a self` is elided, if nothing but the
+output names it
+
+Elision rules are here: https://doc.rust-lang.org/nomicon/lifetime-elision.htm
+*/
+pub fn elide_lifetimes_annotations(
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<ElideLifetimeResult, RepairError> {
+    elide_lifetimes_annotations_with_config(new_file_name, fn_name, &RepairConfig::default(), None)
+}
+
+/**
+Elide again only in the functions a tracked bound repair (`repair_bounds_help_tracked`) modified,
+once the file compiles. An annotation the repair needed at one point can become elidible after
+later fixes, while the rest of the file is left as it was
+*/
+pub fn elide_modified_functions(
+    new_file_name: &str,
+    modified: &[String],
+    config: &RepairConfig,
+) -> Result<Vec<(String, ElideLifetimeResult)>, RepairError> {
+    modified
+        .iter()
+        .map(|selector| {
+            debug!("[{}] re-eliding {}", config.label, selector);
+            let result =
+                elide_lifetimes_annotations_with_config(new_file_name, selector, config, None)?;
+            Ok((selector.clone(), result))
+        })
+        .collect()
+}
+
+/**
+Same as `elide_lifetimes_annotations`, also eliding the lifetimes named in `config.force_elide`
+wherever they are used more than once. Lifetimes in bounds or in the output are still kept, those
+cannot be elided
+
+Forcing can make the function stop compiling, when `compile_cmd` is given the file is checked and
+restored to an elision without forcing if it fails
+*/
+pub fn elide_lifetimes_annotations_with_config(
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+    compile_cmd: Option<&mut Command>,
+) -> Result<ElideLifetimeResult, RepairError> {
+    if config.force_elide.is_empty() {
+        return elide_lifetimes(new_file_name, fn_name, &[], config);
+    }
+    let original = read_source(new_file_name)?;
+    let result = elide_lifetimes(new_file_name, fn_name, &config.force_elide, config)?;
+    let compiles = match compile_cmd {
+        None => true,
+        Some(cmd) => run_compile(cmd)?.status.success(),
+    };
+    if compiles {
+        return Ok(result);
+    }
+    warn!(
+        "[{}] forced elision of {:?} does not compile, eliding without it",
+        config.label, config.force_elide
+    );
+    write_source(new_file_name, &original, config)?;
+    elide_lifetimes(new_file_name, fn_name, &[], config)
+}
+
+fn elide_lifetimes(
+    new_file_name: &str,
+    fn_name: &str,
+    force_elide: &[String],
+    config: &RepairConfig,
+) -> Result<ElideLifetimeResult, RepairError> {
+    let (file_content, mut file) = read_target(new_file_name, fn_name)?;
+    let result = elide_in_ast(&mut file, fn_name, force_elide, config);
+    let file = print_source(&file);
+    write_source(
+        new_file_name,
+        &format_source_like(&file_content, &file),
+        config,
+    )?;
+    Ok(result)
+}
+
+/**
+`elide_lifetimes_annotations` on an already parsed `file`, so syn based tools holding one can chain
+it with their own transforms without printing and parsing in between
+*/
+pub fn elide_lifetimes_in_ast(file: &mut syn::File, fn_name: &str) -> ElideLifetimeResult {
+    elide_lifetimes_in_ast_with_config(file, fn_name, &RepairConfig::default())
+}
+
+/// `elide_lifetimes_in_ast` with the elision options of `config`, `force_elide` included: there is
+/// no compile to fall back on if forcing breaks the function
+pub fn elide_lifetimes_in_ast_with_config(
+    file: &mut syn::File,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> ElideLifetimeResult {
+    elide_in_ast(file, fn_name, &config.force_elide, config)
+}
+
+fn elide_in_ast(
+    file: &mut syn::File,
+    fn_name: &str,
+    force_elide: &[String],
+    config: &RepairConfig,
+) -> ElideLifetimeResult {
+    let default_names = |n: usize| format!("'lt{}", n);
+    let mut visit = FnLifetimeElider {
+        fn_name,
+        force_elide,
+        style: config.elision_style,
+        name_policy: match &config.name_policy {
+            Some(name_policy) => name_policy.as_ref(),
+            None => &default_names,
+        },
+        method_scope: config.method_scope,
+        max_depth: config.max_elision_depth,
+        scope: Scope::new(config.active_cfg.as_deref()),
+        impl_trait: None,
+        outer_lifetimes: vec![],
+        elided_impl_traits: vec![],
+        found: false,
+        declared: vec![],
+        annotations_left: false,
+        has_struct_lt: false,
+        lifetime_map: HashMap::new(),
+    };
+    visit.visit_file_mut(file);
+
+    // `Foo::bar` only selects the impl, elide the declaration in the trait along with it (a bare
+    // name has selected that already)
+    let selector = FnSelector::parse(fn_name);
+    if config.method_scope == MethodScope::Both && selector.qualifier.is_some() {
+        let mut traits = std::mem::take(&mut visit.elided_impl_traits);
+        traits.dedup();
+        for impl_trait in traits {
+            let trait_fn_name = format!("{}::{}", impl_trait, selector.name);
+            let mut trait_visit = FnLifetimeElider {
+                fn_name: &trait_fn_name,
+                method_scope: MethodScope::TraitDecls,
+                scope: Scope::new(config.active_cfg.as_deref()),
+                impl_trait: None,
+                outer_lifetimes: vec![],
+                elided_impl_traits: vec![],
+                found: false,
+                declared: vec![],
+                annotations_left: false,
+                has_struct_lt: false,
+                lifetime_map: HashMap::new(),
+                ..visit
+            };
+            trait_visit.visit_file_mut(file);
+            visit.has_struct_lt |= trait_visit.has_struct_lt;
+        }
+    }
+    ElideLifetimeResult {
+        success: true,
+        found: visit.found,
+        annotations_left: visit.annotations_left,
+        has_struct_lt: visit.has_struct_lt,
+        lifetime_map: visit.lifetime_map,
+    }
+}
+
+struct UnusedLifetimeRemover<'a> {
+    fn_name: &'a str,
+    scope: Scope<'a>,
+    removed: Vec<String>,
+}
+
+impl VisitMut for UnusedLifetimeRemover<'_> {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit_mut::visit_item_impl_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit_mut::visit_item_trait_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing()) {
+            false => (),
+            true => self.remove_unused_lifetimes(&mut i.sig, Some(&i.block)),
+        }
+        syn::visit_mut::visit_impl_item_method_mut(self, i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        let scope = self.scope.free_fn();
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, scope.as_deref()) {
+            false => (),
+            true => self.remove_unused_lifetimes(&mut i.sig, Some(&i.block)),
+        }
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit_mut::visit_item_fn_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, self.scope.enclosing()) {
+            false => (),
+            true => self.remove_unused_lifetimes(&mut i.sig, i.default.as_ref()),
+        }
+        syn::visit_mut::visit_trait_item_method_mut(self, i);
+    }
+}
+
+impl UnusedLifetimeRemover<'_> {
+    fn remove_unused_lifetimes(&mut self, sig: &mut Signature, block: Option<&Block>) {
+        // every declared lifetime occurs once in its own declaration, any further occurrence is a use
+        let mut v = vec![];
+        let mut get_lt = LtGetterElider { v: &mut v };
+        get_lt.visit_signature_mut(&mut sig.clone());
+        if let Some(block) = block {
+            get_lt.visit_block_mut(&mut block.clone());
+        }
+        let mut lt_count: HashMap<String, i32> = HashMap::new();
+        v.into_iter()
+            .for_each(|lt| *lt_count.entry(lt).or_insert(0) += 1);
+
+        let removed = &mut self.removed;
+        sig.generics.params = sig
+            .generics
+            .params
+            .iter()
+            .filter(|gp| match gp {
+                GenericParam::Lifetime(lt) => {
+                    let id = lt.lifetime.to_string();
+                    let used = lt_count.get(&id).is_some_and(|count| *count > 1);
+                    if !used {
+                        removed.push(id);
+                    }
+                    used
+                }
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        clear_empty_generics(&mut sig.generics);
+    }
+}
+
+/**
+Remove lifetime parameters of the function that are declared but never used in its signature or body
+
+Returns the names of the removed lifetimes, the file is only rewritten if any were removed
+*/
+pub fn remove_unused_lifetime_params(
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<Vec<String>, RepairError> {
+    remove_unused_lifetime_params_with_config(new_file_name, fn_name, &RepairConfig::default())
+}
+
+/// Like `remove_unused_lifetime_params`, logging under `config.label`
+pub fn remove_unused_lifetime_params_with_config(
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<Vec<String>, RepairError> {
+    let (file_content, mut file) = read_target(new_file_name, fn_name)?;
+    let mut visit = UnusedLifetimeRemover {
+        fn_name,
+        scope: Scope::new(None),
+        removed: vec![],
+    };
+    visit.visit_file_mut(&mut file);
+    if !visit.removed.is_empty() {
+        debug!(
+            "[{}] removed unused lifetimes: {:?}",
+            config.label, visit.removed
+        );
+        let file = print_source(&file);
+        write_source(
+            new_file_name,
+            &format_source_like(&file_content, &file),
+            config,
+        )?;
+    }
+    Ok(visit.removed)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+////////////////////////////////     CALLEE RENAMER    ////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////////////////////////
+pub struct RenameFn<'a> {
+    pub callee_name: &'a str,
+    pub callee_postfix: &'a str,
+}
+
+impl VisitMut for RenameFn<'_> {
+    fn visit_expr_method_call_mut(&mut self, i: &mut ExprMethodCall) {
+        let callee = i.clone().method.into_token_stream().to_string();
+        match callee.contains(self.callee_name) {
+            true => {
+                i.method = syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap()
+            }
+            false => syn::visit_mut::visit_expr_method_call_mut(self, i),
+        }
+    }
+
+    fn visit_expr_call_mut(&mut self, i: &mut ExprCall) {
+        let callee = i.func.as_ref().into_token_stream().to_string();
+        match callee.contains(self.callee_name) {
+            true => {
+                debug!("callee: {} matched", &callee);
+                *i.func.as_mut() =
+                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
+            }
+            false => {}
+        }
+        syn::visit_mut::visit_expr_call_mut(self, i);
+    }
+    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+        let callee = i.sig.ident.to_string();
+        match callee.contains(self.callee_name) {
+            true => {
+                i.sig.ident =
+                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
+            }
+            false => {}
+        }
+        syn::visit_mut::visit_impl_item_method_mut(self, i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        let callee = i.sig.ident.to_string();
+        match callee.contains(self.callee_name) {
+            true => {
+                i.sig.ident =
+                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
+            }
+            false => {}
+        }
+        syn::visit_mut::visit_item_fn_mut(self, i);
+    }
+    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+        let callee = i.sig.ident.to_string();
+        match callee.contains(self.callee_name) {
+            true => {
+                i.sig.ident =
+                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
+            }
+            false => {}
+        }
+        syn::visit_mut::visit_trait_item_method_mut(self, i);
+    }
+
+    // syn leaves macro arguments (`assert_eq!(foo____EXTRACT_THIS(), 1)`) as tokens, so callees
+    // in them are renamed ident by ident
+    fn visit_macro_mut(&mut self, i: &mut Macro) {
+        i.tokens = self.rename_in_tokens(std::mem::take(&mut i.tokens));
+        syn::visit_mut::visit_macro_mut(self, i);
+    }
+}
+
+impl RenameFn<'_> {
+    fn rename_in_tokens(&self, tokens: TokenStream) -> TokenStream {
+        tokens
+            .into_iter()
+            .map(|tt| match tt {
+                TokenTree::Ident(ident) if ident.to_string().contains(self.callee_name) => {
+                    let callee = ident.to_string().replace(self.callee_postfix, "");
+                    TokenTree::Ident(Ident::new(&callee, ident.span()))
+                }
+                TokenTree::Group(group) => {
+                    let mut renamed =
+                        Group::new(group.delimiter(), self.rename_in_tokens(group.stream()));
+                    renamed.set_span(group.span());
+                    TokenTree::Group(renamed)
+                }
+                tt => tt,
+            })
+            .collect()
+    }
+}
+
+pub fn callee_renamer(new_file_name: &str, fn_name: &str) -> Result<(), RepairError> {
+    callee_renamer_with_config(new_file_name, fn_name, &RepairConfig::default())
+}
+
+/// Like `callee_renamer`, writing the file through `write_source`
+pub fn callee_renamer_with_config(
+    new_file_name: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> Result<(), RepairError> {
+    let (file_content, mut file) = read_target(new_file_name, fn_name)?;
+    rename_callees_in_ast(&mut file, fn_name);
+    let file = print_source(&file);
+    write_source(
+        new_file_name,
+        &format_source_like(&file_content, &file),
+        config,
+    )
+}
+
+/// `callee_renamer` on an already parsed `file`
+pub fn rename_callees_in_ast(file: &mut syn::File, fn_name: &str) {
+    let mut visitor = RenameFn {
+        callee_name: FnSelector::parse(fn_name).name,
+        callee_postfix: "____EXTRACT_THIS",
+    };
+    visitor.visit_file_mut(file);
+}
+
+/**
+`callee_renamer` stripping `postfix` (`____EXTRACT_THIS`) followed by `elide_lifetimes_annotations`,
+on one parse of `file_name` written once. Separately each reprints and formats the whole file
+
+The callees are renamed first, so a definition that carried the postfix is selected by `fn_name`
+*/
+pub fn rename_and_elide(
+    file_name: &str,
+    fn_name: &str,
+    postfix: &str,
+) -> Result<ElideLifetimeResult, RepairError> {
+    rename_and_elide_with_config(file_name, fn_name, postfix, &RepairConfig::default(), None)
+}
+
+/// `rename_and_elide` under `config`, restoring and renaming without forced elision when
+/// `compile_cmd` is given and fails, like `elide_lifetimes_annotations_with_config`
+pub fn rename_and_elide_with_config(
+    file_name: &str,
+    fn_name: &str,
+    postfix: &str,
+    config: &RepairConfig,
+    compile_cmd: Option<&mut Command>,
+) -> Result<ElideLifetimeResult, RepairError> {
+    let (file_content, parsed) = read_target(file_name, fn_name)?;
+    let rename_and_elide = |force_elide: &[String]| {
+        let mut file = parsed.clone();
+        let mut renamer = RenameFn {
+            callee_name: FnSelector::parse(fn_name).name,
+            callee_postfix: postfix,
+        };
+        renamer.visit_file_mut(&mut file);
+        let result = elide_in_ast(&mut file, fn_name, force_elide, config);
+        let file = print_source(&file);
+        write_source(file_name, &format_source_like(&file_content, &file), config)?;
+        Ok(result)
+    };
+    let result = rename_and_elide(&config.force_elide)?;
+    if config.force_elide.is_empty() {
+        return Ok(result);
+    }
+    let compiles = match compile_cmd {
+        None => true,
+        Some(cmd) => run_compile(cmd)?.status.success(),
+    };
+    if compiles {
+        return Ok(result);
+    }
+    warn!(
+        "[{}] forced elision of {:?} does not compile, eliding without it",
+        config.label, config.force_elide
+    );
+    rename_and_elide(&[])
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+////////////////////////////////     PROJECT HELPERS    ////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////////////////////////
+/**
+ * One line of `cargo build/check --message-format=json`. `cargo clippy --message-format=json` uses
+ * the same envelope, its lints are ordinary diagnostics named by `code` (`clippy::...`). Lints are
+ * warnings that don't fail the build though, so the command has to deny the ones to repair
+ * (`cargo clippy --message-format=json -- -D clippy::needless_lifetimes`)
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CargoError {
+    pub message: Option<RustcError>,
+}
+
+/// What happened in one iteration of a project repair loop
+#[derive(Debug, Clone)]
+pub struct IterationTrace {
+    /// Rendered diagnostics the iteration tried to repair
+    pub diagnostics: Vec<String>,
+    /// How many of them a pass knows how to repair, falling towards 0 as the repair converges
+    pub repairable: usize,
+    /// Lines of `src_path` the iteration replaced (`-`) and their replacement (`+`), empty if
+    /// nothing changed
+    pub edit: String,
+    /// Whether the next compile reported fewer diagnostics, `None` if there was none
+    pub reduced_errors: Option<bool>,
+}
+
+pub fn repair_iteration_project(
+    compile_cmd: &mut Command,
+    src_path: &str,
+    process_errors: &dyn Fn(&RustcError) -> bool,
+    config: &RepairConfig,
+) -> RepairResult {
+    repair_iteration_project_with_trace(compile_cmd, src_path, process_errors, config, &mut vec![])
+}
+
+/// Like `repair_iteration_project`, also pushing an `IterationTrace` per iteration onto `trace`,
+/// one for each of the `repair_count` failed compiles
+pub fn repair_iteration_project_with_trace(
+    compile_cmd: &mut Command,
+    src_path: &str,
+    process_errors: &dyn Fn(&RustcError) -> bool,
+    config: &RepairConfig,
+    trace: &mut Vec<IterationTrace>,
+) -> RepairResult {
+    project_repair_loop(
+        compile_cmd,
+        src_path,
+        config,
+        trace,
+        &mut vec![],
+        &mut |errors, ignored, attempted| {
+            attempted.extend(codes_of(errors).map(str::to_string));
+            process_each(errors, ignored, process_errors, config)
+        },
+    )
+}
+
+/**
+Like `repair_iteration_project`, also pushing onto `ignored` every diagnostic an iteration parsed
+but didn't act on: those pointing at other files, those `process_errors` returned false for and
+those of codes out of `max_attempts_per_code`. A diagnostic reported by several compiles is pushed
+once for each
+*/
+pub fn repair_iteration_project_with_ignored(
+    compile_cmd: &mut Command,
+    src_path: &str,
+    process_errors: &dyn Fn(&RustcError) -> bool,
+    config: &RepairConfig,
+    ignored: &mut Vec<RustcError>,
+) -> RepairResult {
+    project_repair_loop(
+        compile_cmd,
+        src_path,
+        config,
+        &mut vec![],
+        ignored,
+        &mut |errors, ignored, attempted| {
+            attempted.extend(codes_of(errors).map(str::to_string));
+            process_each(errors, ignored, process_errors, config)
+        },
+    )
+}
+
+// hands every error to `process_errors`, pushing those it doesn't repair onto `ignored`
+fn process_each(
+    errors: &[RustcError],
+    ignored: &mut Vec<RustcError>,
+    process_errors: &dyn Fn(&RustcError) -> bool,
+    config: &RepairConfig,
+) -> bool {
+    let mut help = false;
+    for error in errors {
+        debug!("[{}] processing error: {}", config.label, &error.rendered);
+        match process_errors(error) {
+            true => help = true,
+            false => ignored.push(error.clone()),
+        }
+    }
+    help
+}
+
+/**
+ * Like `repair_iteration_project`, but each iteration only applies the first pass (in the order
+ * given) that manages to repair one of the current errors; lower priority passes wait until the
+ * errors of the higher priority ones are gone
+ */
+pub fn repair_iteration_project_prioritized(
+    compile_cmd: &mut Command,
+    src_path: &str,
+    passes: &[RepairPass],
+    config: &RepairConfig,
+) -> RepairResult {
+    project_repair_loop(
+        compile_cmd,
+        src_path,
+        config,
+        &mut vec![],
+        &mut vec![],
+        &mut |errors, _, attempted| apply_passes_counting(errors, passes, config, attempted),
+    )
+}
+
+// repairs the errors of an iteration, pushing those it doesn't act on onto the `Vec` and the codes
+// of those it hands to a pass into the set, returns whether any was repaired
+type RepairErrors<'a> =
+    dyn FnMut(&[RustcError], &mut Vec<RustcError>, &mut HashSet<String>) -> bool + 'a;
+
+fn project_repair_loop(
+    compile_cmd: &mut Command,
+    src_path: &str,
+    config: &RepairConfig,
+    trace: &mut Vec<IterationTrace>,
+    ignored: &mut Vec<RustcError>,
+    repair_errors: &mut RepairErrors,
+) -> RepairResult {
+    let mut count = 0;
+    let label = config.label.as_str();
+    let mut repair_result = RepairResult::failed();
+    let original = fs::read_to_string(src_path).ok();
+    // iterations that handed the errors of each code to the passes
+    let mut attempts: HashMap<String, usize> = HashMap::new();
+    let success = loop {
+        let started = Instant::now();
+        // the diagnostics are sorted out as they arrive, while a streamed compile still runs
+        let (mut errors, mut others) = (vec![], vec![]);
+        let compiled = ProjectCompile::start(compile_cmd, config).and_then(|mut compile| {
+            for message in compile.by_ref() {
+                match in_target(&message, src_path) {
+                    true => {
+                        debug!("[{}] message: {:?}", label, message);
+                        errors.push(message);
+                    }
+                    false => others.push(message),
+                }
+            }
+            compile.finish()
+        });
+        repair_result.compile_time += started.elapsed();
+        match compiled {
+            Ok(true) => {
+                if let Some(last) = trace.last_mut() {
+                    last.reduced_errors = Some(true);
+                }
+                info!("[{}] repair succeeded", label);
+                break true;
+            }
+            Ok(false) => (),
+            Err(e) => {
+                warn!("[{}] {}", label, e);
+                break false;
+            }
+        }
+        ignored.extend(others.iter().cloned());
+        // errors elsewhere on the first compile were there before any repair, with none in the
+        // target file there is nothing to repair and iterating would only recompile them
+        if count == 0 && errors.is_empty() {
+            let unrelated = unrelated_errors(others);
+            let checked = check_compiled(&unrelated, src_path);
+            if let (Some(error), Err(e)) = (unrelated.first(), checked) {
+                warn!(
+                    "[{}] {}, either the {} errors predate the repair or the command doesn't \
+                     compile it, first:\n{}",
+                    label,
+                    e,
+                    unrelated.len(),
+                    error.rendered
+                );
+                repair_result.unrelated_error = true;
+                break false;
+            }
+        }
+        count += 1;
+
+        if let Some(last) = trace.last_mut() {
+            last.reduced_errors = Some(errors.len() < last.diagnostics.len());
+        }
+        let before = fs::read_to_string(src_path).unwrap_or_default();
+        let last_failure = errors.last().map_or("", |error| error.rendered.as_str());
+        let (attempted, given_up) = attempted_errors(&errors, &attempts, config);
+        ignored.extend(given_up);
+        let mut attempted_codes = HashSet::new();
+        let helped = repair_errors(&attempted, ignored, &mut attempted_codes);
+        for code in attempted_codes {
+            *attempts.entry(code).or_insert(0) += 1;
+        }
+        let after = fs::read_to_string(src_path).unwrap_or_default();
+        trace.push(IterationTrace {
+            diagnostics: errors.iter().map(|error| error.rendered.clone()).collect(),
+            repairable: errors.iter().filter(|error| is_repairable(error)).count(),
+            edit: edit_summary(&before, &after),
+            reduced_errors: None,
+        });
+        if let Some(on_iteration) = &config.on_iteration {
+            on_iteration(trace.last().unwrap());
+        }
+        if !helped {
+            debug!("[{}] last failure:\n{}", label, last_failure);
+            break false;
+        }
+
+        match &config.snapshot_dir {
+            None => (),
+            Some(dir) => {
+                if let Err(e) = snapshot_iteration(dir, src_path, count) {
+                    warn!("[{}] cannot snapshot the iteration: {}", label, e);
+                }
+            }
+        }
+
+        if config.max_iterations == count {
+            debug!("[{}] last failure:\n{}", label, last_failure);
+            break false;
+        }
+    };
+
+    if config.print_stats {
+        info!("[{}] repair count: {}", label, count);
+        info!("[{}] status: {}", label, success);
+    }
+    let tests_failed = success && !passes_tests(config);
+    let success = success && !tests_failed;
+    let cleaned_up = match original {
+        Some(original) if tests_failed => {
+            warn!(
+                "[{}] tests failed after the repair, reverting {}",
+                label, src_path
+            );
+            write_source(src_path, &original, config)
+        }
+        Some(original) if !success && config.revert_if_not_converged => {
+            warn!("[{}] did not converge, reverting {}", label, src_path);
+            write_source(src_path, &original, config)
+        }
+        Some(original) => format_repaired(src_path, &original, config)
+            .and_then(|()| restore_markers(src_path, &original, config)),
+        None => Ok(()),
+    };
+    if let Err(e) = cleaned_up {
+        warn!("[{}] {}", label, e);
+    }
+
+    repair_result.success = success;
+    repair_result.repair_count = count;
+    repair_result
+}
+
+// runs `compile_cmd` to completion
+fn run_compile(compile_cmd: &mut Command) -> Result<Output, RepairError> {
+    compile_cmd
+        .output()
+        .map_err(|e| RepairError::command(compile_cmd, e))
+}
+
+// a copy of `cmd` with its program, args, env changes and working directory, `Command` isn't
+// `Clone`. An `env_clear` isn't copied, the copy starts from the inherited env
+fn copy_command(cmd: &Command) -> Command {
+    let mut copy = Command::new(cmd.get_program());
+    copy.args(cmd.get_args());
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => copy.env(key, value),
+            None => copy.env_remove(key),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        copy.current_dir(dir);
+    }
+    copy
+}
+
+/*
+The compile of a project repair loop, an iterator over the diagnostics it reports followed by
+`finish` for whether it succeeded. With `config.stream_diagnostics` cargo's stdout is read a json
+message per line as it prints them, so the loop handles each while the compile goes on; otherwise
+they come from the whole output once the compile is done
+*/
+struct ProjectCompile<'a> {
+    compile_cmd: &'a mut Command,
+    parser: &'a dyn DiagnosticParser,
+    // the diagnostics parsed and not yet handed out
+    parsed: std::vec::IntoIter<RustcError>,
+    compile: CompileState,
+}
+
+enum CompileState {
+    Done(ExitStatus),
+    Streaming(Child, Lines<BufReader<ChildStdout>>),
+}
+
+impl<'a> ProjectCompile<'a> {
+    fn start(compile_cmd: &'a mut Command, config: &'a RepairConfig) -> Result<Self, RepairError> {
+        let parser = diagnostic_parser(config);
+        if !config.stream_diagnostics {
+            let out = run_compile(compile_cmd)?;
+            // cargo give rustc error to stdout not stderr
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            return Ok(ProjectCompile {
+                compile_cmd,
+                parser,
+                parsed: parser.parse_cargo(&stdout).into_iter(),
+                compile: CompileState::Done(out.status),
+            });
+        }
+        // the stdio is set on a copy, the caller's command keeps its own
+        let mut child = copy_command(compile_cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| RepairError::command(compile_cmd, e))?;
+        let stdout = match child.stdout.take() {
+            Some(stdout) => stdout,
+            None => {
+                let e = io::Error::other("the compile's stdout is not piped");
+                return Err(RepairError::command(compile_cmd, e));
+            }
+        };
+        Ok(ProjectCompile {
+            compile_cmd,
+            parser,
+            parsed: vec![].into_iter(),
+            compile: CompileState::Streaming(child, BufReader::new(stdout).lines()),
+        })
+    }
+
+    // waits for the compile to exit, the diagnostics not read yet are dropped
+    fn finish(self) -> Result<bool, RepairError> {
+        match self.compile {
+            CompileState::Done(status) => Ok(status.success()),
+            CompileState::Streaming(mut child, lines) => {
+                // closing the pipe first, a compile blocked writing to it would never exit
+                drop(lines);
+                child
+                    .wait()
+                    .map(|status| status.success())
+                    .map_err(|e| RepairError::command(self.compile_cmd, e))
+            }
+        }
+    }
+}
+
+impl Iterator for ProjectCompile<'_> {
+    type Item = RustcError;
+
+    fn next(&mut self) -> Option<RustcError> {
+        loop {
+            if let Some(message) = self.parsed.next() {
+                return Some(message);
+            }
+            match &mut self.compile {
+                CompileState::Done(_) => return None,
+                CompileState::Streaming(_, lines) => {
+                    let line = lines.next()?.ok()?;
+                    self.parsed = self.parser.parse_cargo(&line).into_iter();
+                }
+            }
+        }
+    }
+}
+
+// splits off the errors whose code is out of attempts under `config.max_attempts_per_code`
+fn attempted_errors(
+    errors: &[RustcError],
+    attempts: &HashMap<String, usize>,
+    config: &RepairConfig,
+) -> (Vec<RustcError>, Vec<RustcError>) {
+    let max_attempts = match config.max_attempts_per_code {
+        None => return (errors.to_vec(), vec![]),
+        Some(max_attempts) => max_attempts,
+    };
+    let exhausted: HashSet<&str> = codes_of(errors)
+        .filter(|code| attempts.get(*code).is_some_and(|n| *n >= max_attempts))
+        .collect();
+    if !exhausted.is_empty() {
+        info!(
+            "[{}] gave up on {:?} after {} attempts",
+            config.label, exhausted, max_attempts
+        );
+    }
+    errors.iter().cloned().partition(|error| {
+        let code = error.code.as_ref().map(|code| code.code.as_str());
+        !code.is_some_and(|code| exhausted.contains(code))
+    })
+}
+
+// the codes of `errors`, those without one are left out
+fn codes_of(errors: &[RustcError]) -> impl Iterator<Item = &str> {
+    errors
+        .iter()
+        .filter_map(|error| error.code.as_ref())
+        .map(|code| code.code.as_str())
+}
+
+// codes of the errors the passes repair besides those rustc renders a suggestion for
+const REPAIRABLE_CODES: [&str; 3] = ["E0106", "E0621", "E0700"];
+
+// whether one of the passes knows how to repair the diagnostic
+fn is_repairable(error: &RustcError) -> bool {
+    let known_code = error
+        .code
+        .as_ref()
+        .is_some_and(|code| REPAIRABLE_CODES.contains(&code.code.as_str()));
+    known_code
+        || bounds_help_regex().is_match(&error.rendered)
+        || standard_help_regex().is_match(&error.rendered)
+}
+
+// the lines between the common prefix and suffix of `before` and `after`
+fn edit_summary(before: &str, after: &str) -> String {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+    let prefix = before
+        .iter()
+        .zip(after.iter())
+        .take_while(|(b, a)| b == a)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(b, a)| b == a)
+        .count();
+    let removed = before[prefix..before.len() - suffix]
+        .iter()
+        .map(|line| format!("-{}", line));
+    let added = after[prefix..after.len() - suffix]
+        .iter()
+        .map(|line| format!("+{}", line));
+    removed.chain(added).collect::<Vec<_>>().join("\n")
+}
+
+/// Lines a repair changed, split by whether `format_source` or the repair itself changed them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeAttribution {
+    /// Lines of the original file that formatting it alone changes, 1-based and end exclusive
+    pub formatting: Vec<Range<usize>>,
+    /// Lines of the repaired file that differ from the formatted original, 1-based and end
+    /// exclusive
+    pub repair: Vec<Range<usize>>,
+}
+
+impl ChangeAttribution {
+    /// Whether the diff of the repair mixes in reformatting of the original
+    pub fn reformatted(&self) -> bool {
+        !self.formatting.is_empty()
+    }
+}
+
+/**
+Split the diff between `original_file` and the `repaired_file` written from it into the part that
+comes from every write going through `format_source` and the part that is the actual repair, by
+formatting the original once and diffing against that
+*/
+pub fn attribute_changes(
+    original_file: &str,
+    repaired_file: &str,
+) -> Result<ChangeAttribution, RepairError> {
+    let original = read_source(original_file)?;
+    let repaired = read_source(repaired_file)?;
+    let formatted = format_source(&original);
+    let (formatting, _) = changed_lines(&original, &formatted);
+    let (_, repair) = changed_lines(&formatted, &repaired);
+    Ok(ChangeAttribution { formatting, repair })
+}
+
+/// How much a repair changed its file, to compare repair systems by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepairMetrics {
+    /// Tokens of the repaired file outside its longest common subsequence with the original
+    pub tokens_added: usize,
+    /// Tokens of the original outside that subsequence
+    pub tokens_removed: usize,
+    /// Lifetime parameters the functions declare beyond those they declared before, summed over
+    /// the functions
+    pub lifetimes_introduced: usize,
+    /// Lifetime parameters the functions no longer declare, elided ones mostly
+    pub lifetimes_removed: usize,
+    /// Lifetime bounds the signatures gained: `'a: 'b` inline or in the where clause counts one
+    /// per bound, as do `T: 'a` and `impl Trait + 'a`
+    pub bounds_added: usize,
+}
+
+/// Measure the changes between `original_file` and the `repaired_file` written from it
+pub fn repair_metrics(
+    original_file: &str,
+    repaired_file: &str,
+) -> Result<RepairMetrics, RepairError> {
+    let original = parse_file(original_file)?;
+    let repaired = parse_file(repaired_file)?;
+    Ok(source_metrics(&original, &repaired))
+}
+
+// `repair_metrics` of two sources, formatting doesn't count as the sources are compared by tokens
+fn source_metrics(original: &syn::File, repaired: &syn::File) -> RepairMetrics {
+    let original_tokens = source_tokens(original);
+    let repaired_tokens = source_tokens(repaired);
+    let before: Vec<&str> = original_tokens.iter().map(String::as_str).collect();
+    let after: Vec<&str> = repaired_tokens.iter().map(String::as_str).collect();
+    let mut metrics = RepairMetrics::default();
+    for op in line_diff(&before, &after) {
+        match op {
+            LineOp::Same => (),
+            LineOp::Removed(_) => metrics.tokens_removed += 1,
+            LineOp::Added(_) => metrics.tokens_added += 1,
+        }
+    }
+    let before = signature_shapes(original);
+    let after = signature_shapes(repaired);
+    for (selector, (lifetimes, bounds)) in after.iter() {
+        let (lifetimes_before, bounds_before) = before.get(selector).copied().unwrap_or_default();
+        metrics.lifetimes_introduced += lifetimes.saturating_sub(lifetimes_before);
+        metrics.lifetimes_removed += lifetimes_before.saturating_sub(*lifetimes);
+        metrics.bounds_added += bounds.saturating_sub(bounds_before);
+    }
+    metrics
+}
+
+// the tokens of `file`, delimiters of groups included
+fn source_tokens(file: &syn::File) -> Vec<String> {
+    fn flatten(tokens: TokenStream, out: &mut Vec<String>) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => {
+                    let delimiters = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    out.push(delimiters.0.to_string());
+                    flatten(group.stream(), out);
+                    out.push(delimiters.1.to_string());
+                }
+                token => out.push(token.to_string()),
+            }
+        }
+    }
+    let mut out = vec![];
+    // printed and parsed again for the `use<..>` syn can't hold
+    flatten(print_source(file).parse().unwrap(), &mut out);
+    out.retain(|token| !token.is_empty());
+    out
+}
+
+// declared lifetimes and lifetime bounds of the signature of every function, by selector
+fn signature_shapes(file: &syn::File) -> HashMap<String, (usize, usize)> {
+    let mut visit = SignatureShapes {
+        scope: Scope::new(None),
+        shapes: HashMap::new(),
+    };
+    visit.visit_file(file);
+    visit.shapes
+}
+
+struct SignatureShapes {
+    scope: Scope<'static>,
+    shapes: HashMap<String, (usize, usize)>,
+}
+
+impl SignatureShapes {
+    fn record(&mut self, sig: &Signature) {
+        let mut bounds = LifetimeBoundCounter { count: 0 };
+        bounds.visit_signature(sig);
+        let shape = self
+            .shapes
+            .entry(selector_of(&sig.ident, self.scope.enclosing()))
+            .or_default();
+        shape.0 += sig.generics.lifetimes().count();
+        shape.1 += bounds.count;
+    }
+}
+
+impl<'ast> Visit<'ast> for SignatureShapes {
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit::visit_item_impl(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit::visit_item_trait(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit::visit_item_mod(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        self.record(&i.sig);
+        syn::visit::visit_impl_item_method(self, i);
+    }
+
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        let outer = self.scope.enter(self.scope.free_fn(), &[]);
+        self.record(&i.sig);
+        syn::visit::visit_item_fn(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        self.record(&i.sig);
+        syn::visit::visit_trait_item_method(self, i);
+    }
+}
+
+struct LifetimeBoundCounter {
+    count: usize,
+}
+
+impl<'ast> Visit<'ast> for LifetimeBoundCounter {
+    fn visit_lifetime_def(&mut self, i: &'ast LifetimeDef) {
+        self.count += i.bounds.len();
+    }
+
+    fn visit_predicate_lifetime(&mut self, i: &'ast PredicateLifetime) {
+        self.count += i.bounds.len();
+    }
+
+    fn visit_type_param_bound(&mut self, i: &'ast TypeParamBound) {
+        match i {
+            TypeParamBound::Lifetime(_) => self.count += 1,
+            TypeParamBound::Trait(_) => syn::visit::visit_type_param_bound(self, i),
+        }
+    }
+}
+
+// line ranges (1-based, end exclusive) of `before` and of `after` that aren't part of their
+// longest common subsequence of lines
+fn changed_lines(before: &str, after: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+    let mut old_changed = vec![];
+    let mut new_changed = vec![];
+    for op in line_diff(&before, &after) {
+        match op {
+            LineOp::Same => (),
+            LineOp::Removed(line) => old_changed.push(line),
+            LineOp::Added(line) => new_changed.push(line),
+        }
+    }
+    (line_ranges(&old_changed), line_ranges(&new_changed))
+}
+
+enum LineOp {
+    Same,
+    /// 1-based line of `before`
+    Removed(usize),
+    /// 1-based line of `after`
+    Added(usize),
+}
+
+// walks `before` and `after` along their longest common subsequence of lines
+fn line_diff(before: &[&str], after: &[&str]) -> Vec<LineOp> {
+    let prefix = before
+        .iter()
+        .zip(after.iter())
+        .take_while(|(b, a)| b == a)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(b, a)| b == a)
+        .count();
+    let old = &before[prefix..before.len() - suffix];
+    let new = &after[prefix..after.len() - suffix];
+
+    // lcs[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let mut ops: Vec<LineOp> = (0..prefix).map(|_| LineOp::Same).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(LineOp::Same);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(LineOp::Removed(prefix + i + 1));
+            i += 1;
+        } else {
+            ops.push(LineOp::Added(prefix + j + 1));
+            j += 1;
+        }
+    }
+    ops.extend((0..suffix).map(|_| LineOp::Same));
+    ops
+}
+
+/// A changed region of a diff without context lines, ranges are 1-based and end exclusive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// Lines of the old text the hunk replaces, empty (`n..n`) for a pure insertion before line `n`
+    pub old_range: Range<usize>,
+    /// Lines of the new text that replace them, empty for a pure deletion
+    pub new_range: Range<usize>,
+    /// The removed lines prefixed with `-`, then the added ones prefixed with `+`
+    pub lines: Vec<String>,
+}
+
+/**
+Line diff of `before` and `after` (say the original source and the repaired source
+`repair_in_sandbox` returns) as hunks, so review comments can be attached to each changed region
+*/
+pub fn diff_hunks(before: &str, after: &str) -> Vec<DiffHunk> {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+    let mut hunks: Vec<DiffHunk> = vec![];
+    let (mut old_line, mut new_line) = (1, 1);
+    let mut in_hunk = false;
+    for op in line_diff(&before, &after) {
+        if let LineOp::Same = op {
+            old_line += 1;
+            new_line += 1;
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            hunks.push(DiffHunk {
+                old_range: old_line..old_line,
+                new_range: new_line..new_line,
+                lines: vec![],
+            });
+            in_hunk = true;
+        }
+        let hunk = hunks.last_mut().unwrap();
+        match op {
+            LineOp::Removed(line) => {
+                hunk.old_range.end += 1;
+                hunk.lines.push(format!("-{}", before[line - 1]));
+                old_line += 1;
+            }
+            LineOp::Added(line) => {
+                hunk.new_range.end += 1;
+                hunk.lines.push(format!("+{}", after[line - 1]));
+                new_line += 1;
+            }
+            LineOp::Same => (),
+        }
+    }
+    for hunk in hunks.iter_mut() {
+        // removed lines first, the walk may interleave them with the added ones
+        hunk.lines.sort_by_key(|line| line.starts_with('+'));
+    }
+    hunks
+}
+
+// groups sorted line numbers into ranges of consecutive lines
+fn line_ranges(lines: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for &line in lines {
+        match ranges.last_mut() {
+            Some(range) if range.end == line => range.end += 1,
+            _ => ranges.push(line..line + 1),
+        }
+    }
+    ranges
+}
+
+// whether the diagnostic has a span in `src_path`
+fn in_target(message: &RustcError, src_path: &str) -> bool {
+    message
+        .spans
+        .iter()
+        .any(|span| src_path.contains(&span.file_name))
+}
+
+/**
+Check the compiler reported on `file_name` when it reported on any file. Edits to a file the
+compile command doesn't read (a copy the manifest doesn't point to) never show up in its output,
+so a loop repairing it would only recompile the same errors
+*/
+pub fn check_compiled(diagnostics: &[RustcError], file_name: &str) -> Result<(), RepairError> {
+    let mut compiled: Vec<String> = diagnostics
+        .iter()
+        .flat_map(|message| message.spans.iter())
+        .map(|span| span.file_name.clone())
+        .collect();
+    let reported = diagnostics
+        .iter()
+        .any(|message| in_target(message, file_name));
+    if compiled.is_empty() || reported {
+        return Ok(());
+    }
+    compiled.sort();
+    compiled.dedup();
+    Err(RepairError::NotCompiled {
+        file: file_name.to_string(),
+        compiled,
+    })
+}
+
+/// The errors among diagnostics without a span in the target file that have a span elsewhere
+fn unrelated_errors(others: Vec<RustcError>) -> Vec<RustcError> {
+    others
+        .into_iter()
+        .filter(|message| message.level == "error" && !message.spans.is_empty())
+        .collect()
+}
+
+/// Write `source` to `path`, unless `config.on_write` decides otherwise
+pub fn write_source(path: &str, source: &str, config: &RepairConfig) -> Result<(), RepairError> {
+    let restored;
+    let source = match &config.preserve_markers {
+        None => source,
+        Some(marker) => {
+            let previous = fs::read_to_string(path).unwrap_or_default();
+            restored = reattach_markers(source, &marker_comments(&previous, marker));
+            restored.as_str()
+        }
+    };
+    write_through_hook(path, source, config)
+}
+
+// writes `source` to `path` as `config.on_write` decides, without touching the marker comments
+fn write_through_hook(path: &str, source: &str, config: &RepairConfig) -> Result<(), RepairError> {
+    let decision = match &config.on_write {
+        None => WriteDecision::Proceed,
+        Some(on_write) => on_write(Path::new(path), source),
+    };
+    match decision {
+        WriteDecision::Proceed => write_file(path, source),
+        WriteDecision::Skip => {
+            debug!("[{}] skipped write to {}", config.label, path);
+            Ok(())
+        }
+        WriteDecision::Redirect(target) => {
+            debug!(
+                "[{}] redirected write to {} into {:?}",
+                config.label, path, target
+            );
+            fs::write(&target, source).map_err(|e| RepairError::io(&target.to_string_lossy(), e))
+        }
+    }
+}
+
+// formats `path` once a repair loop is done with it, if the loop changed it: the passes only
+// format the items they edit (see `print_edited`)
+fn format_repaired(path: &str, original: &str, config: &RepairConfig) -> Result<(), RepairError> {
+    let repaired = read_source(path)?;
+    if repaired != original {
+        write_source(path, &format_source_like(original, &repaired), config)?;
+    }
+    Ok(())
+}
+
+// runs `config.test_command` on code a repair got to compile, returns whether it passed. Without a
+// command there is nothing to fail
+fn passes_tests(config: &RepairConfig) -> bool {
+    let (program, args) = match config.test_command.as_deref() {
+        None | Some([]) => return true,
+        Some([program, args @ ..]) => (program, args),
+    };
+    match Command::new(program).args(args).output() {
+        Ok(out) => {
+            if !out.status.success() {
+                debug!(
+                    "[{}] test command failed:\n{}{}",
+                    config.label,
+                    String::from_utf8_lossy(&out.stdout),
+                    String::from_utf8_lossy(&out.stderr)
+                );
+            }
+            out.status.success()
+        }
+        Err(e) => {
+            warn!(
+                "[{}] cannot run test command `{}`: {}",
+                config.label, program, e
+            );
+            false
+        }
+    }
+}
+
+// puts the marker comments of `original` back into `path` once the passes of a repair are done
+// with it, passes without a config (the bound-first repairers') write without `write_source`
+fn restore_markers(path: &str, original: &str, config: &RepairConfig) -> Result<(), RepairError> {
+    let marker = match &config.preserve_markers {
+        None => return Ok(()),
+        Some(marker) => marker,
+    };
+    let current = read_source(path)?;
+    let restored = reattach_markers(&current, &marker_comments(original, marker));
+    if restored != current {
+        write_source(path, &restored, config)?;
+    }
+    Ok(())
+}
+
+/// Copy the current state of `src_path` to `{dir}/iter_{n}.rs` so a repair can be stepped through
+fn snapshot_iteration(dir: &Path, src_path: &str, iteration: i32) -> Result<(), RepairError> {
+    let snapshot = dir.join(format!("iter_{}.rs", iteration));
+    fs::create_dir_all(dir)
+        .and_then(|()| fs::copy(src_path, &snapshot))
+        .map_err(|e| RepairError::io(&snapshot.to_string_lossy(), e))?;
+    Ok(())
+}
+
+/**
+The source files of the crate at `manifest_path` that define a function `fn_name` selects, to pass
+as `src_path` to `repair_project` when only the name is known. More than one means the name is
+ambiguous across the crate, qualify it or pick one
+
+Every `.rs` file under the manifest's directory is parsed except those in `target` and hidden
+directories, files that don't parse are skipped
+*/
+pub fn find_function_sources(manifest_path: &str, fn_name: &str) -> Vec<String> {
+    let root = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let mut sources = vec![];
+    rust_sources(root, &mut sources);
+    sources.sort();
+    sources
+        .into_iter()
+        .filter_map(|path| {
+            let path = path.to_string_lossy().to_string();
+            let file = parse_file(&path)
+                .map_err(|e| debug!("skipping {}: {}", path, e))
+                .ok()?;
+            let mut visit = FnFinder::new(fn_name);
+            visit.visit_file(&file);
+            (!visit.found.is_empty()).then_some(path)
+        })
+        .collect()
+}
+
+// collects the `.rs` files under `dir`, skipping build output and hidden directories
+fn rust_sources(dir: &Path, sources: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("cannot read {:?}: {}", dir, e);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() && name != "target" && !name.starts_with('.') {
+            rust_sources(&path, sources);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            sources.push(path);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+////////////////////////////////   EXPLAINING REPAIRS   ////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////////////////////////
+/**
+Describe the elisions the default config would make to `fn_name` and the repairs the rustc json
+`diagnostics` of `file` would lead to, one sentence each, without writing anything
+
+Every decision comes from the same visitors the repairs use, run on an in-memory copy of the file
+*/
+pub fn explain(file: &str, fn_name: &str, diagnostics: &str) -> Result<Vec<String>, RepairError> {
+    let (_, parsed) = read_target(file, fn_name)?;
+    let mut explanations = vec![];
+
+    let force_elide = RepairConfig::default().force_elide;
+    let default_names = |n: usize| format!("'lt{}", n);
+    let mut elider = FnLifetimeElider {
+        fn_name,
+        force_elide: &force_elide,
+        style: ElisionStyle::Remove,
+        name_policy: &default_names,
+        method_scope: MethodScope::Both,
+        max_depth: None,
+        scope: Scope::new(None),
+        impl_trait: None,
+        outer_lifetimes: vec![],
+        elided_impl_traits: vec![],
+        found: false,
+        declared: vec![],
+        annotations_left: false,
+        has_struct_lt: false,
+        lifetime_map: HashMap::new(),
+    };
+    elider.visit_file_mut(&mut parsed.clone());
+    for lt in elider.declared.iter() {
+        explanations.push(match elider.lifetime_map.get(lt) {
+            None => format!(
+                "would elide `{}` from fn {} because it's used once and not in the output",
+                lt, fn_name
+            ),
+            Some(kept) if kept == lt => format!(
+                "would keep `{}` on fn {} because lifetimes of methods with a receiver are never \
+                 elided",
+                lt, fn_name
+            ),
+            Some(kept) => format!(
+                "would keep `{}` on fn {} as `{}` because it's used more than once, in the output \
+                 or in a position that can't be elided",
+                lt, fn_name, kept
+            ),
+        });
+    }
+
+    let location = Regex::new(r"--> .+:(?P<line>\d+):\d+").unwrap();
+    let bounds_help = bounds_help_regex();
+    let standard_help = standard_help_regex();
+    let deserializer = serde_json::Deserializer::from_str(diagnostics);
+    for item in deserializer.into_iter::<RustcError>() {
+        let rendered = match item {
+            Ok(i) => i.rendered,
+            Err(_) => diagnostics.to_string(),
+        };
+        let line = location
+            .captures(&rendered)
+            .map_or("?".to_string(), |captured| captured["line"].to_string());
+        for captured in bounds_help.captures_iter(&rendered) {
+            let mut bounder = FnLifetimeBounder {
+                fn_name,
+                scope: Scope::new(None),
+                lifetime: &captured["constraint_lhs"],
+                bound: &captured["constraint_rhs"],
+                success: false,
+                unified: false,
+                present: false,
+                modified: vec![],
+            };
+            bounder.visit_file_mut(&mut parsed.clone());
+            let bound = format!("{}: {}", bounder.lifetime, bounder.bound);
+            explanations.push(match (bounder.success, bounder.unified) {
+                (false, _) => format!(
+                    "would skip bound `{}` suggested at line {} because fn {} was not found",
+                    bound, line, fn_name
+                ),
+                (true, false) if bounder.present => format!(
+                    "would skip bound `{}` suggested at line {} because fn {} already has it",
+                    bound, line, fn_name
+                ),
+                (true, false) => format!(
+                    "would add bound `{}` to fn {} because rustc suggested it at line {}",
+                    bound, fn_name, line
+                ),
+                (true, true) => format!(
+                    "would unify `{}` into `{}` in fn {} because rustc suggested `{}` at line {} \
+                     and the reverse bound is already there",
+                    bounder.bound, bounder.lifetime, fn_name, bound, line
+                ),
+            });
+        }
+        for captured in standard_help.captures_iter(&rendered) {
+            let replacements = suggestion_lines(&captured["block"]);
+            let placeholder = replacements
+                .iter()
+                .any(|(_, replacement)| replacement.contains("&'lifetime"));
+            for (line_number, replacement) in replacements {
+                explanations.push(match placeholder {
+                    true => format!(
+                        "would skip the suggestion for line {} because it uses the `'lifetime` \
+                         placeholder",
+                        line_number
+                    ),
+                    false => format!(
+                        "would replace line {} with `{}` because rustc suggested it",
+                        line_number,
+                        replacement.trim()
+                    ),
+                });
+            }
+        }
+    }
+    Ok(explanations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::{Arc, Mutex, Once};
+
+    // a file holding `source` in the temp dir, named after the test so tests running in parallel
+    // don't share one, removed again when dropped along with the binary a `compile_file` of it
+    // leaves in the working directory
+    struct TempSource(String);
+
+    impl TempSource {
+        fn new(name: &str, source: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "rem-repairer-test-{}-{}.rs",
+                std::process::id(),
+                name
+            ));
+            fs::write(&path, source).unwrap();
+            TempSource(path.to_string_lossy().into_owned())
+        }
+
+        fn read(&self) -> String {
+            fs::read_to_string(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempSource {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+            if let Some(binary) = Path::new(&self.0).file_stem() {
+                let _ = fs::remove_file(binary);
+            }
+        }
+    }
+
+    // every log line of the tests, a logger can only be installed once per process
+    static LOGGED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    struct CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            LOGGED.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs() {
+        static INSTALLED: Once = Once::new();
+        INSTALLED.call_once(|| {
+            log::set_logger(&CaptureLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    // a directory in the temp dir named after the test, removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            TempDir(std::env::temp_dir().join(format!(
+                "rem-repairer-test-{}-{}",
+                std::process::id(),
+                name
+            )))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // a cargo json line reporting an error of `code` in `file`
+    fn cargo_error(file: &str, code: &str) -> String {
+        format!(
+            r#"{{"reason":"compiler-message","message":{{"rendered":"error[{code}]: scripted","spans":[{{"file_name":"{file}","byte_start":0,"byte_end":0}}],"level":"error","code":{{"code":"{code}"}}}}}}"#,
+            code = code,
+            file = file
+        )
+    }
+
+    // a compile command that keeps reporting an E0106 in `file` until `repairs` lines of it read
+    // `// repaired`
+    fn scripted_compile(file: &str, repairs: usize) -> Command {
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!(
+                r#"[ "$(grep -c '// repaired' "$0")" -ge {} ] && exit 0; echo '{}'; exit 1"#,
+                repairs,
+                cargo_error(file, "E0106")
+            ),
+            file,
+        ]);
+        compile
+    }
+
+    // the repair `scripted_compile` waits for
+    fn mark_repaired(file: &str) -> bool {
+        let source = fs::read_to_string(file).unwrap();
+        fs::write(file, source + "// repaired\n").unwrap();
+        true
+    }
+
+    fn e0700(lifetime: &str) -> String {
+        format!(
+            r#"{{"rendered":"error[E0700]: hidden type for `impl Sized` captures the lifetime `{}` as defined here","spans":[],"code":{{"code":"E0700"}}}}"#,
+            lifetime
+        )
+    }
+
+    #[test]
+    fn precise_capture_round_trips_past_literals_and_comments() {
+        let source = r##"
+            // use<T> in a comment /* and a nested use<'a> */
+            fn f<'a>(x: &'a i32) -> impl Sized + use<'a> {
+                let s = "use<'a>";
+                let r = r#"use<"#;
+                let c = '<';
+                let u = r#use::<i32>;
+                x
+            }
+        "##;
+        let printed = print_source(&try_parse_source(source).unwrap());
+        assert!(printed.contains("impl Sized + use < 'a >"), "{}", printed);
+        assert!(printed.contains(r#""use<'a>""#), "{}", printed);
+        assert!(printed.contains(r##"r#"use<"#"##), "{}", printed);
+        assert!(printed.contains("r#use :: < i32 >"), "{}", printed);
+        assert!(!printed.contains(PRECISE_CAPTURE), "{}", printed);
+    }
+
+    #[test]
+    fn hidden_capture_is_bounded_before_2024() {
+        let file = TempSource::new("capture_2021", "fn h<'a>(x: &'a i32) -> impl Sized { x }\n");
+        let config = RepairConfig::builder().edition(Edition::E2021).build();
+        let repaired =
+            repair_hidden_lifetime_capture_with_config(&e0700("'a"), &file.0, "h", &config);
+        assert!(repaired.unwrap());
+        let source = file.read();
+        assert!(source.contains("-> impl Sized + 'a"), "{}", source);
+    }
+
+    #[test]
+    fn hidden_capture_is_left_to_the_implicit_capture_of_2024() {
+        let original = "fn h<'a>(x: &'a i32) -> impl Sized { x }\n";
+        let file = TempSource::new("capture_2024", original);
+        let config = RepairConfig::builder().edition(Edition::E2024).build();
+        let repaired =
+            repair_hidden_lifetime_capture_with_config(&e0700("'a"), &file.0, "h", &config);
+        assert!(!repaired.unwrap());
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn hidden_capture_joins_the_use_clause_on_every_edition() {
+        for edition in [Edition::E2021, Edition::E2024] {
+            let file = TempSource::new(
+                &format!("capture_use_{}", edition.as_str()),
+                "fn g<'a, 'b>(x: &'a i32, y: &'b i32) -> impl Sized + use<'b> { x }\n",
+            );
+            let config = RepairConfig::builder().edition(edition).build();
+            let repaired =
+                repair_hidden_lifetime_capture_with_config(&e0700("'a"), &file.0, "g", &config);
+            assert!(repaired.unwrap());
+            let source = file.read();
+            assert!(source.contains("-> impl Sized + use<'b, 'a>"), "{}", source);
+        }
+    }
+
+    #[test]
+    fn qualified_name_selects_the_method_of_one_impl() {
+        let file = TempSource::new(
+            "qualified_name",
+            "struct Foo;
+struct Bar;
+impl Foo {
+    fn bar_extracted<'a>(x: &'a i32) -> i32 {
+        *x
+    }
+}
+impl Bar {
+    fn bar_extracted<'a>(x: &'a i32) -> i32 {
+        *x
+    }
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "Foo::bar_extracted").unwrap();
+        assert!(result.found);
+        let source = file.read();
+        let (foo, bar) = source.split_at(source.find("impl Bar").unwrap());
+        assert!(
+            foo.contains("fn bar_extracted(x: &i32) -> i32"),
+            "{}",
+            source
+        );
+        assert!(
+            bar.contains("fn bar_extracted<'a>(x: &'a i32) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn snapshot_is_taken_of_every_repair() {
+        let file = TempSource::new("snapshots", "fn main() {}\n");
+        let dir = TempDir::new("snapshots");
+        let config = RepairConfig::builder().snapshot_dir(&dir.0).build();
+        let result = repair_iteration_project(
+            &mut scripted_compile(&file.0, 3),
+            &file.0,
+            &|_| mark_repaired(&file.0),
+            &config,
+        );
+        assert!(result.success);
+        assert_eq!(result.repair_count, 3);
+        let snapshots = fs::read_dir(&dir.0).unwrap().count();
+        assert_eq!(snapshots, 3);
+        let last = fs::read_to_string(dir.0.join("iter_3.rs")).unwrap();
+        assert_eq!(last.matches("// repaired").count(), 3);
+    }
+
+    #[test]
+    fn struct_lifetime_argument_is_elided_to_the_placeholder() {
+        let file = TempSource::new(
+            "struct_lifetime",
+            "struct Foo<'a>(&'a i32);
+fn bar_extracted<'a>(x: Foo<'a>) -> i32 {
+    *x.0
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.found);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(x: Foo<'_>) -> i32"),
+            "{}",
+            source
+        );
+        assert!(source.contains("struct Foo<'a>(&'a i32);"), "{}", source);
+    }
+
+    #[test]
+    fn unused_lifetime_param_is_removed() {
+        let file = TempSource::new(
+            "unused_lifetime",
+            "fn bar_extracted<'unused>(x: i32) -> i32 {
+    x
+}
+fn kept<'unused>(x: &'unused i32) -> &'unused i32 {
+    x
+}
+",
+        );
+        let removed = remove_unused_lifetime_params(&file.0, "bar_extracted").unwrap();
+        assert_eq!(removed, ["'unused"]);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(x: i32) -> i32"),
+            "{}",
+            source
+        );
+        assert!(
+            source.contains("fn kept<'unused>(x: &'unused i32)"),
+            "{}",
+            source
+        );
+        assert!(remove_unused_lifetime_params(&file.0, "kept")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn log_lines_carry_the_label() {
+        capture_logs();
+        let file = TempSource::new("label", "fn main() {}\n");
+        let config = RepairConfig::builder()
+            .label("label-105")
+            .print_stats(true)
+            .build();
+        let result = repair_iteration_project(
+            &mut scripted_compile(&file.0, 1),
+            &file.0,
+            &|_| mark_repaired(&file.0),
+            &config,
+        );
+        assert!(result.success);
+        let logged = LOGGED.lock().unwrap();
+        for line in ["[label-105] repair count: 1", "[label-105] status: true"] {
+            assert!(logged.iter().any(|logged| logged == line), "{:?}", logged);
+        }
+    }
+
+    #[test]
+    fn const_generic_array_length_survives_elision() {
+        let file = TempSource::new(
+            "const_array",
+            "fn bar_extracted<'a, const N: usize>(x: &'a i32) -> [&'a i32; N] {
+    [x; N]
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.found);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<const N: usize>(x: &i32) -> [&i32; N]"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn loop_stops_at_max_iterations_and_reverts() {
+        let original = "fn main() {}\n";
+        let file = TempSource::new("max_iterations", original);
+        let config = RepairConfig::builder()
+            .max_iterations(2)
+            .revert_if_not_converged(true)
+            .build();
+        let result = repair_iteration_project(
+            &mut scripted_compile(&file.0, 3),
+            &file.0,
+            &|_| mark_repaired(&file.0),
+            &config,
+        );
+        assert!(!result.success);
+        assert_eq!(result.repair_count, 2);
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn misspelled_name_is_not_found() {
+        let original = "fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+";
+        let file = TempSource::new("not_found", original);
+        assert_eq!(
+            lookup_function(&file.0, "bar_extractd").unwrap(),
+            FnLookup::NotFound
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extractd").unwrap();
+        assert!(!result.found);
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn function_of_a_macro_invocation_is_reported_inside_macro() {
+        let file = TempSource::new(
+            "inside_macro",
+            "macro_rules! define {
+    ($($item:item)*) => { $($item)* };
+}
+define! {
+    fn bar_extracted<'a>(x: &'a i32) -> i32 {
+        *x
+    }
+}
+",
+        );
+        assert_eq!(
+            lookup_function(&file.0, "bar_extracted").unwrap(),
+            FnLookup::InsideMacro
+        );
+        assert_eq!(lookup_function(&file.0, "baz").unwrap(), FnLookup::NotFound);
+    }
+
+    #[test]
+    fn lifetime_map_names_every_surviving_lifetime() {
+        let file = TempSource::new(
+            "lifetime_map",
+            "fn bar_extracted<'a, 'b, 'c>(x: &'a i32, y: &'b i32, z: &'c i32) -> (&'a i32, &'b i32) {
+    (x, y)
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let expected: HashMap<String, String> = [("'a", "'lt0"), ("'b", "'lt1")]
+            .iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect();
+        assert_eq!(result.lifetime_map, expected);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, 'lt1>(x: &'lt0 i32, y: &'lt1 i32, z: &i32)"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn eliding_the_sole_lifetime_leaves_no_brackets() {
+        let file = TempSource::new(
+            "no_brackets",
+            "fn bar_extracted<'a>(x: &'a i32) {
+    println!(\"{}\", x);
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(source.contains("fn bar_extracted(x: &i32)"), "{}", source);
+        assert!(!source.contains("<>"), "{}", source);
+    }
+
+    #[test]
+    fn prioritized_passes_skip_the_repair_a_later_one_undoes() {
+        // the bound repair rewrites the signature, dropping a lifetime repaired before it
+        fn repair_bound(file: &str) -> bool {
+            let source = fs::read_to_string(file)
+                .unwrap()
+                .replace("// lifetime\n", "");
+            fs::write(file, source + "// bound\n").unwrap();
+            true
+        }
+        fn repair_lifetime(file: &str) -> bool {
+            let source = fs::read_to_string(file).unwrap();
+            fs::write(file, source + "// lifetime\n").unwrap();
+            true
+        }
+        fn compile(file: &str) -> Command {
+            let mut compile = Command::new("sh");
+            compile.args([
+                "-c",
+                &format!(
+                    r#"grep -q '// lifetime' "$0" || echo '{}'
+                       grep -q '// bound' "$0" || echo '{}'
+                       grep -q '// lifetime' "$0" && grep -q '// bound' "$0""#,
+                    cargo_error(file, "E0106"),
+                    cargo_error(file, "E0309")
+                ),
+                file,
+            ]);
+            compile
+        }
+        let is_code =
+            |error: &RustcError, code: &str| error.code.as_ref().is_some_and(|c| c.code == code);
+        let config = RepairConfig::default();
+
+        let file = TempSource::new("unprioritized", "fn main() {}\n");
+        let repairs = Cell::new(0);
+        let result = repair_iteration_project(
+            &mut compile(&file.0),
+            &file.0,
+            &|error| {
+                repairs.set(repairs.get() + 1);
+                match is_code(error, "E0309") {
+                    true => repair_bound(&file.0),
+                    false => repair_lifetime(&file.0),
+                }
+            },
+            &config,
+        );
+        assert!(result.success);
+        assert_eq!(repairs.get(), 3);
+
+        let file = TempSource::new("prioritized", "fn main() {}\n");
+        let repairs = Cell::new(0);
+        let bound = |_: &RustcError| {
+            repairs.set(repairs.get() + 1);
+            Ok(repair_bound(&file.0))
+        };
+        let lifetime = |_: &RustcError| {
+            repairs.set(repairs.get() + 1);
+            Ok(repair_lifetime(&file.0))
+        };
+        let passes = [
+            RepairPass {
+                name: "bound",
+                matches: &|error| is_code(error, "E0309"),
+                repair: &bound,
+            },
+            RepairPass {
+                name: "lifetime",
+                matches: &|error| is_code(error, "E0106"),
+                repair: &lifetime,
+            },
+        ];
+        let result =
+            repair_iteration_project_prioritized(&mut compile(&file.0), &file.0, &passes, &config);
+        assert!(result.success);
+        assert_eq!(result.repair_count, 2);
+        assert_eq!(repairs.get(), 2);
+    }
+
+    // a diagnostic rendered as `rendered`, without spans or code
+    fn diagnostic(rendered: &str) -> RustcError {
+        RustcError {
+            rendered: rendered.to_string(),
+            spans: vec![],
+            level: "error".to_string(),
+            code: None,
+        }
+    }
+
+    #[test]
+    fn bound_is_added_to_the_function_of_a_nested_module() {
+        let file = TempSource::new(
+            "nested_bound",
+            "mod inner {
+    pub fn bar_extracted<'a, 'b>(p: &'a mut &'b i32, x: &'a i32) {
+        *p = x;
+    }
+}
+",
+        );
+        let error = diagnostic(
+            "error: lifetime may not live long enough\n  = help: consider adding the following bound: `'a: 'b`",
+        );
+        let repaired = repair_bounds_help_from_errors(
+            &[error],
+            &file.0,
+            "inner::bar_extracted",
+            &RepairConfig::default(),
+        );
+        assert!(repaired.unwrap());
+        let source = file.read();
+        assert!(source.contains("where\n        'a: 'b,"), "{}", source);
+    }
+
+    #[test]
+    fn forced_lifetime_is_elided_unless_the_compile_fails() {
+        let original = "fn bar_extracted<'lt0>(x: &'lt0 i32, y: &'lt0 i32) -> i32 {
+    *x + *y
+}
+";
+        let config = RepairConfig::builder().force_elide(&["'lt0"]).build();
+        let file = TempSource::new("force_elide", original);
+        let mut compiles = Command::new("true");
+        elide_lifetimes_annotations_with_config(
+            &file.0,
+            "bar_extracted",
+            &config,
+            Some(&mut compiles),
+        )
+        .unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(x: &i32, y: &i32) -> i32"),
+            "{}",
+            source
+        );
+
+        let file = TempSource::new("force_elide_fails", original);
+        let mut fails = Command::new("false");
+        elide_lifetimes_annotations_with_config(
+            &file.0,
+            "bar_extracted",
+            &config,
+            Some(&mut fails),
+        )
+        .unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0>(x: &'lt0 i32, y: &'lt0 i32) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn repaired_function_is_extracted_from_the_fixtures() {
+        let before = concat!(env!("CARGO_MANIFEST_DIR"), "/input/lifetime_bounds.rs");
+        let after = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/output/lifetime_bounds_simple_repairer.rs"
+        );
+        let (original, repaired) = extract_repaired_function(before, after, "bar_extracted")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            original,
+            "fn bar_extracted(p: &mut &i32, x: &i32) {\n    *p = &x;\n}\n"
+        );
+        assert_eq!(
+            repaired,
+            "fn bar_extracted<'a>(p: &mut &'a i32, x: &'a i32) {\n    *p = &x;\n}\n"
+        );
+        assert_eq!(
+            extract_function_source(before, "new_foo")
+                .unwrap()
+                .map(|s| s.lines().count()),
+            Some(6)
+        );
+        assert_eq!(extract_function_source(before, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn trait_object_bound_in_the_output_is_kept() {
+        let file = TempSource::new(
+            "dyn_bound",
+            "fn bar_extracted<'a>(x: &'a i32) -> Box<dyn Iterator<Item = i32> + 'a> {
+    Box::new(std::iter::once(*x))
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.annotations_left);
+        let source = file.read();
+        assert!(
+            source.contains(
+                "fn bar_extracted<'lt0>(x: &'lt0 i32) -> Box<dyn Iterator<Item = i32> + 'lt0>"
+            ),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn redirected_write_leaves_the_original_untouched() {
+        let original = "fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+";
+        let file = TempSource::new("redirect_from", original);
+        let staged = TempSource::new("redirect_to", "");
+        let target = PathBuf::from(&staged.0);
+        let config = RepairConfig::builder()
+            .on_write(move |_, _| WriteDecision::Redirect(target.clone()))
+            .build();
+        elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None).unwrap();
+        assert_eq!(file.read(), original);
+        let source = staged.read();
+        assert!(
+            source.contains("fn bar_extracted(x: &i32) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn suggestion_spanning_several_lines_is_applied_as_a_block() {
+        let file = TempSource::new(
+            "multi_line_help",
+            "fn bar_extracted(
+    x: &i32,
+    y: &i32,
+) -> &i32 {
+    x
+}
+",
+        );
+        let error = diagnostic(
+            "error[E0106]: missing lifetime specifier
+ --> src/main.rs:4:6
+  |
+2 |     x: &i32,
+  |        ----
+3 |     y: &i32,
+  |        ----
+4 | ) -> &i32 {
+  |      ^ expected named lifetime parameter
+  |
+help: consider introducing a named lifetime parameter
+  |
+1 ~ fn bar_extracted<'a>(
+2 ~     x: &'a i32,
+3 ~     y: &'a i32,
+4 ~ ) -> &'a i32 {
+  |
+",
+        );
+        let repaired =
+            repair_standard_help_from_errors(&[error], &file.0, &RepairConfig::default());
+        assert!(repaired.unwrap());
+        assert_eq!(
+            file.read(),
+            "fn bar_extracted<'a>(
+    x: &'a i32,
+    y: &'a i32,
+) -> &'a i32 {
+    x
+}
+"
+        );
+    }
+
+    #[test]
+    fn docs_and_attributes_survive_elision_and_bounds() {
+        let file = TempSource::new(
+            "docs_attrs",
+            "/// Moves `x` into `p`
+#[inline]
+#[allow(clippy::needless_lifetimes)]
+fn bar_extracted<'a, 'b>(p: &'a mut &'b i32, x: &'a i32) {
+    *p = x;
+}
+",
+        );
+        // syn parses the doc comment as the `#[doc]` attribute it stands for
+        let attrs = |source: &str| match &try_parse_source(source).unwrap().items[0] {
+            syn::Item::Fn(item) => item.attrs.iter().map(print_source).collect::<Vec<_>>(),
+            item => panic!("not a fn: {}", print_source(item)),
+        };
+        let original = attrs(&file.read());
+        assert_eq!(original[0], "# [doc = \" Moves `x` into `p`\"]");
+        assert_eq!(original.len(), 3);
+        let error = diagnostic("  = help: consider adding the following bound: `'a: 'b`");
+        let repaired = repair_bounds_help_from_errors(
+            &[error],
+            &file.0,
+            "bar_extracted",
+            &RepairConfig::default(),
+        );
+        assert!(repaired.unwrap());
+        assert_eq!(attrs(&file.read()), original);
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert_eq!(attrs(&file.read()), original);
+    }
+
+    #[test]
+    fn lifetime_of_a_closure_return_is_kept() {
+        let file = TempSource::new(
+            "closure_return",
+            "fn bar_extracted<'a>(f: impl Fn() -> &'a i32) -> &'a i32 {
+    f()
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.annotations_left);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0>(f: impl Fn() -> &'lt0 i32) -> &'lt0 i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn owned_parameters_need_no_lifetime_repair() {
+        let file = TempSource::new(
+            "owned_params",
+            "fn owned(x: i32, v: Vec<String>) -> String {
+    v[x as usize].clone()
+}
+fn borrowed(x: &i32) -> i32 {
+    *x
+}
+",
+        );
+        assert!(!may_need_lifetime_repair(&file.0, "owned").unwrap());
+        assert!(may_need_lifetime_repair(&file.0, "borrowed").unwrap());
+        assert!(may_need_lifetime_repair(&file.0, "missing").unwrap());
+    }
+
+    #[test]
+    fn default_repair_function_repairs_a_fixture() {
+        let input = concat!(env!("CARGO_MANIFEST_DIR"), "/input/lifetime_bounds.rs");
+        let expected = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/output/lifetime_bounds_simple_repairer.rs"
+        );
+        let file = TempSource::new("default_repair", "");
+        let any_error = |_: &RustcError| true;
+        let passes = [RepairPass {
+            name: "standard",
+            matches: &any_error,
+            repair: &|error: &RustcError| repair_standard_help(&error.rendered, &file.0),
+        }];
+        let config = RepairConfig::default();
+        let result = default_repair_function(input, &file.0, "bar_extracted", &passes, &config);
+        assert!(result.success);
+        assert!(result.repair_count > 0);
+        assert_eq!(
+            result.final_signatures["bar_extracted"],
+            "fn bar_extracted<'a>(p: &mut &'a i32, x: &'a i32)"
+        );
+        assert_eq!(
+            extract_function_source(&file.0, "bar_extracted").unwrap(),
+            extract_function_source(expected, "bar_extracted").unwrap()
+        );
+    }
+
+    #[test]
+    fn nested_reference_lifetimes_are_counted_apart() {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/input/lifetime_bounds_not_enough_annotations.rs"
+        );
+        let file = TempSource::new("nested_references", &fs::read_to_string(fixture).unwrap());
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert_eq!(result.lifetime_map["'a"], "'lt0");
+        assert_eq!(result.lifetime_map["'b"], "'lt1");
+        assert!(!result.lifetime_map.contains_key("'c"));
+        let source = extract_function_source(&file.0, "bar_extracted")
+            .unwrap()
+            .unwrap();
+        assert!(
+            source.starts_with("fn bar_extracted<'lt0, 'lt1>(p: &'lt0 mut &'lt1 i32, x: &i32)"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn elision_style_removes_or_anonymizes() {
+        let original = "struct Foo<'a>(&'a i32);
+fn bar_extracted<'a, 'b>(x: &'a i32, y: Foo<'b>) -> i32 {
+    *x + *y.0
+}
+";
+        for (style, expected) in [
+            (
+                ElisionStyle::Remove,
+                "fn bar_extracted(x: &i32, y: Foo<'_>) -> i32",
+            ),
+            (
+                ElisionStyle::Anonymize,
+                "fn bar_extracted(x: &'_ i32, y: Foo<'_>) -> i32",
+            ),
+        ] {
+            let file = TempSource::new(&format!("style_{:?}", style), original);
+            let config = RepairConfig::builder().elision_style(style).build();
+            elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None)
+                .unwrap();
+            let source = file.read();
+            assert!(source.contains(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn required_lifetimes_are_reported_after_the_repair() {
+        capture_logs();
+        let input = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/input/in_out_lifetimes_wide_bounds.rs"
+        );
+        let file = TempSource::new("required_lifetimes", "");
+        let config = RepairConfig::builder().label("label-128").build();
+        let result = crate::repair_lifetime_tightest_bound_first::Repairer {}
+            .repair_function_with_config(input, &file.0, "bar_extracted", &config);
+        assert!(result.success);
+        assert!(result.has_non_elidible_lifetime);
+        let logged = LOGGED.lock().unwrap();
+        let report = "[label-128] compiles, but 'lt0, 'lt1, 'lt2 could not be elided";
+        assert!(logged.iter().any(|line| line == report), "{:?}", logged);
+    }
+
+    #[test]
+    fn hidden_capture_of_an_iterator_gets_the_lifetime_bound() {
+        let file = TempSource::new(
+            "capture_iterator",
+            "fn bar_extracted<'a>(v: &'a [i32]) -> impl Iterator<Item = &i32> {
+    v.iter()
+}
+",
+        );
+        let repaired = repair_hidden_lifetime_capture(&e0700("'a"), &file.0, "bar_extracted");
+        assert!(repaired.unwrap());
+        let source = file.read();
+        assert!(
+            source.contains("-> impl Iterator<Item = &i32> + 'a"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn lifetime_of_a_projection_is_kept() {
+        let file = TempSource::new(
+            "projection",
+            "trait Lend {
+    type Item<'a>
+    where
+        Self: 'a;
+}
+fn bar_extracted<'a, L: Lend>(x: L::Item<'a>) -> i32 {
+    0
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.annotations_left);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, L: Lend>(x: L::Item<'lt0>) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn compile_time_adds_up_the_compiles() {
+        let file = TempSource::new("compile_time", "fn main() {}\n");
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!(
+                r#"sleep 0.05; grep -q '// repaired' "$0" && exit 0; echo '{}'; exit 1"#,
+                cargo_error(&file.0, "E0106")
+            ),
+            &file.0,
+        ]);
+        let result = repair_iteration_project(
+            &mut compile,
+            &file.0,
+            &|_| mark_repaired(&file.0),
+            &RepairConfig::default(),
+        );
+        assert!(result.success);
+        assert!(
+            result.compile_time >= Duration::from_millis(100),
+            "{:?}",
+            result.compile_time
+        );
+    }
+
+    #[test]
+    fn trait_declaration_follows_the_elided_impl() {
+        let file = TempSource::new(
+            "trait_impl_sync",
+            "trait Get {
+    fn bar_extracted<'a>(x: &'a i32) -> i32;
+}
+struct Foo;
+impl Get for Foo {
+    fn bar_extracted<'a>(x: &'a i32) -> i32 {
+        *x
+    }
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "<Foo as Get>::bar_extracted").unwrap();
+        let source = file.read();
+        assert_eq!(
+            source.matches("fn bar_extracted(x: &i32) -> i32").count(),
+            2,
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn offset_selects_the_enclosing_function() {
+        let source = "struct Foo;
+impl Foo {
+    fn bar_extracted(x: &i32) -> i32 {
+        *x
+    }
+}
+const X: i32 = 1;
+";
+        let at = |needle: &str| {
+            let offset = source.find(needle).unwrap();
+            function_name_at_offset(source, offset).unwrap()
+        };
+        assert_eq!(at("*x").as_deref(), Some("Foo::bar_extracted"));
+        assert_eq!(at("x: &i32").as_deref(), Some("Foo::bar_extracted"));
+        assert_eq!(at("const X"), None);
+    }
+
+    #[test]
+    fn lifetime_of_a_mutable_reference_is_kept() {
+        let file = TempSource::new(
+            "mut_reference",
+            "fn bar_extracted<'a, T>(x: &mut Vec<&'a T>) {
+    x.clear();
+}
+fn baz_extracted<'a, T: Default>(x: &'a mut T) {
+    *x = T::default();
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.annotations_left);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, T>(x: &mut Vec<&'lt0 T>)"),
+            "{}",
+            source
+        );
+        elide_lifetimes_annotations(&file.0, "baz_extracted").unwrap();
+        elide_lifetimes_annotations(&file.0, "qux_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn baz_extracted<'lt0, T: Default>(x: &'lt0 mut T)"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn clippy_lint_parses_like_a_rustc_diagnostic() {
+        let stdout = r#"{"reason":"compiler-artifact","package_id":"dep 0.1.0","fresh":true}
+{"reason":"compiler-message","package_id":"path+file:///w#app@0.1.0","manifest_path":"/w/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"app","src_path":"/w/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"message":{"rendered":"error: the following explicit lifetimes could be elided: 'a\n --> src/lib.rs:1:18\n","$message_type":"diagnostic","children":[{"children":[],"code":null,"level":"help","message":"elide the lifetimes","rendered":null,"spans":[]}],"code":{"code":"clippy::needless_lifetimes","explanation":null},"level":"error","message":"the following explicit lifetimes could be elided: 'a","spans":[{"byte_end":20,"byte_start":18,"column_end":20,"column_start":18,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":null,"line_end":1,"line_start":1,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}
+{"reason":"build-finished","success":false}
+"#;
+        let errors = RustcJsonParser.parse_cargo(stdout);
+        assert_eq!(errors.len(), 1);
+        let lint = &errors[0];
+        assert_eq!(lint.level, "error");
+        assert_eq!(
+            lint.code.as_ref().unwrap().code,
+            "clippy::needless_lifetimes"
+        );
+        assert_eq!(lint.spans[0].file_name, "src/lib.rs");
+        assert_eq!((lint.spans[0].byte_start, lint.spans[0].byte_end), (18, 20));
+        assert!(in_target(lint, "/w/src/lib.rs"));
+    }
+
+    #[test]
+    fn span_replacement_splices_the_bytes_it_covers() {
+        let file = TempSource::new("span_replacement", "fn f(x: &i32) -> &i32 {\n    x\n}\n");
+        let span = |byte_start, byte_end| RustcSpan {
+            file_name: file.0.clone(),
+            byte_start,
+            byte_end,
+        };
+        apply_span_replacement(&file.0, &span(4, 4), "<'a>").unwrap();
+        apply_span_replacement(&file.0, &span(12, 13), "&'a ").unwrap();
+        assert_eq!(file.read(), "fn f<'a>(x: &'a i32) -> &i32 {\n    x\n}\n");
+        assert!(matches!(
+            apply_span_replacement(&file.0, &span(10, 100), ""),
+            Err(RepairError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn reversed_bound_unifies_instead_of_ping_ponging() {
+        let file = TempSource::new(
+            "ping_pong",
+            "fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) -> i32 {
+    *x + *y
+}
+",
+        );
+        // rustc asks for `'a: 'b` and, once it has it, for `'b: 'a`
+        let suggestion = |bound: &str| {
+            let mut error = diagnostic(&format!(
+                "error: lifetime may not live long enough\n  = help: consider adding the following bound: `{}`",
+                bound
+            ));
+            error.spans.push(RustcSpan {
+                file_name: file.0.clone(),
+                byte_start: 0,
+                byte_end: 0,
+            });
+            let message = CargoError {
+                message: Some(error),
+            };
+            serde_json::to_string(&message).unwrap() + "\n"
+        };
+        let forward = TempSource::new("ping_pong_forward", &suggestion("'a: 'b"));
+        let reverse = TempSource::new("ping_pong_reverse", &suggestion("'b: 'a"));
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            r#"grep -q "'a" "$0" && grep -q "'b" "$0" || exit 0
+               grep -q "'a: 'b" "$0" && cat "$2" || cat "$1"
+               exit 1"#,
+            &file.0,
+            &forward.0,
+            &reverse.0,
+        ]);
+        let config = RepairConfig::default();
+        let result = repair_iteration_project(
+            &mut compile,
+            &file.0,
+            &|error| {
+                repair_bounds_help_from_errors(
+                    std::slice::from_ref(&error),
+                    &file.0,
+                    "bar_extracted",
+                    &config,
+                )
+                .unwrap()
+            },
+            &config,
+        );
+        assert!(result.success);
+        assert_eq!(result.repair_count, 2);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'b>(x: &'b i32, y: &'b i32) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn trace_has_an_entry_per_repair() {
+        let file = TempSource::new("trace", "fn main() {}\n");
+        let mut trace = vec![];
+        let result = repair_iteration_project_with_trace(
+            &mut scripted_compile(&file.0, 2),
+            &file.0,
+            &|_| mark_repaired(&file.0),
+            &RepairConfig::default(),
+            &mut trace,
+        );
+        assert!(result.success);
+        assert_eq!(trace.len(), result.repair_count as usize);
+        assert_eq!(trace[0].diagnostics, ["error[E0106]: scripted"]);
+        assert_eq!(trace[0].edit, "+// repaired");
+        assert_eq!(trace[0].reduced_errors, Some(false));
+        assert_eq!(trace[1].reduced_errors, Some(true));
+    }
+
+    #[test]
+    fn struct_lifetime_of_the_output_is_detected() {
+        let file = TempSource::new(
+            "struct_output",
+            "struct Foo<'a>(&'a i32);
+fn bar_extracted<'a>(x: &'a i32) -> Foo<'a> {
+    Foo(x)
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.has_struct_lt);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(x: &i32) -> Foo<'_>"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn explanations_cover_the_bound_and_the_elision() {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/input/lifetime_bounds_not_enough_annotations.rs"
+        );
+        let original = fs::read_to_string(fixture).unwrap();
+        let file = TempSource::new("explain", &original);
+        let diagnostic = serde_json::to_string(&diagnostic(
+            "error: lifetime may not live long enough\n  --> src/main.rs:19:5\n  = help: consider adding the following bound: `'c: 'b`",
+        ))
+        .unwrap();
+        let explanations = explain(&file.0, "bar_extracted", &diagnostic).unwrap();
+        assert_eq!(
+            explanations,
+            [
+                "would keep `'a` on fn bar_extracted as `'lt0` because it's used more than once, \
+                 in the output or in a position that can't be elided",
+                "would keep `'b` on fn bar_extracted as `'lt1` because it's used more than once, \
+                 in the output or in a position that can't be elided",
+                "would elide `'c` from fn bar_extracted because it's used once and not in the output",
+                "would add bound `'c: 'b` to fn bar_extracted because rustc suggested it at line 19",
+            ]
+        );
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn const_fn_stays_const_through_elision() {
+        let file = TempSource::new(
+            "const_fn",
+            "const fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("const fn bar_extracted(x: &i32) -> i32"),
+            "{}",
+            source
+        );
+
+        // a suggestion that would drop the `const` is skipped
+        let error = diagnostic(
+            "error[E0106]: missing lifetime specifier
+help: consider introducing a named lifetime parameter
+  |
+1 | fn bar_extracted<'a>(x: &'a i32) -> i32 {
+  |                 ++++     ++
+",
+        );
+        let repaired =
+            repair_standard_help_from_errors(&[error], &file.0, &RepairConfig::default());
+        assert!(!repaired.unwrap());
+        assert_eq!(file.read(), source);
+    }
+
+    #[test]
+    fn suggestion_and_bound_of_one_blob_are_both_applied() {
+        let file = TempSource::new(
+            "all_help",
+            "fn bar_extracted<'a>(p: &mut &i32, x: &'a i32) {
+    *p = x;
+}
+",
+        );
+        let blob = [
+            diagnostic(
+                "error[E0621]: explicit lifetime required in the type of `p`
+help: consider introducing a named lifetime parameter
+  |
+1 | fn bar_extracted<'a, 'b>(p: &mut &'b i32, x: &'a i32) {
+  |                    ++++           ++
+",
+            ),
+            diagnostic("error: lifetime may not live long enough\n  = help: consider adding the following bound: `'a: 'b`"),
+        ]
+        .iter()
+        .map(|error| serde_json::to_string(error).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+        let repaired = repair_all_help(&blob, &file.0, "bar_extracted", &RepairConfig::default());
+        assert!(repaired.unwrap());
+        let source = file.read();
+        assert!(
+            source.contains(
+                "fn bar_extracted<'a, 'b>(p: &mut &'b i32, x: &'a i32)\nwhere\n    'a: 'b,"
+            ),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn where_clause_bounds_come_out_sorted() {
+        let source = "fn bar_extracted<'a, 'b, 'c>(x: &'a i32, y: &'b i32, z: &'c i32) {}\n";
+        let bound = |file: &TempSource, bound: &str| {
+            let error = diagnostic(&format!(
+                "  = help: consider adding the following bound: `{}`",
+                bound
+            ));
+            repair_bounds_help_from_errors(
+                &[error],
+                &file.0,
+                "bar_extracted",
+                &RepairConfig::default(),
+            )
+            .unwrap()
+        };
+        let first = TempSource::new("sorted_bounds_first", source);
+        assert!(bound(&first, "'c: 'a") && bound(&first, "'b: 'a"));
+        let second = TempSource::new("sorted_bounds_second", source);
+        assert!(bound(&second, "'b: 'a") && bound(&second, "'c: 'a"));
+        assert_eq!(first.read(), second.read());
+        assert!(
+            first.read().contains("where\n    'b: 'a,\n    'c: 'a,"),
+            "{}",
+            first.read()
+        );
+    }
+
+    #[test]
+    fn bound_already_present_is_no_progress() {
+        let source = "fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) -> &'b i32
+where
+    'a: 'b,
+{
+    x
+}
+";
+        let file = TempSource::new("bound_present", source);
+        let error = diagnostic("  = help: consider adding the following bound: `'a: 'b`");
+        let helped = repair_bounds_help_from_errors(
+            &[error],
+            &file.0,
+            "bar_extracted",
+            &RepairConfig::default(),
+        )
+        .unwrap();
+        assert!(!helped);
+        assert_eq!(file.read(), source);
+    }
+
+    #[test]
+    fn only_the_functions_matching_the_pattern_are_repaired() {
+        let source = "fn a_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+fn helper<'a>(x: &'a i32) -> i32 {
+    *x
+}
+fn b_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+fn main() {}
+";
+        let input = TempSource::new("matching_input", source);
+        let output = TempSource::new("matching_output", "");
+        let pattern = Regex::new("_extracted$").unwrap();
+        let results = repair_functions_matching(
+            &input.0,
+            &output.0,
+            &pattern,
+            &crate::repair_lifetime_tightest_bound_first::Repairer {},
+            &RepairConfig::default(),
+        )
+        .unwrap();
+        let selectors: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(selectors, ["a_extracted", "b_extracted"]);
+        assert!(results.iter().all(|(_, result)| result.success));
+        let repaired = output.read();
+        assert!(
+            repaired.contains("fn a_extracted(x: &i32) -> i32"),
+            "{}",
+            repaired
+        );
+        assert!(
+            repaired.contains("fn helper<'a>(x: &'a i32) -> i32"),
+            "{}",
+            repaired
+        );
+        assert!(
+            repaired.contains("fn b_extracted(x: &i32) -> i32"),
+            "{}",
+            repaired
+        );
+    }
+
+    #[test]
+    fn reformatting_is_told_apart_from_the_repair() {
+        let original = "fn helper( )->i32{ 1 }
+fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+";
+        let before = TempSource::new("attribution_before", original);
+        let after = TempSource::new("attribution_after", original);
+        elide_lifetimes_annotations(&after.0, "bar_extracted").unwrap();
+        let attribution = attribute_changes(&before.0, &after.0).unwrap();
+        assert!(attribution.reformatted());
+        assert_eq!(attribution.formatting, vec![1..2]);
+        assert_eq!(attribution.repair, vec![4..5]);
+    }
+
+    #[test]
+    fn impl_lifetime_is_kept_on_its_methods() {
+        let file = TempSource::new(
+            "impl_lifetime",
+            "struct Foo<'a>(&'a i32);
+impl<'a> Foo<'a> {
+    fn bar_extracted<'b>(x: &'a i32, y: &'b i32) -> i32 {
+        *x + *y
+    }
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "Foo::bar_extracted").unwrap();
+        assert!(result.found);
+        let source = file.read();
+        assert!(source.contains("impl<'a> Foo<'a> {"), "{}", source);
+        assert!(
+            source.contains("fn bar_extracted(x: &'a i32, y: &i32) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn unrelated_error_in_another_file_stops_the_loop() {
+        let original = "fn bar_extracted(x: &i32) -> i32 {\n    *x\n}\n";
+        let file = TempSource::new("unrelated", original);
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!("echo '{}'; exit 1", cargo_error("src/other.rs", "E0308")),
+        ]);
+        let repairs = Cell::new(0);
+        let result = repair_iteration_project(
+            &mut compile,
+            &file.0,
+            &|_| {
+                repairs.set(repairs.get() + 1);
+                true
+            },
+            &RepairConfig::default(),
+        );
+        assert!(!result.success);
+        assert!(result.unrelated_error);
+        assert_eq!(result.repair_count, 0);
+        assert_eq!(repairs.get(), 0);
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn edit_is_emitted_in_the_rustfix_schema() {
+        let source = "fn main() {}\nfn f(x: &i32) -> &i32 {\n    x\n}\n";
+        let edit = RepairEdit {
+            file_name: "src/lib.rs".to_string(),
+            byte_start: 17,
+            byte_end: 17,
+            replacement: "<'a>".to_string(),
+            context_before: None,
+            context_after: None,
+        };
+        assert_eq!(
+            rustfix_json("src/lib.rs", source, &[edit]),
+            serde_json::json!({
+                "file": "src/lib.rs",
+                "replacements": [{
+                    "snippet": {
+                        "file_name": "src/lib.rs",
+                        "line_range": {
+                            "start": { "line": 2, "column": 5 },
+                            "end": { "line": 2, "column": 5 },
+                        },
+                        "range": { "start": 17, "end": 17 },
+                        "text": ["fn f", "", "(x: &i32) -> &i32 {"],
+                    },
+                    "replacement": "<'a>",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn empty_source_is_signalled_not_repaired() {
+        for (name, source) in [("empty", ""), ("whitespace", " \n\t\n")] {
+            let file = TempSource::new(name, source);
+            assert_eq!(
+                lookup_function(&file.0, "bar_extracted").unwrap(),
+                FnLookup::EmptySource
+            );
+            let output = TempSource::new(&format!("{}_output", name), "");
+            let result =
+                default_repair_function(&file.0, &output.0, "", &[], &RepairConfig::default());
+            assert!(!result.success);
+        }
+    }
+
+    #[test]
+    fn only_the_functions_a_bound_was_added_to_are_elided_again() {
+        let file = TempSource::new(
+            "re_elide",
+            "fn bar_extracted<'a, 'b, 'c>(p: &'a mut &'b i32, x: &'b i32, y: &'c i32) {
+    *p = x;
+}
+fn untouched<'a>(x: &'a i32) -> i32 {
+    *x
+}
+",
+        );
+        let stderr = serde_json::to_string(&diagnostic(
+            "  = help: consider adding the following bound: `'a: 'b`",
+        ))
+        .unwrap();
+        let mut modified = vec![];
+        assert!(
+            repair_bounds_help_tracked(&stderr, &file.0, "bar_extracted", &mut modified).unwrap()
+        );
+        assert_eq!(modified, ["bar_extracted"]);
+        let elided =
+            elide_modified_functions(&file.0, &modified, &RepairConfig::default()).unwrap();
+        assert_eq!(elided.len(), 1);
+        let source = file.read();
+        assert!(
+            source.contains(
+                "fn bar_extracted<'lt0, 'lt1>(p: &'lt0 mut &'lt1 i32, x: &'lt1 i32, y: &i32)"
+            ),
+            "{}",
+            source
+        );
+        assert!(
+            source.contains("fn untouched<'a>(x: &'a i32) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn references_in_tuples_and_arrays_are_elided() {
+        let file = TempSource::new(
+            "tuple_array",
+            "fn bar_extracted<'a, 'b>(pair: (&'a i32, &'b str)) -> i32 {
+    *pair.0
+}
+fn baz_extracted<'a, 'b>(refs: [&'a i32; 2], other: &'b i32) -> i32 {
+    *refs[0] + *other
+}
+fn qux_extracted<'a>(pair: (&'a i32, &'a i32)) -> i32 {
+    *pair.0
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        elide_lifetimes_annotations(&file.0, "baz_extracted").unwrap();
+        elide_lifetimes_annotations(&file.0, "qux_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(pair: (&i32, &str)) -> i32"),
+            "{}",
+            source
+        );
+        assert!(
+            source.contains("fn baz_extracted(refs: [&i32; 2], other: &i32) -> i32"),
+            "{}",
+            source
+        );
+        // used twice in one tuple
+        assert!(
+            source.contains("fn qux_extracted<'lt0>(pair: (&'lt0 i32, &'lt0 i32)) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn unparsable_source_is_a_parse_error_with_its_span() {
+        let file = TempSource::new(
+            "parse_error",
+            "fn ok() {}
+fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    let = *x;
+}
+",
+        );
+        match elide_lifetimes_annotations(&file.0, "bar_extracted") {
+            Err(RepairError::Parse {
+                file: Some(parsed),
+                fn_name: Some(fn_name),
+                line,
+                column,
+                ..
+            }) => {
+                assert_eq!(parsed, file.0);
+                assert_eq!(fn_name, "bar_extracted");
+                assert_eq!((line, column), (3, 8));
+            }
+            Err(e) => panic!("not a parse error: {:?}", e),
+            Ok(_) => panic!("parsed"),
+        }
+    }
+
+    #[test]
+    fn lifetime_used_twice_in_a_boxed_closure_is_kept() {
+        let file = TempSource::new(
+            "boxed_closure",
+            "fn bar_extracted<'a>(f: Box<dyn Fn(&'a i32) -> &'a i32>) -> i32 {
+    0
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.annotations_left);
+        let source = file.read();
+        assert!(
+            source
+                .contains("fn bar_extracted<'lt0>(f: Box<dyn Fn(&'lt0 i32) -> &'lt0 i32>) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn single_line_change_is_one_minimal_hunk() {
+        let before = "fn main() {}\nfn f(x: &i32) -> &i32 {\n    x\n}\n";
+        let after = "fn main() {}\nfn f<'a>(x: &'a i32) -> &'a i32 {\n    x\n}\n";
+        assert_eq!(
+            diff_hunks(before, after),
+            [DiffHunk {
+                old_range: 2..3,
+                new_range: 2..3,
+                lines: vec![
+                    "-fn f(x: &i32) -> &i32 {".to_string(),
+                    "+fn f<'a>(x: &'a i32) -> &'a i32 {".to_string(),
+                ],
+            }]
+        );
+        assert!(diff_hunks(before, before).is_empty());
+    }
+
+    #[test]
+    fn duplicate_names_are_ambiguous() {
+        let file = TempSource::new(
+            "ambiguous",
+            "struct Foo;\nstruct Bar;\nimpl Foo {\n    fn bar() {}\n}\nimpl Bar {\n    fn bar() {}\n}\n",
+        );
+        match check_unambiguous(&file.0, "bar", &RepairConfig::default()) {
+            Err(RepairError::AmbiguousFunction {
+                fn_name,
+                candidates,
+            }) => {
+                assert_eq!(fn_name, "bar");
+                assert_eq!(
+                    candidates,
+                    [format!("{}:4:8", file.0), format!("{}:7:8", file.0)]
+                );
+            }
+            other => panic!("expected an ambiguous function, got {:?}", other),
+        }
+        assert!(check_unambiguous(&file.0, "Foo::bar", &RepairConfig::default()).is_ok());
+        let config = RepairConfig::builder().apply_to_all(true).build();
+        assert!(check_unambiguous(&file.0, "bar", &config).is_ok());
+    }
+
+    // `ERR <code> <file> <message>`, a line per diagnostic
+    struct BespokeParser;
+
+    impl DiagnosticParser for BespokeParser {
+        fn parse_rustc(&self, stderr: &str) -> Vec<RustcError> {
+            self.parse_cargo(stderr)
+        }
+
+        fn parse_cargo(&self, stdout: &str) -> Vec<RustcError> {
+            stdout
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.strip_prefix("ERR ")?.splitn(3, ' ');
+                    let (code, file, message) = (fields.next()?, fields.next()?, fields.next()?);
+                    Some(RustcError {
+                        rendered: format!("error[{}]: {}", code, message),
+                        spans: vec![RustcSpan {
+                            file_name: file.to_string(),
+                            byte_start: 0,
+                            byte_end: 0,
+                        }],
+                        level: "error".to_string(),
+                        code: Some(RustcCode {
+                            code: code.to_string(),
+                        }),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn custom_parser_reads_a_bespoke_format() {
+        let file = TempSource::new("bespoke", "fn bar_extracted() {}\n");
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            r#"grep -q '// repaired' "$0" && exit 0; echo "ERR E0106 $0 missing lifetime specifier"; exit 1"#,
+            &file.0,
+        ]);
+        let seen = Mutex::new(vec![]);
+        let config = RepairConfig::builder()
+            .diagnostic_parser(BespokeParser)
+            .build();
+        let result = repair_iteration_project(
+            &mut compile,
+            &file.0,
+            &|err| {
+                seen.lock().unwrap().push(err.clone());
+                mark_repaired(&file.0)
+            },
+            &config,
+        );
+        assert!(result.success);
+        assert_eq!(result.repair_count, 1);
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].rendered, "error[E0106]: missing lifetime specifier");
+        assert_eq!(seen[0].code.as_ref().unwrap().code, "E0106");
+        assert_eq!(seen[0].spans[0].file_name, file.0);
+    }
+
+    #[test]
+    fn signatures_only_skips_a_suggestion_in_a_body() {
+        let original = "fn bar_extracted(x: &i32) -> i32 {\n    x\n}\n";
+        let body_help = diagnostic(
+            "error[E0308]: mismatched types
+ --> src/main.rs:2:5
+  |
+2 |     x
+  |     ^ expected `i32`, found `&i32`
+  |
+help: consider dereferencing the borrow
+  |
+2 ~     *x
+  |
+",
+        );
+        let file = TempSource::new("signatures_only", original);
+        let config = RepairConfig::builder().signatures_only(true).build();
+        let repaired =
+            repair_standard_help_from_errors(std::slice::from_ref(&body_help), &file.0, &config);
+        assert!(!repaired.unwrap());
+        assert_eq!(file.read(), original);
+
+        let repaired =
+            repair_standard_help_from_errors(&[body_help], &file.0, &RepairConfig::default());
+        assert!(repaired.unwrap());
+        assert_eq!(
+            file.read(),
+            "fn bar_extracted(x: &i32) -> i32 {\n    *x\n}\n"
+        );
+    }
+
+    #[test]
+    fn edited_copy_the_compile_never_reads_is_an_early_error() {
+        let original = TempSource::new(
+            "compiled_original",
+            "fn bar_extracted(x: &i32) -> &i32 {\n    x\n}\n",
+        );
+        let copy = TempSource::new("compiled_copy", &original.read());
+        let mut compile = scripted_compile(&original.0, 1);
+        let repairs = Cell::new(0);
+        let result = repair_iteration_project(
+            &mut compile,
+            &copy.0,
+            &|_| {
+                repairs.set(repairs.get() + 1);
+                mark_repaired(&copy.0)
+            },
+            &RepairConfig::default(),
+        );
+        assert!(!result.success);
+        assert!(result.unrelated_error);
+        assert_eq!(repairs.get(), 0);
+
+        let errors: Vec<RustcError> =
+            serde_json::from_str::<CargoError>(&cargo_error(&original.0, "E0106"))
+                .unwrap()
+                .message
+                .into_iter()
+                .collect();
+        match check_compiled(&errors, &copy.0) {
+            Err(RepairError::NotCompiled { file, compiled }) => {
+                assert_eq!(file, copy.0);
+                assert_eq!(compiled, std::slice::from_ref(&original.0));
             }
+            other => panic!("expected the copy not to be compiled, got {:?}", other),
         }
+        assert!(check_compiled(&errors, &original.0).is_ok());
+        assert!(check_compiled(&[], &copy.0).is_ok());
     }
-}
 
-pub struct ElideLifetimeResult {
-    #[allow(dead_code)]
-    pub success: bool,
-    pub annotations_left: bool,
-    pub has_struct_lt: bool,
+    #[test]
+    fn identity_over_a_generic_is_fully_elided() {
+        let file = TempSource::new(
+            "identity",
+            "fn bar_extracted<'a, T>(x: &'a T) -> &'a T {\n    x\n}\n",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<T>(x: &T) -> &T"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn identity_next_to_an_elided_reference_keeps_its_lifetime() {
+        let file = TempSource::new(
+            "identity_two_refs",
+            "fn bar_extracted<'a, T, U>(x: &'a T, y: &U) -> &'a T {\n    x\n}\n",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, T, U>(x: &'lt0 T, y: &U) -> &'lt0 T"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn repairable_errors_fall_towards_convergence() {
+        let file = TempSource::new("converging", "fn main() {}\n");
+        let runs = TempSource::new("converging_runs", "");
+        // a compile fewer E0106 each run, next to an E0308 no pass repairs, until the fourth
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!(
+                r#"echo run >> "$1"; n=$(wc -l < "$1"); [ "$n" -ge 4 ] && exit 0
+echo '{}'
+while [ "$n" -lt 4 ]; do echo '{}'; n=$((n + 1)); done
+exit 1"#,
+                cargo_error(&file.0, "E0308"),
+                cargo_error(&file.0, "E0106")
+            ),
+            &file.0,
+            &runs.0,
+        ]);
+        let mut trace = vec![];
+        let result = repair_iteration_project_with_trace(
+            &mut compile,
+            &file.0,
+            &|_| true,
+            &RepairConfig::default(),
+            &mut trace,
+        );
+        assert!(result.success);
+        let repairable: Vec<usize> = trace.iter().map(|iteration| iteration.repairable).collect();
+        assert_eq!(repairable, [3, 2, 1]);
+        let diagnostics: Vec<usize> = trace
+            .iter()
+            .map(|iteration| iteration.diagnostics.len())
+            .collect();
+        assert_eq!(diagnostics, [4, 3, 2]);
+    }
+
+    #[test]
+    fn callees_inside_macros_are_renamed() {
+        let file = TempSource::new(
+            "macro_callee",
+            "fn bar_extracted____EXTRACT_THIS() -> i32 {
+    1
+}
+fn main() {
+    println!(\"{}\", bar_extracted____EXTRACT_THIS());
+    assert_eq!(bar_extracted____EXTRACT_THIS(), 1);
 }
+",
+        );
+        callee_renamer(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(!source.contains("____EXTRACT_THIS"), "{}", source);
+        assert!(source.contains("fn bar_extracted() -> i32"), "{}", source);
+        assert!(
+            source.contains("println!(\"{}\", bar_extracted())"),
+            "{}",
+            source
+        );
+        assert!(
+            source.contains("assert_eq!(bar_extracted(), 1)"),
+            "{}",
+            source
+        );
+    }
 
-/**
-Elide lifetimes that are only used once in the inputs and not used in output(s)/bound(s)
+    #[test]
+    fn unconverged_repair_under_the_cap_is_reverted() {
+        let original = "fn bar_extracted(x: &i32, y: &i32) -> &i32 {\n    x\n}\nfn main() {}\n";
+        let file = TempSource::new("cap_original", original);
+        let output = TempSource::new("cap_output", "");
+        // changes the file without ever repairing the missing lifetime
+        let attempt = |_: &str| {
+            let source = fs::read_to_string(&output.0).unwrap();
+            fs::write(&output.0, source + "// attempt\n").unwrap();
+            true
+        };
+        let repair = |config: &RepairConfig| {
+            default_repair_function_with_processor(
+                &file.0,
+                &output.0,
+                "bar_extracted",
+                &attempt,
+                &RepairSteps::none(),
+                config,
+            )
+        };
+        let capped = RepairConfig::builder().max_iterations(2);
 
-Do not elide lifetimes when receiver (self) is in the input
+        let result = repair(&capped.clone().build());
+        assert!(!result.success);
+        assert!(output.read().contains("// attempt"));
 
-Elision rules are here: https://doc.rust-lang.org/nomicon/lifetime-elision.htm
-*/
-pub fn elide_lifetimes_annotations(new_file_name: &str, fn_name: &str) -> ElideLifetimeResult {
-    let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-    let mut file = syn::parse_str::<syn::File>(file_content.as_str())
-        .map_err(|e| format!("{:?}", e))
+        let result = repair(&capped.revert_if_not_converged(true).build());
+        assert!(!result.success);
+        assert_eq!(output.read(), original);
+    }
+
+    #[test]
+    fn static_lifetime_arguments_are_never_elided() {
+        let file = TempSource::new(
+            "static_arg",
+            "struct Foo<'a>(&'a str);
+fn bar_extracted<'a>(foo: Foo<'static>, x: &'a i32, name: &'static str) -> i32 {
+    *x
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains(
+                "fn bar_extracted(foo: Foo<'static>, x: &i32, name: &'static str) -> i32"
+            ),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn context_lines_of_a_suggestion_are_not_replacements() {
+        let file = TempSource::new(
+            "context_lines",
+            "// picks the first of two numbers
+fn bar_extracted(x: &i32, y: &i32) -> &i32 {
+    x
+}
+",
+        );
+        // rustc shortens the context lines, taking them as replacements would cut the source
+        let error = diagnostic(
+            "error[E0106]: missing lifetime specifier
+ --> src/main.rs:2:39
+  |
+1 | // picks the first ...
+2 | fn bar_extracted(x: &i32, y: &i32) -> &i32 {
+  |                     ----     ----     ^ expected named lifetime parameter
+  |
+help: consider introducing a named lifetime parameter
+  |
+1 | // picks the first ...
+2 | fn bar_extracted<'a>(x: &'a i32, y: &'a i32) -> &'a i32 {
+  |                 ++++     ++          ++          ++
+3 |     ...
+  |
+",
+        );
+        let repaired =
+            repair_standard_help_from_errors(&[error], &file.0, &RepairConfig::default());
+        assert!(repaired.unwrap());
+        assert_eq!(
+            file.read(),
+            "// picks the first of two numbers
+fn bar_extracted<'a>(x: &'a i32, y: &'a i32) -> &'a i32 {
+    x
+}
+"
+        );
+    }
+
+    #[test]
+    fn transforms_chain_on_a_parsed_file() {
+        let mut file: syn::File = syn::parse_str(
+            "fn bar_extracted____EXTRACT_THIS<'a>(x: &'a i32) -> &'a i32 {
+    x
+}
+fn baz<'a, 'b>(x: &'a i32, y: &'b i32) -> &'a i32 {
+    x
+}
+fn main() {
+    bar_extracted____EXTRACT_THIS(&1);
+}
+",
+        )
         .unwrap();
-    let mut visit = FnLifetimeElider {
-        fn_name,
-        annotations_left: false,
-        has_struct_lt: false,
-    };
-    visit.visit_file_mut(&mut file);
-    let file = file.into_token_stream().to_string();
-    fs::write(new_file_name.to_string(), format_source(&file)).unwrap();
-    ElideLifetimeResult {
-        success: true,
-        annotations_left: visit.annotations_left,
-        has_struct_lt: visit.has_struct_lt,
+        rename_callees_in_ast(&mut file, "bar_extracted");
+        let result = elide_lifetimes_in_ast(&mut file, "bar_extracted");
+        assert!(result.found);
+        assert_eq!(
+            add_lifetime_bound_in_ast(&mut file, "baz", "'b", "'a"),
+            ["baz"]
+        );
+        let source = format_source(&print_source(&file));
+        assert!(!source.contains("____EXTRACT_THIS"), "{}", source);
+        assert!(
+            source.contains("fn bar_extracted(x: &i32) -> &i32"),
+            "{}",
+            source
+        );
+        assert!(source.contains("'b: 'a"), "{}", source);
     }
+
+    #[test]
+    fn lifetime_of_an_associated_type_binding_is_kept() {
+        let file = TempSource::new(
+            "assoc_binding",
+            "fn bar_extracted<'a, I>(first: &'a u8, iter: I) -> usize
+where
+    I: Iterator<Item = &'a u8>,
+{
+    iter.filter(|x| *x == first).count()
 }
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, I>(first: &'lt0 u8, iter: I) -> usize"),
+            "{}",
+            source
+        );
+        assert!(
+            source.contains("I: Iterator<Item = &'lt0 u8>,"),
+            "{}",
+            source
+        );
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-////////////////////////////////     CALLEE RENAMER    ////////////////////////////////////////////
-////////////////////////////////////////////////////////////////////////////////////////////////////
-pub struct RenameFn<'a> {
-    pub(crate) callee_name: &'a str,
-    pub(crate) callee_postfix: &'a str,
+        // not declaring it would leave the where clause with an undeclared lifetime
+        let file = TempSource::new(
+            "assoc_binding_only",
+            "fn bar_extracted<'a, I>(iter: I) -> usize
+where
+    I: Iterator<Item = &'a u8>,
+{
+    iter.count()
 }
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, I>(iter: I) -> usize"),
+            "{}",
+            source
+        );
+        assert!(
+            source.contains("I: Iterator<Item = &'lt0 u8>,"),
+            "{}",
+            source
+        );
+    }
 
-impl VisitMut for RenameFn<'_> {
-    fn visit_expr_method_call_mut(&mut self, i: &mut ExprMethodCall) {
-        let callee = i.clone().method.into_token_stream().to_string();
-        match callee.contains(self.callee_name) {
-            true => {
-                i.method = syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap()
-            }
-            false => syn::visit_mut::visit_expr_method_call_mut(self, i),
-        }
+    #[test]
+    fn final_signature_is_the_elided_one() {
+        let input = concat!(env!("CARGO_MANIFEST_DIR"), "/input/lifetime_bounds.rs");
+        let file = TempSource::new("final_signature", "");
+        let result = crate::repair_lifetime_tightest_bound_first::Repairer {}
+            .repair_function_with_config(input, &file.0, "bar_extracted", &RepairConfig::default());
+        assert!(result.success);
+        assert_eq!(result.final_signatures.len(), 1);
+        let signature = &result.final_signatures["bar_extracted"];
+        assert_eq!(
+            signature,
+            "fn bar_extracted<'lt0, 'lt1>(p: &'lt1 mut &'lt0 i32, x: &'lt0 i32)"
+        );
+        let source = file.read();
+        assert!(source.contains(signature.as_str()), "{}", source);
     }
 
-    fn visit_expr_call_mut(&mut self, i: &mut ExprCall) {
-        let callee = i.func.as_ref().into_token_stream().to_string();
-        match callee.contains(self.callee_name) {
-            true => {
-                debug!("callee: {} matched", &callee);
-                *i.func.as_mut() =
-                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
-            }
-            false => {}
-        }
-        syn::visit_mut::visit_expr_call_mut(self, i);
+    #[test]
+    fn surviving_lifetimes_are_named_by_the_policy() {
+        let source = "fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) -> (&'a i32, &'b i32) {
+    (x, y)
+}
+";
+        let file = TempSource::new("name_policy", source);
+        let config = RepairConfig::builder()
+            .name_policy(|n| format!("'arg{}", n))
+            .build();
+        elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None).unwrap();
+        let repaired = file.read();
+        assert!(
+            repaired.contains(
+                "fn bar_extracted<'arg0, 'arg1>(x: &'arg0 i32, y: &'arg1 i32) -> (&'arg0 i32, &'arg1 i32)"
+            ),
+            "{}",
+            repaired
+        );
+
+        // a name no lifetime can be declared with falls back to the default
+        let file = TempSource::new("name_policy_anonymous", source);
+        let config = RepairConfig::builder()
+            .name_policy(|n| match n {
+                0 => "'_".to_string(),
+                n => format!("'arg{}", n),
+            })
+            .build();
+        elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None).unwrap();
+        let repaired = file.read();
+        assert!(
+            repaired.contains("fn bar_extracted<'lt0, 'arg1>"),
+            "{}",
+            repaired
+        );
     }
-    fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
-        let callee = i.sig.ident.to_string();
-        match callee.contains(self.callee_name) {
-            true => {
-                i.sig.ident =
-                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
-            }
-            false => {}
+
+    #[test]
+    fn policy_names_never_merge_or_shadow_lifetimes() {
+        let source = "struct Foo<'a>(&'a i32);
+impl<'a> Foo<'a> {
+    fn bar_extracted<'b, 'c, 'd>(x: &'b i32, y: &'c i32, z: &'d i32) -> (&'b i32, &'c i32, &'d i32) {
+        (x, y, z)
+    }
+}
+";
+        let file = TempSource::new("name_policy_collisions", source);
+        let config = RepairConfig::builder()
+            .name_policy(|n| ["'x", "'x", "'a"][n].to_string())
+            .build();
+        elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None).unwrap();
+        let repaired = file.read();
+        assert!(
+            repaired.contains(
+                "fn bar_extracted<'x, 'lt1, 'lt2>(
+        x: &'x i32,
+        y: &'lt1 i32,
+        z: &'lt2 i32,
+    ) -> (&'x i32, &'lt1 i32, &'lt2 i32)"
+            ),
+            "{}",
+            repaired
+        );
+    }
+
+    #[test]
+    fn extern_and_variadic_functions_are_left_untouched() {
+        let original = "extern \"C\" fn bar_extracted<'a>(x: &'a i32) -> &'a i32 {
+    x
+}
+unsafe extern \"C\" fn variadic<'a>(x: &'a i32, mut args: ...) -> i32 {
+    *x
+}
+";
+        let file = TempSource::new("extern_c", original);
+        let result = elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(result.found);
+        elide_lifetimes_annotations(&file.0, "variadic").unwrap();
+        assert_eq!(file.read(), original);
+        assert!(skips_lifetimes(
+            &syn::parse_str::<ItemFn>("unsafe extern \"C\" fn variadic(mut args: ...) {}")
+                .unwrap()
+                .sig
+        ));
+        assert!(!skips_lifetimes(
+            &syn::parse_str::<ItemFn>("fn bar_extracted() {}")
+                .unwrap()
+                .sig
+        ));
+    }
+
+    #[test]
+    fn stubborn_code_gives_way_once_out_of_attempts() {
+        let file = TempSource::new("budget", "fn main() {}\n");
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!(
+                r#"grep -q '// repaired' "$0" && exit 0; echo '{}'; echo '{}'; exit 1"#,
+                cargo_error(&file.0, "E0308"),
+                cargo_error(&file.0, "E0106")
+            ),
+            &file.0,
+        ]);
+        let code_is = |code: &'static str| {
+            move |error: &RustcError| error.code.as_ref().is_some_and(|c| c.code == code)
+        };
+        let (stubborn, fixable) = (code_is("E0308"), code_is("E0106"));
+        // claims a repair of the E0308 every time without ever getting rid of it
+        let attempt = |_: &RustcError| {
+            let source = fs::read_to_string(&file.0).unwrap();
+            fs::write(&file.0, source + "// attempt\n").unwrap();
+            Ok(true)
+        };
+        let passes = [
+            RepairPass {
+                name: "stubborn",
+                matches: &stubborn,
+                repair: &attempt,
+            },
+            RepairPass {
+                name: "fixable",
+                matches: &fixable,
+                repair: &|_| Ok(mark_repaired(&file.0)),
+            },
+        ];
+
+        let config = RepairConfig::builder().max_iterations(5).build();
+        let result = repair_iteration_project_prioritized(&mut compile, &file.0, &passes, &config);
+        assert!(!result.success);
+        assert!(!file.read().contains("// repaired"));
+
+        fs::write(&file.0, "fn main() {}\n").unwrap();
+        let config = RepairConfig::builder()
+            .max_iterations(5)
+            .max_attempts_per_code(2)
+            .build();
+        let result = repair_iteration_project_prioritized(&mut compile, &file.0, &passes, &config);
+        assert!(result.success);
+        assert_eq!(result.repair_count, 3);
+        assert_eq!(file.read().matches("// attempt").count(), 2);
+    }
+
+    #[test]
+    fn diagnostics_not_acted_on_are_ignored() {
+        let file = TempSource::new("ignored", "fn main() {}\n");
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!(
+                r#"grep -q '// repaired' "$0" && exit 0; echo '{}'; echo '{}'; exit 1"#,
+                cargo_error("src/other.rs", "E0308"),
+                cargo_error(&file.0, "E0106")
+            ),
+            &file.0,
+        ]);
+        let mut ignored = vec![];
+        let result = repair_iteration_project_with_ignored(
+            &mut compile,
+            &file.0,
+            &|_| mark_repaired(&file.0),
+            &RepairConfig::default(),
+            &mut ignored,
+        );
+        assert!(result.success);
+        assert_eq!(ignored.len(), 1);
+        assert_eq!(ignored[0].spans[0].file_name, "src/other.rs");
+        assert_eq!(ignored[0].code.as_ref().unwrap().code, "E0308");
+    }
+
+    #[test]
+    fn receiver_lifetime_is_elided_by_rule_three() {
+        let file = TempSource::new(
+            "receiver",
+            "struct Foo(i32);
+impl Foo {
+    fn bar_extracted<'a>(&'a self, x: &i32) -> &'a i32 {
+        &self.0
+    }
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "Foo::bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(&self, x: &i32) -> &i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn streamed_diagnostics_are_the_batched_ones() {
+        let run = |stream_diagnostics: bool| {
+            let file = TempSource::new(&format!("stream_{}", stream_diagnostics), "fn main() {}\n");
+            // two diagnostics a compile, with cargo's other output around them
+            let mut compile = Command::new("sh");
+            compile.args([
+                "-c",
+                &format!(
+                    r#"[ "$(grep -c '// repaired' "$0")" -ge 2 ] && exit 0
+echo '{{"reason":"compiler-artifact"}}'
+echo '{}'
+sleep 0.1
+echo '{}'
+echo 'not json'
+exit 1"#,
+                    cargo_error(&file.0, "E0106"),
+                    cargo_error(&file.0, "E0621")
+                ),
+                &file.0,
+            ]);
+            let seen = Mutex::new(vec![]);
+            let config = RepairConfig::builder()
+                .stream_diagnostics(stream_diagnostics)
+                .build();
+            let mut trace = vec![];
+            let result = repair_iteration_project_with_trace(
+                &mut compile,
+                &file.0,
+                &|error| {
+                    seen.lock().unwrap().push(error.rendered.clone());
+                    error.code.as_ref().unwrap().code == "E0106" && mark_repaired(&file.0)
+                },
+                &config,
+                &mut trace,
+            );
+            assert!(result.success);
+            let edits: Vec<String> = trace.into_iter().map(|iteration| iteration.edit).collect();
+            (
+                result.repair_count,
+                seen.into_inner().unwrap(),
+                edits,
+                file.read(),
+            )
+        };
+        let streamed = run(true);
+        assert_eq!(streamed.0, 2);
+        assert_eq!(
+            streamed.1,
+            [
+                "error[E0106]: scripted",
+                "error[E0621]: scripted",
+                "error[E0106]: scripted",
+                "error[E0621]: scripted"
+            ]
+        );
+        assert_eq!(streamed, run(false));
+    }
+
+    #[test]
+    fn lifetime_of_a_type_param_default_is_kept() {
+        let file = TempSource::new(
+            "param_default",
+            "fn bar_extracted<'a, T = &'a i32>(x: &'a i32, t: T) -> i32 {
+    *x
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'lt0, T = &'lt0 i32>(x: &'lt0 i32, t: T) -> i32"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn marker_comment_survives_elision() {
+        let file = TempSource::new(
+            "marker",
+            "fn main() {}
+
+// EXTRACTED
+fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+",
+        );
+        let config = RepairConfig::builder()
+            .preserve_markers("// EXTRACTED")
+            .build();
+        elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None).unwrap();
+        let source = file.read();
+        assert!(
+            source.contains("// EXTRACTED\nfn bar_extracted(x: &i32) -> i32"),
+            "{}",
+            source
+        );
+
+        // without the option the reprint drops it
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert!(!file.read().contains("// EXTRACTED"));
+    }
+
+    #[test]
+    fn path_rustc_prints_selects_the_method() {
+        // the signature lines of the file, in order
+        fn signatures(file: &TempSource) -> Vec<String> {
+            file.read()
+                .lines()
+                .filter(|line| line.contains("fn bar_extracted"))
+                .map(|line| line.trim().trim_end_matches([';', '{', ' ']).to_string())
+                .collect()
         }
-        syn::visit_mut::visit_impl_item_method_mut(self, i);
+        let elided = "fn bar_extracted(x: &i32) -> i32";
+        let kept = "fn bar_extracted<'a>(x: &'a i32) -> i32";
+
+        let file = TempSource::new(
+            "qualified_inherent",
+            "struct Foo;
+impl Foo {
+    fn bar_extracted<'a>(x: &'a i32) -> i32 {
+        *x
     }
+}
+fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "Foo::bar_extracted").unwrap();
+        assert!(result.found);
+        assert_eq!(signatures(&file), [elided, kept]);
 
-    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
-        let callee = i.sig.ident.to_string();
-        match callee.contains(self.callee_name) {
-            true => {
-                i.sig.ident =
-                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
-            }
-            false => {}
+        let file = TempSource::new(
+            "qualified_trait",
+            "struct Foo;
+trait Trait {
+    fn bar_extracted<'a>(x: &'a i32) -> i32;
+}
+impl Trait for Foo {
+    fn bar_extracted<'a>(x: &'a i32) -> i32 {
+        *x
+    }
+}
+fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
+}
+",
+        );
+        let result = elide_lifetimes_annotations(&file.0, "<Foo as Trait>::bar_extracted").unwrap();
+        assert!(result.found);
+        assert_eq!(signatures(&file), [elided, elided, kept]);
+    }
+
+    #[test]
+    fn lifetime_named_like_a_type_param_is_reported() {
+        capture_logs();
+        let file = TempSource::new(
+            "collision",
+            "fn bar_extracted<'a, 'b, T>(x: &'a T, y: &'b T) -> (&'a T, &'b T) {
+    (x, y)
+}
+",
+        );
+        let config = RepairConfig::builder()
+            .name_policy(|n| ["'t", "'u"][n].to_string())
+            .build();
+        elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None).unwrap();
+        let source = file.read();
+        assert!(source.contains("fn bar_extracted<'t, 'u, T>"), "{}", source);
+        let logged = LOGGED.lock().unwrap();
+        let report = "'t of bar_extracted is named like its parameter T";
+        assert!(logged.iter().any(|line| line == report), "{:?}", logged);
+        assert!(!logged
+            .iter()
+            .any(|line| line.starts_with("'u of bar_extracted")));
+    }
+
+    #[test]
+    fn function_is_found_across_the_files_of_a_crate() {
+        let dir = TempDir::new("crate_sources");
+        let files = [
+            ("Cargo.toml", "[package]\nname = \"fixture\"\n"),
+            ("src/lib.rs", "mod a;\nmod b;\nfn other() {}\n"),
+            ("src/a.rs", "fn bar_extracted() {}\n"),
+            ("src/b/mod.rs", "mod c;\n"),
+            (
+                "src/b/c.rs",
+                "struct Foo;\nimpl Foo {\n    fn bar_extracted(&self) {}\n}\n",
+            ),
+            ("src/broken.rs", "fn bar_extracted( {\n"),
+            ("target/debug/build/out.rs", "fn bar_extracted() {}\n"),
+            (".hidden/d.rs", "fn bar_extracted() {}\n"),
+        ];
+        for (path, source) in files {
+            let path = dir.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
         }
-        syn::visit_mut::visit_item_fn_mut(self, i);
+        let manifest = dir.0.join("Cargo.toml");
+        let sources = find_function_sources(&manifest.to_string_lossy(), "bar_extracted");
+        let expected: Vec<String> = ["src/a.rs", "src/b/c.rs"]
+            .iter()
+            .map(|path| dir.0.join(path).to_string_lossy().to_string())
+            .collect();
+        assert_eq!(sources, expected);
+        assert!(find_function_sources(&manifest.to_string_lossy(), "missing").is_empty());
     }
-    fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
-        let callee = i.sig.ident.to_string();
-        match callee.contains(self.callee_name) {
-            true => {
-                i.sig.ident =
-                    syn::parse_str(callee.replace(self.callee_postfix, "").as_str()).unwrap();
+
+    #[test]
+    fn lifetime_bound_joins_a_where_clause_of_type_predicates() {
+        let mut file: syn::File = syn::parse_str(
+            "fn bar_extracted<'a, 'b, T>(x: &'a T, y: &'b T) -> &'a T
+where
+    T: Clone,
+{
+    x
+}
+",
+        )
+        .unwrap();
+        assert_eq!(
+            add_lifetime_bound_in_ast(&mut file, "bar_extracted", "'b", "'a"),
+            ["bar_extracted"]
+        );
+        let source = format_source(&print_source(&file));
+        assert!(
+            source.contains("where\n    'b: 'a,\n    T: Clone,\n{"),
+            "{}",
+            source
+        );
+        assert!(syn::parse_str::<syn::File>(&source).is_ok());
+    }
+
+    #[test]
+    fn metrics_of_the_tightest_repair_of_a_fixture() {
+        let input = concat!(env!("CARGO_MANIFEST_DIR"), "/input/lifetime_bounds.rs");
+        let output = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/output/lifetime_bounds_tightest_bounds_first_repairer.rs"
+        );
+        // `<'lt0>` and the two `'lt0` of the references, a lifetime is a quote and an ident
+        assert_eq!(
+            repair_metrics(input, output).unwrap(),
+            RepairMetrics {
+                tokens_added: 8,
+                tokens_removed: 0,
+                lifetimes_introduced: 1,
+                lifetimes_removed: 0,
+                bounds_added: 0,
             }
-            false => {}
+        );
+
+        let original = TempSource::new(
+            "metrics_original",
+            "fn f<'a, 'b, 'c>(x: &'a i32, y: &'b i32, z: &'c i32) {}\n",
+        );
+        let repaired = TempSource::new(
+            "metrics_repaired",
+            "fn f<'a, 'b: 'a>(x: &'a i32, y: &'b i32, z: &i32) {}\n",
+        );
+        let metrics = repair_metrics(&original.0, &repaired.0).unwrap();
+        assert_eq!(metrics.lifetimes_introduced, 0);
+        assert_eq!(metrics.lifetimes_removed, 1);
+        assert_eq!(metrics.bounds_added, 1);
+    }
+
+    #[test]
+    fn intermediate_iterations_are_not_reformatted() {
+        let unformatted = "fn   main( ) {   let x=1; }";
+        let original = format!(
+            "{}\nfn bar_extracted<'a>(v: &'a [i32]) -> impl Iterator<Item = &i32> {{\n    v.iter()\n}}\n",
+            unformatted
+        );
+        let file = TempSource::new("not_reformatted", &original);
+        let compiled = TempSource::new("not_reformatted_compiled", "");
+        // keeps what each compile sees, failing until the capture is repaired
+        let mut compile = Command::new("sh");
+        compile.args([
+            "-c",
+            &format!(
+                r#"cat "$0" >> "$1"; echo '=====' >> "$1"; grep -q "+ 'a" "$0" && exit 0; echo '{}'; exit 1"#,
+                cargo_error(&file.0, "E0700")
+            ),
+            &file.0,
+            &compiled.0,
+        ]);
+        let result = repair_iteration_project(
+            &mut compile,
+            &file.0,
+            &|_| repair_hidden_lifetime_capture(&e0700("'a"), &file.0, "bar_extracted").unwrap(),
+            &RepairConfig::default(),
+        );
+        assert!(result.success);
+        let compiled = compiled.read();
+        let compiles: Vec<&str> = compiled.split("=====\n").collect();
+        assert_eq!(compiles, [original.as_str(), compiles[1], ""]);
+        assert!(compiles[1].starts_with(unformatted), "{}", compiles[1]);
+        assert!(compiles[1].contains("+ 'a {"), "{}", compiles[1]);
+        // once the loop is done the whole file is formatted
+        let source = file.read();
+        assert!(
+            source.starts_with("fn main() {\n    let x = 1;\n}\n"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn lifetime_of_an_impl_trait_argument_is_kept() {
+        let file = TempSource::new(
+            "impl_trait_arg",
+            "fn bar_extracted<'a>(x: impl Iterator<Item = i32> + 'a) -> Box<dyn Iterator<Item = i32> + 'a> {
+    Box::new(x)
+}
+",
+        );
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        let source = file.read();
+        assert!(
+            source.contains(
+                "fn bar_extracted<'lt0>(\n    x: impl Iterator<Item = i32> + 'lt0,\n) -> Box<dyn Iterator<Item = i32> + 'lt0>"
+            ),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn failing_test_command_reverts_the_repair() {
+        let original = "fn main() {}\n";
+        let file = TempSource::new("test_command", original);
+        let repair = |test_command: &[&str]| {
+            fs::write(&file.0, original).unwrap();
+            let config = RepairConfig::builder().test_command(test_command).build();
+            repair_iteration_project(
+                &mut scripted_compile(&file.0, 1),
+                &file.0,
+                &|_| mark_repaired(&file.0),
+                &config,
+            )
+        };
+        let result = repair(&["false"]);
+        assert!(!result.success);
+        assert_eq!(file.read(), original);
+
+        let result = repair(&["sh", "-c", "exit 0"]);
+        assert!(result.success);
+        assert_eq!(file.read(), "fn main() {}\n// repaired\n");
+    }
+
+    #[test]
+    fn bound_for_a_missing_function_is_unapplied() {
+        let original = "fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) {}\n";
+        let file = TempSource::new("unapplied", original);
+        let rendered = "error: lifetime may not live long enough\n  = help: consider adding the following bound: `'a: 'b`";
+        let stderr = serde_json::to_string(&diagnostic(rendered)).unwrap();
+        let (mut modified, mut unapplied) = (vec![], vec![]);
+        let repaired = repair_bounds_help_with_unapplied(
+            &stderr,
+            &file.0,
+            "missing",
+            &mut modified,
+            &mut unapplied,
+        );
+        assert!(!repaired.unwrap());
+        assert!(modified.is_empty());
+        assert_eq!(
+            unapplied,
+            [UnappliedBound {
+                lifetime: "'a".to_string(),
+                bound: "'b".to_string(),
+                rendered: rendered.to_string(),
+            }]
+        );
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn main_and_exported_functions_are_untouched() {
+        let original = "fn main() {
+    let x = 1;
+}
+#[no_mangle]
+fn exported<'a>(x: &'a i32) -> i32 {
+    *x
+}
+";
+        let file = TempSource::new("fixed_signatures", original);
+        for fn_name in ["main", "exported"] {
+            elide_lifetimes_annotations(&file.0, fn_name).unwrap();
+            assert_eq!(file.read(), original);
+            let mut parsed: syn::File = syn::parse_str(original).unwrap();
+            assert!(add_lifetime_bound_in_ast(&mut parsed, fn_name, "'a", "'static").is_empty());
         }
-        syn::visit_mut::visit_trait_item_method_mut(self, i);
+
+        // only the free `fn main` is the entry point
+        let file = TempSource::new(
+            "method_main",
+            "struct Foo;
+impl Foo {
+    fn main<'a>(x: &'a i32) -> i32 {
+        *x
     }
 }
+",
+        );
+        elide_lifetimes_annotations(&file.0, "Foo::main").unwrap();
+        let source = file.read();
+        assert!(source.contains("fn main(x: &i32) -> i32"), "{}", source);
+    }
 
-pub fn callee_renamer(new_file_name: &str, fn_name: &str) {
-    let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-    let mut file = syn::parse_str::<syn::File>(file_content.as_str())
-        .map_err(|e| format!("{:?}", e))
-        .unwrap();
-    let mut visitor = RenameFn {
-        callee_name: fn_name,
-        callee_postfix: "____EXTRACT_THIS",
-    };
-    visitor.visit_file_mut(&mut file);
-    let file = file.into_token_stream().to_string();
-    fs::write(new_file_name.to_string(), format_source(&file)).unwrap()
+    #[test]
+    fn bound_is_read_off_a_note_only_diagnostic() {
+        let original = "fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) -> &'a i32 {
+    y
 }
+";
+        let rendered = "error: lifetime may not live long enough
+ --> src/main.rs:2:5
+  |
+1 | fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) -> &'a i32 {
+  |                  --  -- lifetime `'b` defined here
+  |                  |
+  |                  lifetime `'a` defined here
+2 |     y
+  |     ^ function was supposed to return data with lifetime `'a` but it is returning data with lifetime `'b`
+";
+        let file = TempSource::new("note_only", original);
+        let stderr = serde_json::to_string(&diagnostic(rendered)).unwrap();
+        assert!(repair_note_bounds(&stderr, &file.0, "bar_extracted").unwrap());
+        let source = file.read();
+        assert!(source.contains("where\n    'b: 'a,"), "{}", source);
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-////////////////////////////////     PROJECT HELPERS    ////////////////////////////////////////////
-////////////////////////////////////////////////////////////////////////////////////////////////////
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CargoError {
-    pub message: Option<RustcError>,
+        // with the bound suggested it is `repair_bounds_help`'s to add
+        let file = TempSource::new("note_with_help", original);
+        let helped = format!(
+            "{}  |\n  = help: consider adding the following bound: `'b: 'a`\n",
+            rendered
+        );
+        let stderr = serde_json::to_string(&diagnostic(&helped)).unwrap();
+        assert!(!repair_note_bounds(&stderr, &file.0, "bar_extracted").unwrap());
+        assert_eq!(file.read(), original);
+    }
+
+    #[test]
+    fn function_behind_an_inactive_feature_is_skipped() {
+        let original = "#[cfg(feature = \"x\")]
+fn bar_extracted<'a>(x: &'a i32) -> i32 {
+    *x
 }
+";
+        let file = TempSource::new("inactive_cfg", original);
+        let repair = |active: &[&str]| {
+            let config = RepairConfig::builder().active_cfg(active).build();
+            elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", &config, None)
+                .unwrap();
+            let mut parsed: syn::File = syn::parse_str(original).unwrap();
+            add_lifetime_bound_in_ast_with_config(
+                &mut parsed,
+                "bar_extracted",
+                "'a",
+                "'static",
+                &config,
+            )
+        };
+        // nothing confirms the compile enabled `x`, or it didn't
+        elide_lifetimes_annotations(&file.0, "bar_extracted").unwrap();
+        assert_eq!(file.read(), original);
+        assert!(repair(&["test"]).is_empty());
+        assert_eq!(file.read(), original);
+        assert!(repair(&["feature = \"y\""]).is_empty());
+        assert_eq!(file.read(), original);
 
-pub fn repair_iteration_project(
-    compile_cmd: &mut Command,
-    src_path: &str,
-    process_errors: &dyn Fn(&RustcError) -> bool,
-    print_stats: bool,
-    max_iterations: Option<i32>,
-) -> RepairResult {
-    let mut count = 0;
-    let max_iterations = max_iterations.unwrap_or(25);
-    let mut repair_result = RepairResult {
-        success: false,
-        repair_count: 0,
-        has_non_elidible_lifetime: false,
-        has_struct_lt: false,
-    };
-    let success = loop {
-        let out = compile_cmd.output().unwrap();
-        if out.status.success() {
-            info!("repair succeeded");
-            break true;
-        }
-        // cargo give rustc error to stdout not stderr
-        let stdout = String::from_utf8_lossy(&out.stdout);
-        let binding = stdout.to_string();
-        let deserializer = serde_json::Deserializer::from_str(binding.as_str());
-        let stream = deserializer.into_iter::<CargoError>();
-        count += 1;
+        assert_eq!(repair(&["feature = \"x\""]), ["bar_extracted"]);
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted(x: &i32) -> i32"),
+            "{}",
+            source
+        );
+    }
 
-        let mut help = false;
-        let mut last_failure = format!("");
-        for item in stream {
-            match &item {
-                Ok(item) => match &item.message {
-                    None => {}
-                    Some(message) => {
-                        let spans = &message.spans;
-                        debug!("message: {:?}", &message);
-                        for span in spans {
-                            if src_path.contains(&span.file_name) {
-                                debug!("processing error: {}", &message.rendered);
-                                last_failure = message.rendered.clone();
-                                if process_errors(&message) {
-                                    help = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                },
-                Err(e) => {
-                    debug!("error parsing cargo error:\n{}", e);
-                }
+    #[test]
+    fn signature_views_of_the_fixture_before_and_after() {
+        let input = concat!(env!("CARGO_MANIFEST_DIR"), "/input/lifetime_bounds.rs");
+        let output = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/output/lifetime_bounds_tightest_bounds_first_repairer.rs"
+        );
+        let input_view = |name: &str, ty: &str, lifetimes: &[&str]| InputView {
+            name: name.to_string(),
+            ty: ty.to_string(),
+            lifetimes: lifetimes.iter().map(|lt| lt.to_string()).collect(),
+        };
+        let views = repaired_signature_views(input, output, "bar_extracted").unwrap();
+        assert_eq!(views.len(), 1);
+        let (before, after) = &views["bar_extracted"];
+        assert_eq!(
+            *before,
+            SignatureView {
+                lifetimes: vec![],
+                inputs: vec![
+                    input_view("p", "&mut &i32", &[]),
+                    input_view("x", "&i32", &[]),
+                ],
+                output_lifetimes: vec![],
             }
-        }
+        );
+        assert_eq!(
+            *after,
+            SignatureView {
+                lifetimes: vec!["'lt0".to_string()],
+                inputs: vec![
+                    input_view("p", "&mut &'lt0 i32", &["'lt0"]),
+                    input_view("x", "&'lt0 i32", &["'lt0"]),
+                ],
+                output_lifetimes: vec![],
+            }
+        );
+    }
 
-        if !help {
-            debug!("last failure:\n{}", last_failure);
-            break false;
-        }
+    #[test]
+    fn static_const_reference_survives_the_repair() {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/output/in_out_lifetimes_tightest_bounds_first_repairer.rs"
+        );
+        // the fixture's function before the repair
+        let input = TempSource::new(
+            "const_w_input",
+            &fs::read_to_string(fixture).unwrap().replace(
+                "fn bar_extracted<'lt0>(x_ref: &'lt0 i32, z: &'lt0 i32, y: &'lt0 i32) -> &'lt0 i32",
+                "fn bar_extracted(x_ref: &i32, z: &i32, y: &i32) -> &i32",
+            ),
+        );
+        let file = TempSource::new("const_w", "");
+        let result = crate::repair_lifetime_tightest_bound_first::Repairer {}
+            .repair_function_with_config(
+                &input.0,
+                &file.0,
+                "bar_extracted",
+                &RepairConfig::default(),
+            );
+        assert!(result.success);
+        assert_eq!(
+            extract_function_source(&file.0, "bar_extracted").unwrap(),
+            extract_function_source(fixture, "bar_extracted").unwrap()
+        );
+        let compiled = compile_file(&file.0, &vec!["--error-format=json"])
+            .output()
+            .unwrap();
+        assert!(
+            compiled.status.success(),
+            "{}",
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+    }
 
-        if max_iterations == count {
-            debug!("last failure:\n{}", last_failure);
-            break false;
+    #[test]
+    fn constructed_diagnostics_are_repaired_without_json() {
+        let file = TempSource::new(
+            "constructed",
+            "fn bar_extracted(x: &i32, y: &i32) -> &i32 {\n    x\n}\n",
+        );
+        let error = |code: Option<&str>, rendered: &str| RustcError {
+            rendered: rendered.to_string(),
+            spans: vec![RustcSpan {
+                file_name: file.0.clone(),
+                byte_start: 38,
+                byte_end: 39,
+            }],
+            level: "error".to_string(),
+            code: code.map(|code| RustcCode {
+                code: code.to_string(),
+            }),
+        };
+        let missing = error(
+            Some("E0106"),
+            "error[E0106]: missing lifetime specifier
+help: consider introducing a named lifetime parameter
+  |
+1 | fn bar_extracted<'a>(x: &'a i32, y: &'a i32) -> &'a i32 {
+  |                 ++++     ++          ++          ++
+",
+        );
+        let config = RepairConfig::default();
+        assert!(repair_standard_help_from_errors(&[missing], &file.0, &config).unwrap());
+        let source = file.read();
+        assert!(
+            source.contains("fn bar_extracted<'a>(x: &'a i32, y: &'a i32) -> &'a i32"),
+            "{}",
+            source
+        );
+
+        let outlives = error(
+            None,
+            "error: lifetime may not live long enough\n  = help: consider adding the following bound: `'b: 'a`",
+        );
+        fs::write(
+            &file.0,
+            "fn bar_extracted<'a, 'b>(x: &'a i32, y: &'b i32) -> &'a i32 {\n    y\n}\n",
+        )
+        .unwrap();
+        assert!(
+            repair_bounds_help_from_errors(&[outlives], &file.0, "bar_extracted", &config).unwrap()
+        );
+        let source = file.read();
+        assert!(source.contains("where\n    'b: 'a,"), "{}", source);
+    }
+
+    #[test]
+    fn final_newlines_are_kept_by_the_repair() {
+        let error = diagnostic(
+            "error[E0106]: missing lifetime specifier
+help: consider introducing a named lifetime parameter
+  |
+1 | fn bar_extracted<'a>(x: &'a i32, y: &'a i32) -> &'a i32 {
+  |                 ++++     ++          ++          ++
+",
+        );
+        let unrepaired = "fn bar_extracted(x: &i32, y: &i32) -> &i32 {\n    x\n}";
+        let repaired = "fn bar_extracted<'a>(x: &'a i32, y: &'a i32) -> &'a i32 {\n    x\n}";
+        for ending in ["", "\n", "\n\n"] {
+            let file = TempSource::new("final_newline", &format!("{}{}", unrepaired, ending));
+            let helped = repair_standard_help_from_errors(
+                std::slice::from_ref(&error),
+                &file.0,
+                &RepairConfig::default(),
+            );
+            assert!(helped.unwrap());
+            assert_eq!(file.read(), format!("{}{}", repaired, ending));
+
+            let reformatted = format_source_like(&format!("{}{}", repaired, ending), unrepaired);
+            assert_eq!(reformatted, format!("{}{}", unrepaired, ending));
         }
-    };
+    }
+
+    #[test]
+    fn rename_and_elide_writes_once() {
+        let file = TempSource::new(
+            "rename_and_elide",
+            "fn bar_extracted____EXTRACT_THIS<'a>(x: &'a i32) -> &'a i32 {
+    x
+}
+fn main() {
+    assert_eq!(*bar_extracted____EXTRACT_THIS(&1), 1);
+}
+",
+        );
+        let writes = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&writes);
+        let config = RepairConfig::builder()
+            .on_write(move |_, source| {
+                recorded.lock().unwrap().push(source.to_string());
+                WriteDecision::Proceed
+            })
+            .build();
+        let result = rename_and_elide_with_config(
+            &file.0,
+            "bar_extracted",
+            "____EXTRACT_THIS",
+            &config,
+            None,
+        )
+        .unwrap();
+        assert!(result.found);
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(
+            writes[0],
+            "fn bar_extracted(x: &i32) -> &i32 {
+    x
+}
+fn main() {
+    assert_eq!(*bar_extracted(&1), 1);
+}
+"
+        );
+        assert_eq!(file.read(), writes[0]);
+    }
 
-    if print_stats {
-        info!("repair count: {}", count);
-        info!("status: {}", success);
+    #[test]
+    fn placeholder_lifetime_is_filled_in_when_enabled() {
+        let original = "fn bar_extracted<'lt0>(x: &'lt0 i32, y: &i32) -> &i32 {\n    y\n}\n";
+        let error = diagnostic(
+            "error[E0106]: missing lifetime specifier
+help: consider using the `'lifetime` lifetime
+  |
+1 | fn bar_extracted<'lt0>(x: &'lt0 i32, y: &'lifetime i32) -> &'lifetime i32 {
+  |                                           +++++++++           +++++++++
+",
+        );
+        let file = TempSource::new("placeholder", original);
+        let repaired = repair_standard_help_from_errors(
+            std::slice::from_ref(&error),
+            &file.0,
+            &RepairConfig::default(),
+        );
+        assert!(!repaired.unwrap());
+        assert_eq!(file.read(), original);
+
+        let config = RepairConfig::builder().fill_placeholders(true).build();
+        let repaired = repair_standard_help_from_errors(&[error], &file.0, &config);
+        assert!(repaired.unwrap());
+        let source = file.read();
+        assert!(
+            source
+                .contains("fn bar_extracted<'lt1, 'lt0>(x: &'lt0 i32, y: &'lt1 i32) -> &'lt1 i32"),
+            "{}",
+            source
+        );
     }
 
-    repair_result.success = success;
-    repair_result.repair_count = count;
-    repair_result
+    #[test]
+    fn depth_limit_leaves_the_nested_lifetime_alone() {
+        let source = "struct Foo<T>(T);
+struct Bar<T>(T);
+fn bar_extracted<'a, 'b>(x: &'a i32, nested: Foo<Bar<&'b i32>>) -> i32 {
+    *x
+}
+";
+        let elide = |name: &str, config: &RepairConfig| {
+            let file = TempSource::new(name, source);
+            elide_lifetimes_annotations_with_config(&file.0, "bar_extracted", config, None)
+                .unwrap();
+            file.read()
+        };
+        let limited = elide(
+            "depth_limited",
+            &RepairConfig::builder().max_elision_depth(1).build(),
+        );
+        assert!(
+            limited.contains("fn bar_extracted<'lt0>(x: &i32, nested: Foo<Bar<&'lt0 i32>>) -> i32"),
+            "{}",
+            limited
+        );
+        let unlimited = elide("depth_unlimited", &RepairConfig::default());
+        assert!(
+            unlimited.contains("fn bar_extracted(x: &i32, nested: Foo<Bar<&i32>>) -> i32"),
+            "{}",
+            unlimited
+        );
+    }
+
+    #[test]
+    fn edit_context_is_the_lines_around_it() {
+        let source = "struct Foo;\n\nfn bar_extracted(x: &i32) -> &i32 {\n    x\n}\n";
+        let insert = |at: usize, replacement: &str| RepairEdit {
+            file_name: "src/lib.rs".to_string(),
+            byte_start: at,
+            byte_end: at,
+            replacement: replacement.to_string(),
+            context_before: None,
+            context_after: None,
+        };
+        let at = source.find("(x").unwrap();
+        let edit = insert(at, "<'a>").with_context(source, 2);
+        assert_eq!(edit.context_before.as_deref(), Some("struct Foo;\n"));
+        assert_eq!(edit.context_after.as_deref(), Some("    x\n}"));
+        let edit = insert(at, "<'a>").with_context(source, 1);
+        assert_eq!(edit.context_before.as_deref(), Some(""));
+        assert_eq!(edit.context_after.as_deref(), Some("    x"));
+
+        // nothing before the first line
+        let edit = insert(0, "pub ").with_context(source, 3);
+        assert_eq!(edit.context_before.as_deref(), Some(""));
+        assert_eq!(
+            edit.context_after.as_deref(),
+            Some("\nfn bar_extracted(x: &i32) -> &i32 {\n    x")
+        );
+    }
+
+    #[test]
+    fn redirected_repair_never_writes_the_repaired_file() {
+        let original = "fn bar_extracted(x: &i32, y: &i32) -> &i32 {
+    x
+}
+
+fn main() {}
+";
+        let file = TempSource::new("redirect_repair_from", original);
+        let staged = TempSource::new("redirect_repair_to", "");
+        let target = PathBuf::from(&staged.0);
+        let writes = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&writes);
+        let config = RepairConfig::builder()
+            .max_iterations(2)
+            .on_write(move |_, source| {
+                recorded.lock().unwrap().push(source.to_string());
+                WriteDecision::Redirect(target.clone())
+            })
+            .build();
+        let repairer = crate::repair_lifetime_simple::Repairer {};
+        repairer.repair_function_with_config(&file.0, &file.0, "bar_extracted", &config);
+        assert_eq!(file.read(), original);
+        let writes = writes.lock().unwrap();
+        assert!(
+            writes.iter().any(|source| source.contains("-> &'a i32")),
+            "{:?}",
+            writes
+        );
+    }
+
+    #[test]
+    fn failed_repairs_of_matching_functions_leave_the_input() {
+        let source = "fn a_extracted(x: &i32, y: &i32) -> &i32 {
+    x
+}
+fn main() {}
+";
+        let input = TempSource::new("matching_failed_input", source);
+        let output = TempSource::new("matching_failed_output", "");
+        let pattern = Regex::new("_extracted$").unwrap();
+        // stops after the help is applied, before the compile that would confirm it
+        let config = RepairConfig::builder().max_iterations(1).build();
+        let results = repair_functions_matching(
+            &input.0,
+            &output.0,
+            &pattern,
+            &crate::repair_lifetime_simple::Repairer {},
+            &config,
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].1.success);
+        assert_eq!(output.read(), source);
+        assert!(!Path::new(&format!("{}.prev", output.0)).exists());
+    }
+
+    #[test]
+    fn streaming_leaves_the_stdio_of_the_command_alone() {
+        let file = TempSource::new("stream_stdio", "fn main() {}\n");
+        let mut compile = Command::new("sh");
+        compile.args(["-c", "echo warnings >&2"]);
+        let config = RepairConfig::builder().stream_diagnostics(true).build();
+        let result = repair_iteration_project(&mut compile, &file.0, &|_| false, &config);
+        assert!(result.success);
+        assert_eq!(compile.output().unwrap().stderr, b"warnings\n");
+    }
 }
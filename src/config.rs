@@ -0,0 +1,420 @@
+use std::fmt::{self, Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::common::{DiagnosticParser, IterationTrace};
+
+/// What to do with a file write the repair is about to make
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteDecision {
+    Proceed,
+    /// Leave the file as it is
+    Skip,
+    /// Write the contents to this path instead
+    Redirect(PathBuf),
+}
+
+/// How the elider writes a reference whose lifetime it elides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ElisionStyle {
+    /// `&'a T` becomes `&T`
+    #[default]
+    Remove,
+    /// `&'a T` becomes `&'_ T`, keeping the lifetime visible
+    Anonymize,
+}
+
+/// Edition of the repaired code, for the rules that differ between editions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Edition {
+    E2015,
+    E2018,
+    #[default]
+    E2021,
+    /// `impl Trait` in return position captures every lifetime in scope unless a `use<..>` clause
+    /// says otherwise
+    E2024,
+}
+
+impl Edition {
+    /// As written in a manifest (`2021`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Edition::E2015 => "2015",
+            Edition::E2018 => "2018",
+            Edition::E2021 => "2021",
+            Edition::E2024 => "2024",
+        }
+    }
+}
+
+/// Which methods the elider may select when `fn_name` names a trait method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MethodScope {
+    /// Declarations in traits and their impls, eliding an impl method also elides the declaration
+    /// it implements so the two stay in sync
+    #[default]
+    Both,
+    TraitDecls,
+    Impls,
+}
+
+/// Called with the target path and new contents before each write, see [`WriteDecision`]
+pub type WriteHook = Arc<dyn Fn(&Path, &str) -> WriteDecision + Send + Sync>;
+
+/**
+Names the n-th lifetime left declared on a function after elision, in place of `'lt{n}`
+
+Those are the lifetimes elision can't express, so the name has to be one a lifetime can be declared
+with and `'_` can't be forced: `'_`, `'static`, a name given to an earlier lifetime of the function
+and one the enclosing impl declares fall back to `'lt{n}`, or the next `'lt{k}` that is free
+*/
+pub type LifetimeNamePolicy = Arc<dyn Fn(usize) -> String + Send + Sync>;
+
+/// Called with the trace of each iteration of a project repair as it finishes, to show progress.
+/// Its `reduced_errors` is still `None` then, the next compile decides it
+pub type IterationHook = Arc<dyn Fn(&IterationTrace) + Send + Sync>;
+
+/**
+Options shared by the repair loops, constructed through [`RepairConfig::builder`]
+
+Defaults match the behaviour of the loops before they were configurable, so callers only need to
+set the options they care about
+*/
+#[derive(Clone)]
+pub struct RepairConfig {
+    /// Give up after this many failed compiles
+    pub max_iterations: i32,
+    /// Stop handing the errors of a code to the passes of a project repair once they were tried
+    /// this many times and the code is still reported, `None` keeps trying until `max_iterations`
+    pub max_attempts_per_code: Option<usize>,
+    /// Restore the repaired file (`src_path` of the project loops, the copy
+    /// `default_repair_function` works on) when it still doesn't compile in the end, whether the
+    /// loop ran out of `max_iterations` or of repairs
+    pub revert_if_not_converged: bool,
+    /// Program and arguments run once a repair compiles, e.g. `cargo test --manifest-path ...`.
+    /// If it fails the repair counts as failed and the file is restored, whatever
+    /// `revert_if_not_converged` says: the code compiles but the repair changed what it does
+    pub test_command: Option<Vec<String>>,
+    /// Log the repair count and status once the loop finishes
+    pub print_stats: bool,
+    /// Copy the file to `iter_{n}.rs` in this directory after every applied repair
+    pub snapshot_dir: Option<PathBuf>,
+    /// Where `repair_in_sandbox` creates its temporary crates, `None` uses the system's temp dir
+    /// (`TMPDIR` on unix)
+    pub sandbox_dir: Option<PathBuf>,
+    /// Prefix for log lines, to tell concurrent repairs apart
+    pub label: String,
+    /// Worker threads for batch repairs, `None` uses one per core
+    pub threads: Option<usize>,
+    /// Lifetimes the elider removes even when they are used more than once, e.g. `'lt0` left by
+    /// an earlier run
+    pub force_elide: Vec<String>,
+    /// Whether elided references drop their lifetime or get `'_`
+    pub elision_style: ElisionStyle,
+    /// Whether elision applies to trait declarations, impls or both
+    pub method_scope: MethodScope,
+    /// How deep into a type the elider may reach: 0 only touches lifetimes written on the
+    /// parameter or return type itself (`&'a T`, `Foo<'a>`), every type it is nested in adds one
+    /// (`'a` of `Foo<Bar<&'a T>>` is at 2). Deeper lifetimes stay named, even in `force_elide`,
+    /// `None` elides at any depth
+    pub max_elision_depth: Option<usize>,
+    /// Names the lifetimes that survive elision, `None` numbers them `'lt0`, `'lt1`, ...
+    pub name_policy: Option<LifetimeNamePolicy>,
+    /// The cfg options the repair compile enables (`test`, `unix`, `feature = "x"`), written as in
+    /// `#[cfg]`. Functions behind a cfg that doesn't hold are left alone, `None` leaves every
+    /// function behind a cfg alone as nothing confirms the compile included it
+    pub active_cfg: Option<Vec<String>>,
+    /// Repair every function a bare name selects instead of refusing an ambiguous target
+    pub apply_to_all: bool,
+    /// Skip the line based suggestions that would change a function body, leaving the edits to
+    /// the passes that rewrite signatures. Unifying two lifetimes still renames the dropped one
+    /// where the body names it
+    pub signatures_only: bool,
+    /// Apply suggestions that use rustc's `'lifetime` placeholder with a fresh lifetime in its place,
+    /// declared on the function whose signature it ends up in, instead of skipping them
+    pub fill_placeholders: bool,
+    /// Text of the line comments (`EXTRACTED` for `// EXTRACTED`) that mark functions, put back on
+    /// them after the repair reprints the file without comments
+    pub preserve_markers: Option<String>,
+    /// Consulted before every write the repair makes, `None` writes in place
+    pub on_write: Option<WriteHook>,
+    /// Told about every iteration of a project repair, see [`IterationHook`]
+    pub on_iteration: Option<IterationHook>,
+    /// Reads the compiler output in the iteration loops, `None` expects rustc's json
+    pub diagnostic_parser: Option<Arc<dyn DiagnosticParser>>,
+    /// Parse each diagnostic of a project compile as cargo prints it instead of once the compile
+    /// is done, the loop sorts out the errors in the target while the rest of a long compile runs.
+    /// The parser gets one line at a time
+    pub stream_diagnostics: bool,
+    /// Edition the repaired code is compiled with, deciding how a hidden lifetime capture is
+    /// repaired and the edition of the crate `repair_in_sandbox` creates. The compile commands are
+    /// left as they are
+    pub edition: Edition,
+}
+
+impl Default for RepairConfig {
+    fn default() -> Self {
+        RepairConfig {
+            max_iterations: 25,
+            max_attempts_per_code: None,
+            revert_if_not_converged: false,
+            test_command: None,
+            print_stats: true,
+            snapshot_dir: None,
+            sandbox_dir: None,
+            label: String::new(),
+            threads: None,
+            force_elide: vec![],
+            elision_style: ElisionStyle::Remove,
+            method_scope: MethodScope::Both,
+            max_elision_depth: None,
+            name_policy: None,
+            active_cfg: None,
+            apply_to_all: false,
+            signatures_only: false,
+            fill_placeholders: false,
+            preserve_markers: None,
+            on_write: None,
+            on_iteration: None,
+            diagnostic_parser: None,
+            stream_diagnostics: false,
+            edition: Edition::E2021,
+        }
+    }
+}
+
+impl Debug for RepairConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RepairConfig")
+            .field("max_iterations", &self.max_iterations)
+            .field("max_attempts_per_code", &self.max_attempts_per_code)
+            .field("revert_if_not_converged", &self.revert_if_not_converged)
+            .field("test_command", &self.test_command)
+            .field("print_stats", &self.print_stats)
+            .field("snapshot_dir", &self.snapshot_dir)
+            .field("sandbox_dir", &self.sandbox_dir)
+            .field("label", &self.label)
+            .field("threads", &self.threads)
+            .field("force_elide", &self.force_elide)
+            .field("elision_style", &self.elision_style)
+            .field("method_scope", &self.method_scope)
+            .field("max_elision_depth", &self.max_elision_depth)
+            .field("name_policy", &self.name_policy.is_some())
+            .field("active_cfg", &self.active_cfg)
+            .field("apply_to_all", &self.apply_to_all)
+            .field("signatures_only", &self.signatures_only)
+            .field("fill_placeholders", &self.fill_placeholders)
+            .field("preserve_markers", &self.preserve_markers)
+            .field("on_write", &self.on_write.is_some())
+            .field("on_iteration", &self.on_iteration.is_some())
+            .field("diagnostic_parser", &self.diagnostic_parser.is_some())
+            .field("stream_diagnostics", &self.stream_diagnostics)
+            .field("edition", &self.edition)
+            .finish()
+    }
+}
+
+impl RepairConfig {
+    pub fn builder() -> RepairConfigBuilder {
+        RepairConfigBuilder::default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RepairConfigBuilder {
+    config: RepairConfig,
+}
+
+impl RepairConfigBuilder {
+    pub fn max_iterations(mut self, max_iterations: i32) -> Self {
+        self.config.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn max_attempts_per_code(mut self, max_attempts_per_code: usize) -> Self {
+        self.config.max_attempts_per_code = Some(max_attempts_per_code);
+        self
+    }
+
+    pub fn revert_if_not_converged(mut self, revert_if_not_converged: bool) -> Self {
+        self.config.revert_if_not_converged = revert_if_not_converged;
+        self
+    }
+
+    pub fn test_command(mut self, command: &[&str]) -> Self {
+        self.config.test_command = Some(command.iter().map(|arg| arg.to_string()).collect());
+        self
+    }
+
+    pub fn print_stats(mut self, print_stats: bool) -> Self {
+        self.config.print_stats = print_stats;
+        self
+    }
+
+    pub fn snapshot_dir(mut self, snapshot_dir: impl Into<PathBuf>) -> Self {
+        self.config.snapshot_dir = Some(snapshot_dir.into());
+        self
+    }
+
+    pub fn sandbox_dir(mut self, sandbox_dir: impl Into<PathBuf>) -> Self {
+        self.config.sandbox_dir = Some(sandbox_dir.into());
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.config.label = label.into();
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = Some(threads);
+        self
+    }
+
+    pub fn force_elide(mut self, lifetimes: &[&str]) -> Self {
+        self.config.force_elide = lifetimes.iter().map(|lt| lt.to_string()).collect();
+        self
+    }
+
+    pub fn elision_style(mut self, elision_style: ElisionStyle) -> Self {
+        self.config.elision_style = elision_style;
+        self
+    }
+
+    pub fn method_scope(mut self, method_scope: MethodScope) -> Self {
+        self.config.method_scope = method_scope;
+        self
+    }
+
+    pub fn max_elision_depth(mut self, max_elision_depth: usize) -> Self {
+        self.config.max_elision_depth = Some(max_elision_depth);
+        self
+    }
+
+    pub fn name_policy(
+        mut self,
+        name_policy: impl Fn(usize) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.config.name_policy = Some(Arc::new(name_policy));
+        self
+    }
+
+    pub fn active_cfg(mut self, cfgs: &[&str]) -> Self {
+        self.config.active_cfg = Some(cfgs.iter().map(|cfg| cfg.to_string()).collect());
+        self
+    }
+
+    pub fn apply_to_all(mut self, apply_to_all: bool) -> Self {
+        self.config.apply_to_all = apply_to_all;
+        self
+    }
+
+    pub fn signatures_only(mut self, signatures_only: bool) -> Self {
+        self.config.signatures_only = signatures_only;
+        self
+    }
+
+    pub fn fill_placeholders(mut self, fill_placeholders: bool) -> Self {
+        self.config.fill_placeholders = fill_placeholders;
+        self
+    }
+
+    pub fn preserve_markers(mut self, marker: impl Into<String>) -> Self {
+        self.config.preserve_markers = Some(marker.into());
+        self
+    }
+
+    pub fn on_write(
+        mut self,
+        on_write: impl Fn(&Path, &str) -> WriteDecision + Send + Sync + 'static,
+    ) -> Self {
+        self.config.on_write = Some(Arc::new(on_write));
+        self
+    }
+
+    pub fn on_iteration(
+        mut self,
+        on_iteration: impl Fn(&IterationTrace) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.on_iteration = Some(Arc::new(on_iteration));
+        self
+    }
+
+    pub fn diagnostic_parser(mut self, parser: impl DiagnosticParser + 'static) -> Self {
+        self.config.diagnostic_parser = Some(Arc::new(parser));
+        self
+    }
+
+    pub fn stream_diagnostics(mut self, stream_diagnostics: bool) -> Self {
+        self.config.stream_diagnostics = stream_diagnostics;
+        self
+    }
+
+    pub fn edition(mut self, edition: Edition) -> Self {
+        self.config.edition = edition;
+        self
+    }
+
+    pub fn build(self) -> RepairConfig {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_every_field() {
+        let config = RepairConfig::builder()
+            .max_iterations(3)
+            .max_attempts_per_code(2)
+            .revert_if_not_converged(true)
+            .test_command(&["cargo", "test"])
+            .print_stats(false)
+            .snapshot_dir("snapshots")
+            .sandbox_dir("sandboxes")
+            .label("job")
+            .threads(4)
+            .force_elide(&["'lt0"])
+            .elision_style(ElisionStyle::Anonymize)
+            .method_scope(MethodScope::Impls)
+            .max_elision_depth(1)
+            .name_policy(|n| format!("'r{}", n))
+            .active_cfg(&["test"])
+            .apply_to_all(true)
+            .signatures_only(true)
+            .fill_placeholders(true)
+            .preserve_markers("EXTRACTED")
+            .on_write(|_, _| WriteDecision::Skip)
+            .on_iteration(|_| ())
+            .stream_diagnostics(true)
+            .edition(Edition::E2024)
+            .build();
+        assert_eq!(config.max_iterations, 3);
+        assert_eq!(config.max_attempts_per_code, Some(2));
+        assert!(config.revert_if_not_converged);
+        assert_eq!(config.test_command.unwrap(), ["cargo", "test"]);
+        assert!(!config.print_stats);
+        assert_eq!(config.snapshot_dir.unwrap(), Path::new("snapshots"));
+        assert_eq!(config.sandbox_dir.unwrap(), Path::new("sandboxes"));
+        assert_eq!(config.label, "job");
+        assert_eq!(config.threads, Some(4));
+        assert_eq!(config.force_elide, ["'lt0"]);
+        assert_eq!(config.elision_style, ElisionStyle::Anonymize);
+        assert_eq!(config.method_scope, MethodScope::Impls);
+        assert_eq!(config.max_elision_depth, Some(1));
+        assert_eq!(config.name_policy.unwrap()(2), "'r2");
+        assert_eq!(config.active_cfg.unwrap(), ["test"]);
+        assert!(config.apply_to_all);
+        assert!(config.signatures_only);
+        assert!(config.fill_placeholders);
+        assert_eq!(config.preserve_markers.as_deref(), Some("EXTRACTED"));
+        let decision = config.on_write.unwrap()(Path::new("a.rs"), "");
+        assert_eq!(decision, WriteDecision::Skip);
+        assert!(config.on_iteration.is_some());
+        assert!(config.stream_diagnostics);
+        assert_eq!(config.edition, Edition::E2024);
+    }
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::process::Command;
+
+/// Failure of a repair API that an embedder can handle instead of a panic
+#[derive(Debug)]
+pub enum RepairError {
+    /// Source that syn cannot parse, `file` is `None` for source given as a string and `fn_name`
+    /// the function being repaired, if any
+    Parse {
+        file: Option<String>,
+        fn_name: Option<String>,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Io {
+        file: String,
+        source: io::Error,
+    },
+    /// The target name selects several functions, `candidates` are their `file:line:column`
+    AmbiguousFunction {
+        fn_name: String,
+        candidates: Vec<String>,
+    },
+    /// The compiler reported on `compiled` but not on the edited `file`
+    NotCompiled {
+        file: String,
+        compiled: Vec<String>,
+    },
+    /// The compile `command` could not be run or waited on
+    Command {
+        command: String,
+        source: io::Error,
+    },
+}
+
+impl RepairError {
+    pub fn parse(file: Option<&str>, error: &syn::Error) -> Self {
+        let start = error.span().start();
+        RepairError::Parse {
+            file: file.map(str::to_string),
+            fn_name: None,
+            line: start.line,
+            column: start.column,
+            message: error.to_string(),
+        }
+    }
+
+    pub fn io(file: &str, source: io::Error) -> Self {
+        RepairError::Io {
+            file: file.to_string(),
+            source,
+        }
+    }
+
+    pub fn command(command: &Command, source: io::Error) -> Self {
+        let command = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        RepairError::Command { command, source }
+    }
+
+    /// The error naming `fn_name` as the function being repaired when it came up, for a parse error
+    pub fn in_function(self, fn_name: &str) -> Self {
+        match self {
+            RepairError::Parse {
+                file,
+                line,
+                column,
+                message,
+                ..
+            } if !fn_name.is_empty() => RepairError::Parse {
+                file,
+                fn_name: Some(fn_name.to_string()),
+                line,
+                column,
+                message,
+            },
+            e => e,
+        }
+    }
+}
+
+impl Display for RepairError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairError::Parse {
+                file,
+                fn_name,
+                line,
+                column,
+                message,
+            } => {
+                write!(
+                    f,
+                    "cannot parse {}:{}:{}",
+                    file.as_deref().unwrap_or("<source>"),
+                    line,
+                    column + 1
+                )?;
+                if let Some(fn_name) = fn_name {
+                    write!(f, " repairing `{}`", fn_name)?;
+                }
+                write!(f, ": {}", message)
+            }
+            RepairError::Io { file, source } => write!(f, "cannot access {}: {}", file, source),
+            RepairError::AmbiguousFunction {
+                fn_name,
+                candidates,
+            } => write!(
+                f,
+                "`{}` is ambiguous, it selects {}",
+                fn_name,
+                candidates.join(", ")
+            ),
+            RepairError::NotCompiled { file, compiled } => write!(
+                f,
+                "the compiler reported on {} but not on {}",
+                compiled.join(", "),
+                file
+            ),
+            RepairError::Command { command, source } => {
+                write!(f, "cannot run `{}`: {}", command, source)
+            }
+        }
+    }
+}
+
+impl Error for RepairError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RepairError::Io { source, .. } | RepairError::Command { source, .. } => Some(source),
+            RepairError::Parse { .. }
+            | RepairError::AmbiguousFunction { .. }
+            | RepairError::NotCompiled { .. } => None,
+        }
+    }
+}
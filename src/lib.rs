@@ -1,5 +1,11 @@
+pub mod command;
 pub mod common;
+pub mod config;
+pub mod error;
+#[cfg(feature = "parallel")]
+pub mod parallel;
 pub mod repair_lifetime_loosest_bound_first;
 pub mod repair_lifetime_simple;
 pub mod repair_lifetime_tightest_bound_first;
-pub mod repair_rustfix;
\ No newline at end of file
+pub mod repair_rustfix;
+pub mod sandbox;
\ No newline at end of file
@@ -5,15 +5,12 @@ use std::iter::zip;
 use std::process::exit;
 use std::time::SystemTime;
 
-mod common;
-mod repair_lifetime_loosest_bound_first;
-mod repair_lifetime_simple;
-mod repair_lifetime_tightest_bound_first;
-mod repair_rustfix;
-
-use crate::common::RepairResult;
 use crate::RepairerType::{LoosestBoundsFirst, TightestBoundsFirst};
-use common::RepairSystem;
+use rem_repairer::common::{RepairResult, RepairSystem};
+use rem_repairer::{
+    repair_lifetime_loosest_bound_first, repair_lifetime_simple,
+    repair_lifetime_tightest_bound_first,
+};
 
 #[derive(Parser)]
 struct Cli {
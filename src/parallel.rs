@@ -0,0 +1,185 @@
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::collections::HashMap;
+
+use crate::common::{RepairResult, RepairSystem};
+use crate::config::RepairConfig;
+
+/// A single function repair, run as
+/// `repair_system.repair_function_with_config(file_name, new_file_name, fn_name, config)`
+#[derive(Debug)]
+pub struct RepairJob {
+    pub repair_system: Box<dyn RepairSystem + Send + Sync>,
+    pub file_name: String,
+    pub new_file_name: String,
+    pub fn_name: String,
+}
+
+impl RepairJob {
+    fn run(&self, config: &RepairConfig) -> RepairResult {
+        self.repair_system.repair_function_with_config(
+            &self.file_name,
+            &self.new_file_name,
+            &self.fn_name,
+            config,
+        )
+    }
+}
+
+/**
+Run independent function repairs concurrently, returning the results in the order of `jobs`
+
+Every job runs under `config` and compiles its own file, so jobs only interfere when one
+writes a file another reads or writes: jobs sharing a `file_name` or `new_file_name` are
+grouped and run one after the other, while the groups run in parallel on `config.threads`
+workers
+*/
+pub fn repair_functions_parallel(jobs: Vec<RepairJob>, config: &RepairConfig) -> Vec<RepairResult> {
+    // union the jobs touching a common path, each group is keyed by its lowest job index
+    let mut leader: Vec<usize> = (0..jobs.len()).collect();
+    let mut job_of_file: HashMap<&str, usize> = HashMap::new();
+    for (index, job) in jobs.iter().enumerate() {
+        for path in [job.file_name.as_str(), job.new_file_name.as_str()] {
+            let other = *job_of_file.entry(path).or_insert(index);
+            let (a, b) = (
+                find_leader(&mut leader, index),
+                find_leader(&mut leader, other),
+            );
+            leader[a.max(b)] = a.min(b);
+        }
+    }
+    let leaders: Vec<usize> = (0..jobs.len())
+        .map(|index| find_leader(&mut leader, index))
+        .collect();
+
+    let mut groups: Vec<Vec<(usize, RepairJob)>> = vec![];
+    let mut group_of_leader: HashMap<usize, usize> = HashMap::new();
+    for ((index, job), leader) in jobs.into_iter().enumerate().zip(leaders) {
+        let group = *group_of_leader.entry(leader).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push((index, job));
+    }
+
+    let run = || {
+        groups
+            .into_par_iter()
+            .flat_map_iter(|group| {
+                group
+                    .into_iter()
+                    .map(|(index, job)| (index, job.run(config)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut results = match config.threads {
+        None => run(),
+        Some(threads) => ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(run),
+    };
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn find_leader(leader: &mut [usize], mut index: usize) -> usize {
+    while leader[index] != index {
+        leader[index] = leader[leader[index]];
+        index = leader[index];
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    static RUNS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    // reports the function it repaired, the first job (and producer) finishes last
+    #[derive(Debug, Clone)]
+    struct Scripted;
+
+    impl RepairSystem for Scripted {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn repair_project_with_config(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+            _: &RepairConfig,
+        ) -> RepairResult {
+            RepairResult::failed()
+        }
+
+        fn repair_file(&self, _: &str, _: &str) -> RepairResult {
+            RepairResult::failed()
+        }
+
+        fn repair_function_with_config(
+            &self,
+            _: &str,
+            new_file_name: &str,
+            fn_name: &str,
+            _: &RepairConfig,
+        ) -> RepairResult {
+            RUNS.lock().unwrap().push(format!("start {fn_name}"));
+            if fn_name == "first" || fn_name == "produce" {
+                thread::sleep(Duration::from_millis(100));
+            }
+            RUNS.lock().unwrap().push(format!("end {fn_name}"));
+            let mut result = RepairResult::failed();
+            result.success = true;
+            result
+                .final_signatures
+                .insert(fn_name.to_string(), new_file_name.to_string());
+            result
+        }
+
+        fn clone_box(&self) -> Box<dyn RepairSystem> {
+            Box::new(self.clone())
+        }
+    }
+
+    fn job(new_file_name: &str, fn_name: &str) -> RepairJob {
+        RepairJob {
+            repair_system: Box::new(Scripted),
+            file_name: new_file_name.to_string(),
+            new_file_name: new_file_name.to_string(),
+            fn_name: fn_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn results_are_in_the_order_of_the_jobs() {
+        let jobs = vec![job("a.rs", "first"), job("b.rs", "second")];
+        let config = RepairConfig::builder().threads(2).build();
+        let results = repair_functions_parallel(jobs, &config);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.success));
+        assert_eq!(results[0].final_signatures["first"], "a.rs");
+        assert_eq!(results[1].final_signatures["second"], "b.rs");
+    }
+
+    #[test]
+    fn job_reading_another_jobs_output_waits_for_it() {
+        let mut reader = job("chained.rs", "consume");
+        reader.file_name = "chain_out.rs".to_string();
+        let jobs = vec![job("chain_out.rs", "produce"), reader];
+        let config = RepairConfig::builder().threads(2).build();
+        let results = repair_functions_parallel(jobs, &config);
+        assert!(results.iter().all(|result| result.success));
+        let runs = RUNS.lock().unwrap();
+        let position = |run: &str| runs.iter().position(|logged| logged == run).unwrap();
+        assert!(position("end produce") < position("start consume"));
+    }
+}
@@ -1,20 +1,24 @@
 use log::debug;
 use proc_macro2::Span;
-use quote::ToTokens;
 use std::borrow::BorrowMut;
-use std::fs;
+use std::cell::Cell;
+use std::process::Command;
 use syn::{
     visit_mut::VisitMut, AngleBracketedGenericArguments, FnArg, GenericArgument, ImplItemMethod,
-    Lifetime, LifetimeDef, PathArguments, ReturnType, Signature, TraitItemMethod, Type,
-    TypeParamBound,
+    ItemImpl, ItemMod, ItemTrait, Lifetime, LifetimeDef, PathArguments, ReturnType, Signature,
+    TraitItemMethod, Type, TypeParamBound,
 };
 
 use crate::common::{
-    callee_renamer, elide_lifetimes_annotations, repair_bounds_help, repair_iteration,
-    repair_iteration_project, RepairResult, RepairSystem, RustcError,
+    default_repair_function_with_steps, default_repair_project,
+    elide_lifetimes_annotations_with_config, format_source_like, has_fixed_signature, print_source,
+    read_target, rename_and_elide_with_config, repair_bounds_help_with_config,
+    repair_note_bounds_with_config, write_file, FnSelector, RepairPass, RepairResult, RepairSteps,
+    RepairSystem, RustcError, Scope,
 };
+use crate::config::RepairConfig;
+use crate::error::RepairError;
 use crate::repair_lifetime_simple;
-use rem_utils::{check_project, compile_file, format_source};
 
 #[derive(Debug, Clone)]
 pub struct Repairer {}
@@ -24,72 +28,101 @@ impl RepairSystem for Repairer {
         "_loosest_bounds_first_repairer"
     }
 
-    fn repair_project(&self, src_path: &str, manifest_path: &str, fn_name: &str) -> RepairResult {
-        let annot_res = annotate_loose_named_lifetime(src_path, fn_name);
-        if !annot_res.success {
-            return RepairResult {
-                success: false,
-                repair_count: 0,
-                has_non_elidible_lifetime: false,
-                has_struct_lt: false,
-            };
-        }
-        // println!("annotated: {}", fs::read_to_string(&src_path).unwrap());
-        let mut compile_cmd = check_project(manifest_path, &vec![]);
-        let process_errors =
-            |ce: &RustcError| repair_bounds_help(ce.rendered.as_str(), src_path, fn_name);
-        match repair_iteration_project(&mut compile_cmd, src_path, &process_errors, true, Some(50))
-        {
-            RepairResult {
-                success: true,
-                repair_count,
-                ..
-            } => {
-                debug!("pre elision: {}", fs::read_to_string(&src_path).unwrap());
-                let elide_res = elide_lifetimes_annotations(src_path, fn_name);
-                callee_renamer(src_path, fn_name);
-                RepairResult {
-                    success: true,
-                    repair_count,
-                    has_non_elidible_lifetime: elide_res.annotations_left,
-                    has_struct_lt: elide_res.has_struct_lt || annot_res.has_struct_lt,
-                }
-            }
-            result => result,
-        }
+    fn default_config(&self, label: &str) -> RepairConfig {
+        RepairConfig::builder()
+            .max_iterations(50)
+            .label(label)
+            .build()
+    }
+
+    fn repair_project_with_config(
+        &self,
+        src_path: &str,
+        manifest_path: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult {
+        let annotated_struct_lt = Cell::new(false);
+        let prepare = |src_path: &str| {
+            let annot_res = annotate_loose_named_lifetime(src_path, fn_name)?;
+            annotated_struct_lt.set(annot_res.has_struct_lt);
+            Ok(annot_res.success)
+        };
+        let finish = |src_path: &str, compile_cmd: &mut Command, result: &mut RepairResult| {
+            let (pre_elision, _) = read_target(src_path, fn_name)?;
+            debug!("[{}] pre elision: {}", config.label, pre_elision);
+            let elide_res = rename_and_elide_with_config(
+                src_path,
+                fn_name,
+                "____EXTRACT_THIS",
+                config,
+                Some(compile_cmd),
+            )?;
+            elide_res.report_annotations_left(&config.label);
+            result.has_non_elidible_lifetime = elide_res.annotations_left;
+            result.has_struct_lt = elide_res.has_struct_lt || annotated_struct_lt.get();
+            Ok(())
+        };
+        let steps = RepairSteps {
+            prepare: &prepare,
+            finish: &finish,
+        };
+        let bounds = |ce: &RustcError| {
+            repair_bounds_help_with_config(ce.rendered.as_str(), src_path, fn_name, config)
+        };
+        let notes = |ce: &RustcError| {
+            repair_note_bounds_with_config(ce.rendered.as_str(), src_path, fn_name, config)
+        };
+        let passes = bound_passes(&bounds, &notes);
+        default_repair_project(src_path, manifest_path, fn_name, &passes, &steps, config)
     }
 
     fn repair_file(&self, file_name: &str, new_file_name: &str) -> RepairResult {
         repair_lifetime_simple::Repairer {}.repair_file(file_name, new_file_name)
     }
 
-    fn repair_function(&self, file_name: &str, new_file_name: &str, fn_name: &str) -> RepairResult {
-        fs::copy(file_name, &new_file_name).unwrap();
-        annotate_loose_named_lifetime(&new_file_name, fn_name);
-        // println!("annotated: {}", fs::read_to_string(&new_file_name).unwrap());
-        let args: Vec<&str> = vec!["--error-format=json"];
-
-        let mut compile_cmd = compile_file(&new_file_name, &args);
-
-        let process_errors = |stderr: &str| repair_bounds_help(stderr, new_file_name, fn_name);
-
-        match repair_iteration(&mut compile_cmd, &process_errors, true, Some(50)) {
-            RepairResult {
-                success: true,
-                repair_count,
-                ..
-            } => {
-                // println!("repaired: {}", fs::read_to_string(&new_file_name).unwrap());
-                let elide_res = elide_lifetimes_annotations(new_file_name, fn_name);
-                RepairResult {
-                    success: true,
-                    repair_count,
-                    has_non_elidible_lifetime: elide_res.annotations_left,
-                    has_struct_lt: elide_res.has_struct_lt,
-                }
-            }
-            result => result,
-        }
+    fn repair_function_with_config(
+        &self,
+        file_name: &str,
+        new_file_name: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult {
+        let prepare = |new_file_name: &str| {
+            annotate_loose_named_lifetime(new_file_name, fn_name)?;
+            Ok(true)
+        };
+        let finish = |new_file_name: &str, compile_cmd: &mut Command, result: &mut RepairResult| {
+            let elide_res = elide_lifetimes_annotations_with_config(
+                new_file_name,
+                fn_name,
+                config,
+                Some(compile_cmd),
+            )?;
+            elide_res.report_annotations_left(&config.label);
+            result.has_non_elidible_lifetime = elide_res.annotations_left;
+            result.has_struct_lt = elide_res.has_struct_lt;
+            Ok(())
+        };
+        let steps = RepairSteps {
+            prepare: &prepare,
+            finish: &finish,
+        };
+        let bounds = |ce: &RustcError| {
+            repair_bounds_help_with_config(ce.rendered.as_str(), new_file_name, fn_name, config)
+        };
+        let notes = |ce: &RustcError| {
+            repair_note_bounds_with_config(ce.rendered.as_str(), new_file_name, fn_name, config)
+        };
+        let passes = bound_passes(&bounds, &notes);
+        default_repair_function_with_steps(
+            file_name,
+            new_file_name,
+            fn_name,
+            &passes,
+            &steps,
+            config,
+        )
     }
 
     fn clone_box(&self) -> Box<dyn RepairSystem> {
@@ -97,6 +130,25 @@ impl RepairSystem for Repairer {
     }
 }
 
+// the bounds rustc suggests first, those read off the notes only when there are none
+fn bound_passes<'a>(
+    bounds: &'a dyn Fn(&RustcError) -> Result<bool, RepairError>,
+    notes: &'a dyn Fn(&RustcError) -> Result<bool, RepairError>,
+) -> [RepairPass<'a>; 2] {
+    [
+        RepairPass {
+            name: "bounds",
+            matches: &|_| true,
+            repair: bounds,
+        },
+        RepairPass {
+            name: "notes",
+            matches: &|_| true,
+            repair: notes,
+        },
+    ]
+}
+
 struct LooseLifetimeAnnotatorTypeHelper {
     lt_num: i32,
     has_struct_lt: bool,
@@ -233,35 +285,60 @@ impl VisitMut for LooseLifetimeAnnotatorFnArgHelper {
 
 struct LooseLifetimeAnnotator<'a> {
     fn_name: &'a str,
+    scope: Scope<'static>,
     lt_num: i32,
     success: bool,
     has_struct_lt: bool,
 }
 
 impl VisitMut for LooseLifetimeAnnotator<'_> {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        let outer = self.scope.enter_impl(i);
+        syn::visit_mut::visit_item_impl_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        let outer = self.scope.enter_trait(i);
+        syn::visit_mut::visit_item_trait_mut(self, i);
+        self.scope.leave(outer);
+    }
+
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
     fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
-        let id = i.sig.ident.to_string();
-        //println!("caller name: {}, at: {}", self.caller_fn_name, &id);
-        match id == self.fn_name.to_string() {
+        let enclosing = self.scope.enclosing();
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, enclosing) {
             false => (),
+            // left as it is, like a function without inputs
+            true if has_fixed_signature(&i.attrs, &i.sig, enclosing) => self.success = true,
             true => self.loose_lifetime_annotator(&mut i.sig),
         }
         syn::visit_mut::visit_impl_item_method_mut(self, i);
     }
 
     fn visit_item_fn_mut(&mut self, i: &mut syn::ItemFn) {
-        let id = i.sig.ident.to_string();
-        match id == self.fn_name.to_string() {
+        let scope = self.scope.free_fn();
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, scope.as_deref()) {
             false => (),
+            true if has_fixed_signature(&i.attrs, &i.sig, scope.as_deref()) => self.success = true,
             true => self.loose_lifetime_annotator(&mut i.sig),
         }
+        let outer = self.scope.enter(scope, &[]);
+        syn::visit_mut::visit_item_fn_mut(self, i);
+        self.scope.leave(outer);
     }
 
     fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
-        let id = i.sig.ident.to_string();
-        //println!("caller name: {}, at: {}", self.caller_fn_name, &id);
-        match id == self.fn_name.to_string() {
+        let enclosing = self.scope.enclosing();
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, enclosing) {
             false => (),
+            // left as it is, like a function without inputs
+            true if has_fixed_signature(&i.attrs, &i.sig, enclosing) => self.success = true,
             true => self.loose_lifetime_annotator(&mut i.sig),
         }
         syn::visit_mut::visit_trait_item_method_mut(self, i);
@@ -329,29 +406,26 @@ struct AnnotationResult {
     has_struct_lt: bool,
 }
 
-fn annotate_loose_named_lifetime(new_file_name: &str, fn_name: &str) -> AnnotationResult {
-    let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-    let mut file = syn::parse_str::<syn::File>(file_content.as_str())
-        .map_err(|e| format!("{:?}", e))
-        .unwrap();
+fn annotate_loose_named_lifetime(
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<AnnotationResult, RepairError> {
+    let (file_content, mut file) = read_target(new_file_name, fn_name)?;
     let mut visit = LooseLifetimeAnnotator {
         fn_name,
+        scope: Scope::new(None),
         success: false,
         has_struct_lt: false,
         lt_num: 0,
     };
     visit.visit_file_mut(&mut file);
-    let file = file.into_token_stream().to_string();
-    let success = match visit.success {
-        true => {
-            fs::write(new_file_name.to_string(), format_source(&file)).unwrap();
-            true
-        }
-        false => false,
-    };
+    let file = print_source(&file);
+    if visit.success {
+        write_file(new_file_name, &format_source_like(&file_content, &file))?;
+    }
 
-    AnnotationResult {
-        success,
+    Ok(AnnotationResult {
+        success: visit.success,
         has_struct_lt: visit.has_struct_lt,
-    }
+    })
 }
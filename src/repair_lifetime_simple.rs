@@ -1,9 +1,8 @@
-use std::fs;
-
 use crate::common::{
-    repair_bounds_help, repair_iteration, repair_standard_help, RepairResult, RepairSystem,
+    default_repair_function, repair_bounds_help_with_config, repair_standard_help_with_config,
+    RepairPass, RepairResult, RepairSystem, RustcError,
 };
-use rem_utils::compile_file;
+use crate::config::RepairConfig;
 
 #[derive(Debug, Clone)]
 pub struct Repairer {}
@@ -13,36 +12,51 @@ impl RepairSystem for Repairer {
         "_simple_repairer"
     }
 
-    fn repair_project(
+    fn repair_project_with_config(
         &self,
         _src_path: &str,
         _manifest_path: &str,
         _fn_name: &str,
+        _config: &RepairConfig,
     ) -> RepairResult {
-        RepairResult {
-            success: false,
-            repair_count: 0,
-            has_non_elidible_lifetime: false,
-            has_struct_lt: false,
-        }
+        RepairResult::failed()
     }
 
     fn repair_file(&self, file_name: &str, new_file_name: &str) -> RepairResult {
         self.repair_function(file_name, new_file_name, "")
     }
 
-    fn repair_function(&self, file_name: &str, new_file_name: &str, fn_name: &str) -> RepairResult {
-        let args: Vec<&str> = vec!["--error-format=json"];
-        fs::copy(file_name, &new_file_name).unwrap();
-
-        let mut compile_cmd = compile_file(&new_file_name, &args);
-
-        let process_errors = |stderr: &str| {
-            repair_bounds_help(stderr, new_file_name, fn_name)
-                || repair_standard_help(stderr, new_file_name)
-        };
+    fn repair_function_with_config(
+        &self,
+        file_name: &str,
+        new_file_name: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult {
+        let any_error = |_: &RustcError| true;
+        let passes = [
+            RepairPass {
+                name: "bounds",
+                matches: &any_error,
+                repair: &|ce: &RustcError| {
+                    repair_bounds_help_with_config(
+                        ce.rendered.as_str(),
+                        new_file_name,
+                        fn_name,
+                        config,
+                    )
+                },
+            },
+            RepairPass {
+                name: "standard",
+                matches: &any_error,
+                repair: &|ce: &RustcError| {
+                    repair_standard_help_with_config(ce.rendered.as_str(), new_file_name, config)
+                },
+            },
+        ];
 
-        repair_iteration(&mut compile_cmd, &process_errors, true, None)
+        default_repair_function(file_name, new_file_name, fn_name, &passes, config)
     }
 
     fn clone_box(&self) -> Box<dyn RepairSystem> {
@@ -1,17 +1,20 @@
 use proc_macro2::Span;
-use quote::ToTokens;
 use regex::Regex;
 
 use log::debug;
-use std::fs;
-use syn::{visit_mut::VisitMut, FnArg, Lifetime, LifetimeDef, Type};
+use std::process::Command;
+use syn::{visit_mut::VisitMut, FnArg, ItemMod, Lifetime, LifetimeDef, Type};
 
 use crate::common::{
-    callee_renamer, elide_lifetimes_annotations, repair_bounds_help, repair_iteration,
-    repair_iteration_project, RepairResult, RepairSystem, RustcError,
+    default_repair_function_with_steps, default_repair_project,
+    elide_lifetimes_annotations_with_config, format_source_like, has_fixed_signature, print_edited,
+    print_source, read_target, rename_and_elide_with_config, repair_bounds_help_with_config,
+    repair_note_bounds_with_config, write_file, FnSelector, RepairPass, RepairResult, RepairSteps,
+    RepairSystem, RustcError, Scope,
 };
+use crate::config::RepairConfig;
+use crate::error::RepairError;
 use crate::repair_lifetime_simple;
-use rem_utils::{check_project, compile_file, format_source};
 
 #[derive(Debug, Clone)]
 pub struct Repairer {}
@@ -21,74 +24,101 @@ impl RepairSystem for Repairer {
         "_tightest_bounds_first_repairer"
     }
 
-    fn repair_project(&self, src_path: &str, manifest_path: &str, fn_name: &str) -> RepairResult {
-        annotate_tight_named_lifetime(src_path, fn_name);
-        let mut compile_cmd = check_project(manifest_path, &vec![]);
-        let process_errors = |ce: &RustcError| {
-            if repair_bounds_help(ce.rendered.as_str(), src_path, fn_name) {
-                true
-            } else {
-                loosen_bounds(ce.rendered.as_str(), src_path, fn_name)
-            }
+    fn default_config(&self, label: &str) -> RepairConfig {
+        RepairConfig::builder()
+            .max_iterations(50)
+            .label(label)
+            .build()
+    }
+
+    fn repair_project_with_config(
+        &self,
+        src_path: &str,
+        manifest_path: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult {
+        let prepare = |src_path: &str| {
+            annotate_tight_named_lifetime(src_path, fn_name)?;
+            Ok(true)
         };
-        match repair_iteration_project(&mut compile_cmd, src_path, &process_errors, true, Some(50))
-        {
-            RepairResult {
-                success: true,
-                repair_count,
-                ..
-            } => {
-                debug!("pre elision: {}", fs::read_to_string(&src_path).unwrap());
-                let elide_res = elide_lifetimes_annotations(src_path, fn_name);
-                callee_renamer(src_path, fn_name);
-                RepairResult {
-                    success: true,
-                    repair_count,
-                    has_non_elidible_lifetime: elide_res.annotations_left,
-                    has_struct_lt: elide_res.has_struct_lt,
-                }
-            }
-            result => result,
-        }
+        let finish = |src_path: &str, compile_cmd: &mut Command, result: &mut RepairResult| {
+            let (pre_elision, _) = read_target(src_path, fn_name)?;
+            debug!("[{}] pre elision: {}", config.label, pre_elision);
+            let elide_res = rename_and_elide_with_config(
+                src_path,
+                fn_name,
+                "____EXTRACT_THIS",
+                config,
+                Some(compile_cmd),
+            )?;
+            elide_res.report_annotations_left(&config.label);
+            result.has_non_elidible_lifetime = elide_res.annotations_left;
+            result.has_struct_lt = elide_res.has_struct_lt;
+            Ok(())
+        };
+        let steps = RepairSteps {
+            prepare: &prepare,
+            finish: &finish,
+        };
+        let bounds = |ce: &RustcError| {
+            repair_bounds_help_with_config(ce.rendered.as_str(), src_path, fn_name, config)
+        };
+        let notes = |ce: &RustcError| {
+            repair_note_bounds_with_config(ce.rendered.as_str(), src_path, fn_name, config)
+        };
+        let loosen = |ce: &RustcError| loosen_bounds(ce.rendered.as_str(), src_path, fn_name);
+        let passes = bound_passes(&bounds, &notes, &loosen);
+        default_repair_project(src_path, manifest_path, fn_name, &passes, &steps, config)
     }
 
     fn repair_file(&self, file_name: &str, new_file_name: &str) -> RepairResult {
         repair_lifetime_simple::Repairer {}.repair_file(file_name, new_file_name)
     }
 
-    fn repair_function(&self, file_name: &str, new_file_name: &str, fn_name: &str) -> RepairResult {
-        fs::copy(file_name, &new_file_name).unwrap();
-        annotate_tight_named_lifetime(&new_file_name, fn_name);
-        //println!("annotated: {}", fs::read_to_string(&new_file_name).unwrap());
-        let args: Vec<&str> = vec!["--error-format=json"];
-
-        let mut compile_cmd = compile_file(&new_file_name, &args);
-
-        let process_errors = |stderr: &str| {
-            if repair_bounds_help(stderr, new_file_name, fn_name) {
-                true
-            } else {
-                loosen_bounds(stderr, new_file_name, fn_name)
-            }
+    fn repair_function_with_config(
+        &self,
+        file_name: &str,
+        new_file_name: &str,
+        fn_name: &str,
+        config: &RepairConfig,
+    ) -> RepairResult {
+        let prepare = |new_file_name: &str| {
+            annotate_tight_named_lifetime(new_file_name, fn_name)?;
+            Ok(true)
         };
-
-        match repair_iteration(&mut compile_cmd, &process_errors, true, Some(50)) {
-            RepairResult {
-                success: true,
-                repair_count,
-                ..
-            } => {
-                // println!("repaired: {}", fs::read_to_string(&new_file_name).unwrap());
-                let elide_res = elide_lifetimes_annotations(new_file_name, fn_name);
-                RepairResult {
-                    success: true,
-                    repair_count,
-                    has_non_elidible_lifetime: elide_res.annotations_left,
-                    has_struct_lt: elide_res.has_struct_lt,
-                }
-            }
-            result => result,
-        }
+        let finish = |new_file_name: &str, compile_cmd: &mut Command, result: &mut RepairResult| {
+            let elide_res = elide_lifetimes_annotations_with_config(
+                new_file_name,
+                fn_name,
+                config,
+                Some(compile_cmd),
+            )?;
+            elide_res.report_annotations_left(&config.label);
+            result.has_non_elidible_lifetime = elide_res.annotations_left;
+            result.has_struct_lt = elide_res.has_struct_lt;
+            Ok(())
+        };
+        let steps = RepairSteps {
+            prepare: &prepare,
+            finish: &finish,
+        };
+        let bounds = |ce: &RustcError| {
+            repair_bounds_help_with_config(ce.rendered.as_str(), new_file_name, fn_name, config)
+        };
+        let notes = |ce: &RustcError| {
+            repair_note_bounds_with_config(ce.rendered.as_str(), new_file_name, fn_name, config)
+        };
+        let loosen = |ce: &RustcError| loosen_bounds(ce.rendered.as_str(), new_file_name, fn_name);
+        let passes = bound_passes(&bounds, &notes, &loosen);
+        default_repair_function_with_steps(
+            file_name,
+            new_file_name,
+            fn_name,
+            &passes,
+            &steps,
+            config,
+        )
     }
 
     fn clone_box(&self) -> Box<dyn RepairSystem> {
@@ -96,6 +126,32 @@ impl RepairSystem for Repairer {
     }
 }
 
+// the bounds rustc suggests first, then those read off the notes, loosening the annotated bounds
+// only when neither helps
+fn bound_passes<'a>(
+    bounds: &'a dyn Fn(&RustcError) -> Result<bool, RepairError>,
+    notes: &'a dyn Fn(&RustcError) -> Result<bool, RepairError>,
+    loosen: &'a dyn Fn(&RustcError) -> Result<bool, RepairError>,
+) -> [RepairPass<'a>; 3] {
+    [
+        RepairPass {
+            name: "bounds",
+            matches: &|_| true,
+            repair: bounds,
+        },
+        RepairPass {
+            name: "notes",
+            matches: &|_| true,
+            repair: notes,
+        },
+        RepairPass {
+            name: "loosen",
+            matches: &|_| true,
+            repair: loosen,
+        },
+    ]
+}
+
 struct TightLifetimeAnnotatorTypeHelper {}
 
 impl VisitMut for TightLifetimeAnnotatorTypeHelper {
@@ -131,14 +187,24 @@ impl VisitMut for TightLifetimeAnnotatorFnArgHelper {
 
 struct TightLifetimeAnnotator<'a> {
     fn_name: &'a str,
+    // only ever modules, methods are left alone
+    scope: Scope<'static>,
     success: bool,
 }
 
 impl VisitMut for TightLifetimeAnnotator<'_> {
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
     fn visit_item_fn_mut(&mut self, i: &mut syn::ItemFn) {
-        let id = i.sig.ident.to_string();
-        match id == self.fn_name.to_string() {
+        let enclosing = self.scope.enclosing();
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, enclosing) {
             false => (),
+            // left as it is, like a function without inputs
+            true if has_fixed_signature(&i.attrs, &i.sig, enclosing) => self.success = true,
             true => match (&mut i.sig.inputs, &mut i.sig.generics, &mut i.sig.output) {
                 (inputs, _, _) if inputs.len() == 0 => self.success = true,
                 (_, gen, _)
@@ -174,31 +240,32 @@ impl VisitMut for TightLifetimeAnnotator<'_> {
                 }
             },
         }
+        syn::visit_mut::visit_item_fn_mut(self, i);
     }
 }
 
-pub fn annotate_tight_named_lifetime(new_file_name: &str, fn_name: &str) -> bool {
-    let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-    let mut file = syn::parse_str::<syn::File>(file_content.as_str())
-        .map_err(|e| format!("{:?}", e))
-        .unwrap();
+pub fn annotate_tight_named_lifetime(
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<bool, RepairError> {
+    let (file_content, mut file) = read_target(new_file_name, fn_name)?;
     let mut visit = TightLifetimeAnnotator {
         fn_name,
+        scope: Scope::new(None),
         success: false,
     };
     visit.visit_file_mut(&mut file);
-    let file = file.into_token_stream().to_string();
-    match visit.success {
-        true => {
-            fs::write(new_file_name.to_string(), format_source(&file)).unwrap();
-            true
-        }
-        false => false,
+    let file = print_source(&file);
+    if visit.success {
+        write_file(new_file_name, &format_source_like(&file_content, &file))?;
     }
+    Ok(visit.success)
 }
 
 struct BoundsLoosener<'a> {
     fn_name: &'a str,
+    // only ever modules, methods are left alone
+    scope: Scope<'static>,
     arg_name: &'a str,
     success: bool,
 }
@@ -230,10 +297,17 @@ impl VisitMut for ArgBoundLoosener<'_> {
 }
 
 impl VisitMut for BoundsLoosener<'_> {
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        let outer = self.scope.enter_mod(i);
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.leave(outer);
+    }
+
     fn visit_item_fn_mut(&mut self, i: &mut syn::ItemFn) {
-        let id = i.sig.ident.to_string();
-        match id == self.fn_name.to_string() {
+        let enclosing = self.scope.enclosing();
+        match FnSelector::parse(self.fn_name).matches(&i.sig.ident, enclosing) {
             false => (),
+            true if has_fixed_signature(&i.attrs, &i.sig, enclosing) => (),
             true => {
                 let mut lt_count = 0;
                 let gen = &mut i.sig.generics;
@@ -266,10 +340,15 @@ impl VisitMut for BoundsLoosener<'_> {
                 }
             }
         }
+        syn::visit_mut::visit_item_fn_mut(self, i);
     }
 }
 
-pub fn loosen_bounds(stderr: &str, new_file_name: &str, fn_name: &str) -> bool {
+pub fn loosen_bounds(
+    stderr: &str,
+    new_file_name: &str,
+    fn_name: &str,
+) -> Result<bool, RepairError> {
     let deserializer = serde_json::Deserializer::from_str(stderr);
     let stream = deserializer.into_iter::<RustcError>();
     let mut helped = false;
@@ -283,25 +362,23 @@ pub fn loosen_bounds(stderr: &str, new_file_name: &str, fn_name: &str) -> bool {
 
         for captured in error_lines {
             //println!("ref_full: {}, ref: {}", &captured["ref_full"], &captured["ref"]);
-            let file_content: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-            let mut file = syn::parse_str::<syn::File>(file_content.as_str())
-                .map_err(|e| format!("{:?}", e))
-                .unwrap();
+            let (file_content, mut file) = read_target(new_file_name, fn_name)?;
             let mut visit = BoundsLoosener {
                 fn_name,
+                scope: Scope::new(None),
                 arg_name: &captured["ref"],
                 success: false,
             };
             visit.visit_file_mut(&mut file);
-            let file = file.into_token_stream().to_string();
+            let file = print_edited(&file_content, &file);
             match visit.success {
                 true => {
-                    fs::write(new_file_name.to_string(), format_source(&file)).unwrap();
+                    write_file(new_file_name, &file)?;
                     helped = true
                 }
                 false => (),
             }
         }
     }
-    helped
+    Ok(helped)
 }
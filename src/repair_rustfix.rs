@@ -1,7 +1,12 @@
-use crate::common::{repair_iteration, RepairResult, RepairSystem};
+use crate::common::{
+    default_repair_function_with_processor, write_source, RepairResult, RepairSteps, RepairSystem,
+};
+use crate::config::RepairConfig;
+use crate::error::RepairError;
 
-use rem_utils::compile_file;
+use log::warn;
 use std::collections::HashSet;
+use std::error::Error;
 use std::fs;
 
 #[derive(Debug, Clone)]
@@ -12,53 +17,65 @@ impl RepairSystem for Repairer {
         "_rustfix_repairer"
     }
 
-    fn repair_project(
+    fn repair_project_with_config(
         &self,
         _src_path: &str,
         _manifest_path: &str,
         _fn_name: &str,
+        _config: &RepairConfig,
     ) -> RepairResult {
-        RepairResult {
-            success: false,
-            repair_count: 0,
-            has_non_elidible_lifetime: false,
-            has_struct_lt: false,
-        }
+        RepairResult::failed()
     }
 
     fn repair_file(&self, file_name: &str, new_file_name: &str) -> RepairResult {
-        fs::copy(file_name, &new_file_name).unwrap();
-        let args = vec!["--error-format=json"];
-
-        let mut compile_cmd = compile_file(&new_file_name, &args);
+        self.repair_function(file_name, new_file_name, "")
+    }
 
+    fn repair_function_with_config(
+        &self,
+        file_name: &str,
+        new_file_name: &str,
+        _: &str,
+        config: &RepairConfig,
+    ) -> RepairResult {
         let process_errors = |stderr: &str| {
-            let suggestions = rustfix::get_suggestions_from_json(
-                stderr,
-                &HashSet::new(),
-                rustfix::Filter::Everything,
-            )
-            .expect("rustfix failed to run on error json");
-
-            if suggestions.len() == 0 {
-                return false;
-            }
-
-            let code: String = fs::read_to_string(&new_file_name).unwrap().parse().unwrap();
-            let fixed = rustfix::apply_suggestions(&code, &suggestions)
-                .expect("could not apply suggestions");
-            fs::write(new_file_name.to_string(), fixed).unwrap();
-            true
+            apply_rustfix(stderr, new_file_name, config).unwrap_or_else(|e| {
+                warn!("[{}] rustfix failed: {}", config.label, e);
+                false
+            })
         };
 
-        repair_iteration(&mut compile_cmd, &process_errors, true, None)
-    }
-
-    fn repair_function(&self, file_name: &str, new_file_name: &str, _: &str) -> RepairResult {
-        self.repair_file(file_name, new_file_name)
+        // rustfix applies every suggestion wherever it points, the whole file is the target
+        default_repair_function_with_processor(
+            file_name,
+            new_file_name,
+            "",
+            &process_errors,
+            &RepairSteps::none(),
+            config,
+        )
     }
 
     fn clone_box(&self) -> Box<dyn RepairSystem> {
         Box::new((*self).clone())
     }
 }
+
+// applies the suggestions in the json `stderr` to `new_file_name`, returns whether there were any
+fn apply_rustfix(
+    stderr: &str,
+    new_file_name: &str,
+    config: &RepairConfig,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let suggestions =
+        rustfix::get_suggestions_from_json(stderr, &HashSet::new(), rustfix::Filter::Everything)?;
+
+    if suggestions.len() == 0 {
+        return Ok(false);
+    }
+
+    let code = fs::read_to_string(new_file_name).map_err(|e| RepairError::io(new_file_name, e))?;
+    let fixed = rustfix::apply_suggestions(&code, &suggestions)?;
+    write_source(new_file_name, &fixed, config)?;
+    Ok(true)
+}
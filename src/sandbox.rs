@@ -0,0 +1,189 @@
+use log::debug;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::common::{RepairResult, RepairSystem};
+use crate::config::{Edition, RepairConfig};
+
+fn sandbox_manifest(edition: Edition) -> String {
+    format!(
+        r#"[package]
+name = "rem_sandbox"
+version = "0.1.0"
+edition = "{}"
+
+[lib]
+path = "src/lib.rs"
+
+[workspace]
+"#,
+        edition.as_str()
+    )
+}
+
+static SANDBOX_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A throwaway cargo crate, removed again when dropped
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    fn create(base: &Path, edition: Edition) -> io::Result<Self> {
+        let dir = base.join(format!(
+            "rem-repairer-{}-{}",
+            process::id(),
+            SANDBOX_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(dir.join("src"))?;
+        let sandbox = Sandbox { dir };
+        fs::write(sandbox.manifest_path(), sandbox_manifest(edition))?;
+        Ok(sandbox)
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.dir.join("Cargo.toml")
+    }
+
+    fn src_path(&self) -> PathBuf {
+        self.dir.join("src").join("lib.rs")
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            debug!("could not remove sandbox {:?}: {}", self.dir, e);
+        }
+    }
+}
+
+fn path_str(path: &Path) -> io::Result<&str> {
+    path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("sandbox path {:?} is not valid UTF-8", path),
+        )
+    })
+}
+
+/**
+Repair `fn_name` in `source` as the library of a temporary crate and return the repaired source,
+without touching any of the caller's files. The crate is removed afterwards, also on failure
+
+The crate goes in `config.sandbox_dir`, the system's temp dir by default: point it at fast local
+storage (a tmpfs) when the temp dir is slow or short on space
+*/
+pub fn repair_in_sandbox(
+    repair_system: &dyn RepairSystem,
+    source: &str,
+    fn_name: &str,
+    config: &RepairConfig,
+) -> io::Result<(String, RepairResult)> {
+    let base = config.sandbox_dir.clone().unwrap_or_else(env::temp_dir);
+    let sandbox = Sandbox::create(&base, config.edition)?;
+    debug!("[{}] repairing in sandbox {:?}", config.label, sandbox.dir);
+    let src_path = sandbox.src_path();
+    fs::write(&src_path, source)?;
+    let result = repair_system.repair_project_with_config(
+        path_str(&src_path)?,
+        path_str(&sandbox.manifest_path())?,
+        fn_name,
+        config,
+    );
+    let repaired = fs::read_to_string(&src_path)?;
+    Ok((repaired, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repair_lifetime_tightest_bound_first::Repairer;
+
+    #[test]
+    fn sandboxed_repair_returns_the_repaired_source() {
+        let source = include_str!("../input/lifetime_bounds.rs");
+        let base = env::temp_dir().join(format!("rem-repairer-test-{}-sandboxed", process::id()));
+        let config = RepairConfig::builder().sandbox_dir(&base).build();
+        let (repaired, result) =
+            repair_in_sandbox(&Repairer {}, source, "bar_extracted", &config).unwrap();
+        let left = fs::read_dir(&base).unwrap().count();
+        fs::remove_dir_all(&base).unwrap();
+        assert_eq!(left, 0);
+        assert!(result.success);
+        assert!(result.repair_count > 0);
+        assert!(
+            repaired.contains("fn bar_extracted<'lt0, 'lt1>(p: &'lt1 mut &'lt0 i32, x: &'lt0 i32)"),
+            "{}",
+            repaired
+        );
+    }
+
+    // notes in the source where it was handed the crate
+    #[derive(Debug, Clone)]
+    struct ReportsPaths;
+
+    impl RepairSystem for ReportsPaths {
+        fn name(&self) -> &str {
+            "reports_paths"
+        }
+
+        fn repair_project_with_config(
+            &self,
+            src_path: &str,
+            manifest_path: &str,
+            _: &str,
+            _: &RepairConfig,
+        ) -> RepairResult {
+            let source = fs::read_to_string(src_path).unwrap();
+            let report = format!("{}\n{}\n{}\n", source, src_path, manifest_path);
+            fs::write(src_path, report).unwrap();
+            RepairResult::failed()
+        }
+
+        fn repair_file(&self, _: &str, _: &str) -> RepairResult {
+            RepairResult::failed()
+        }
+
+        fn repair_function_with_config(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+            _: &RepairConfig,
+        ) -> RepairResult {
+            RepairResult::failed()
+        }
+
+        fn clone_box(&self) -> Box<dyn RepairSystem> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn sandbox_is_created_under_the_sandbox_dir() {
+        let base = env::temp_dir().join(format!("rem-repairer-test-{}-sandbox-dir", process::id()));
+        let config = RepairConfig::builder().sandbox_dir(&base).build();
+        let (repaired, _) =
+            repair_in_sandbox(&ReportsPaths, "fn main() {}", "main", &config).unwrap();
+        let left = fs::read_dir(&base).unwrap().count();
+        fs::remove_dir_all(&base).unwrap();
+        assert_eq!(left, 0);
+        let paths: Vec<&Path> = repaired.lines().skip(1).map(Path::new).collect();
+        assert_eq!(paths.len(), 2);
+        for path in &paths {
+            assert!(
+                path.starts_with(&base),
+                "{:?} is not under {:?}",
+                path,
+                base
+            );
+        }
+        assert!(paths[0].ends_with("src/lib.rs"));
+        assert!(paths[1].ends_with("Cargo.toml"));
+    }
+}